@@ -0,0 +1,486 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! An asynchronous client, enabled with the `async` feature.
+//!
+//! `AsyncClient` has the same operations as `Client`, using the same operation
+//! builders, but their `send` methods are `async`.  Nodes, sniffing and retries
+//! work in the same way.
+//!
+//! The underlying HTTP library requires a Tokio 0.1 runtime, the futures
+//! returned can be run on one using the `compat` layer of the `futures` crate.
+//!
+//! A [`Transport`](../transport/trait.Transport.html) given to the builder, or
+//! a cassette, is also used by an `AsyncClient`.  As a `Transport` is
+//! synchronous it is called directly, blocking the task until it responds;
+//! this is intended for tests, e.g. with a `MockTransport`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::compat::Future01CompatExt;
+use futures01::Stream as Stream01;
+
+use reqwest::{header::CONTENT_TYPE, r#async::Client as HttpClient, Method, StatusCode, Url};
+
+use serde::ser::Serialize;
+
+use tokio_timer::Delay;
+
+use crate::{
+    error::{self, EsError},
+    pool::{Node, NodePool},
+    retry::{self, Retry, RetryPolicy},
+    sniff::{self, NodesInfo},
+    transport::{Transport, TransportRequest, TransportResponse},
+    EsResponse,
+};
+
+/// Read the body of a response in full
+async fn read_response(response: reqwest::r#async::Response) -> Result<TransportResponse, EsError> {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().concat2().compat().await?;
+    Ok(TransportResponse::new(status, body.to_vec()).with_headers(headers))
+}
+
+/// The asynchronous equivalent of [`Client`](../struct.Client.html).
+///
+/// To create an `AsyncClient` use
+/// [`ClientBuilder::build_async`](../builder/struct.ClientBuilder.html#method.build_async).
+/// Cloning an `AsyncClient` is cheap, clones share the same connections and
+/// nodes.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rs_es::ClientBuilder;
+///
+/// # async fn example() -> Result<(), rs_es::error::EsError> {
+/// let client = ClientBuilder::new("http://localhost:9200").build_async()?;
+/// let version = client.version().send().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncClient {
+    nodes: Arc<NodePool>,
+    transport: AsyncTransport,
+    retry_policy: RetryPolicy,
+}
+
+/// How an `AsyncClient` sends requests
+#[derive(Debug, Clone)]
+pub(crate) enum AsyncTransport {
+    /// Asynchronously, over HTTP
+    Http(HttpClient),
+
+    /// With a synchronous `Transport`, e.g. in tests
+    Blocking(Arc<dyn Transport>),
+}
+
+impl AsyncTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, EsError> {
+        match self {
+            AsyncTransport::Http(http_client) => {
+                let url = request.url().clone();
+                let mut builder = http_client.request(request.method().clone(), url.clone());
+                if !url.username().is_empty() {
+                    builder = builder.basic_auth(url.username(), url.password());
+                }
+                if let Some(body) = request.body() {
+                    builder = builder.body(body.to_vec());
+                }
+                let response = builder
+                    .header(CONTENT_TYPE, "application/json")
+                    .send()
+                    .compat()
+                    .await?;
+                read_response(response).await
+            }
+            AsyncTransport::Blocking(transport) => transport.send(request),
+        }
+    }
+}
+
+/// Wait before retrying a request
+pub(crate) async fn wait(backoff: Duration) {
+    if let Err(err) = Delay::new(Instant::now() + backoff).compat().await {
+        log::error!("Cannot wait before retrying: {}", err);
+    }
+}
+
+/// Create an async HTTP function for the given method, either with a fixed
+/// `Retry`, or taking a `Retry` argument
+macro_rules! async_es_op {
+    ($n:ident,$cn:ident,$retry:expr) => {
+        pub(crate) async fn $n(&self, url: &str) -> Result<TransportResponse, EsError> {
+            log::info!("Doing {} on {}", stringify!($n), url);
+            self.do_es_op(url, $retry, Method::$cn, None).await
+        }
+    };
+    ($n:ident,$cn:ident) => {
+        pub(crate) async fn $n(
+            &self,
+            url: &str,
+            retry: Retry<'_>,
+        ) -> Result<TransportResponse, EsError> {
+            log::info!("Doing {} on {}", stringify!($n), url);
+            self.do_es_op(url, retry, Method::$cn, None).await
+        }
+    };
+}
+
+/// Create an async HTTP function with a request body for the given method,
+/// either with a fixed `Retry`, or taking a `Retry` argument
+macro_rules! async_es_body_op {
+    ($n:ident,$cn:ident,$retry:expr) => {
+        pub(crate) async fn $n<E>(&self, url: &str, body: &E) -> Result<TransportResponse, EsError>
+        where
+            E: Serialize,
+        {
+            log::info!("Doing {} on {}", stringify!($n), url);
+            self.do_es_body_op(url, body, $retry, Method::$cn).await
+        }
+    };
+    ($n:ident,$cn:ident) => {
+        pub(crate) async fn $n<E>(
+            &self,
+            url: &str,
+            body: &E,
+            retry: Retry<'_>,
+        ) -> Result<TransportResponse, EsError>
+        where
+            E: Serialize,
+        {
+            log::info!("Doing {} on {}", stringify!($n), url);
+            self.do_es_body_op(url, body, retry, Method::$cn).await
+        }
+    };
+}
+
+impl AsyncClient {
+    pub(crate) fn new(
+        nodes: NodePool,
+        transport: AsyncTransport,
+        retry_policy: RetryPolicy,
+    ) -> AsyncClient {
+        AsyncClient {
+            nodes: Arc::new(nodes),
+            transport,
+            retry_policy,
+        }
+    }
+
+    /// Take a nearly complete ElasticSearch URL, and stick
+    /// the URL of the first node on the front.
+    pub fn full_url(&self, suffix: &str) -> Url {
+        self.nodes
+            .first()
+            .url()
+            .join(suffix)
+            .expect("Invalid URL created")
+    }
+
+    /// The URLs of each node currently thought to be alive
+    pub fn live_nodes(&self) -> Vec<Url> {
+        self.nodes.live_urls()
+    }
+
+    /// Discover the nodes in the cluster, see
+    /// [`Client::sniff_nodes`](../struct.Client.html#method.sniff_nodes).
+    pub async fn sniff_nodes(&self) -> Result<Vec<Url>, EsError> {
+        // Not via `do_es_op`, as that may itself sniff
+        let response = self
+            .send_to_node("/_nodes/http", &Method::GET, &None)
+            .await
+            .and_then(crate::do_req)?;
+        match response.status_code() {
+            StatusCode::OK => (),
+            _ => return Err(error::unexpected_status(response)),
+        }
+        let info: NodesInfo = response.read_response()?;
+
+        let urls = sniff::discovered_urls(self.nodes.first().url(), &info);
+        log::info!("Sniffed nodes: {:?}", urls);
+        self.nodes.set_nodes(urls.clone());
+        Ok(urls)
+    }
+
+    async fn sniff_if_due(&self) {
+        if self.nodes.sniff_due() {
+            if let Err(err) = self.sniff_nodes().await {
+                log::warn!("Failed to sniff nodes: {}", err);
+            }
+            self.nodes.sniff_done();
+        }
+    }
+
+    /// Send a request, retrying according to the retry policy if the request is
+    /// safe to repeat.
+    pub(crate) async fn do_es_op(
+        &self,
+        url: &str,
+        retry: Retry<'_>,
+        method: Method,
+        body: Option<Vec<u8>>,
+    ) -> Result<TransportResponse, EsError> {
+        self.sniff_if_due().await;
+
+        let policy = match retry {
+            Retry::Never => None,
+            Retry::Default => Some(&self.retry_policy),
+            Retry::Policy(policy) => Some(policy),
+        };
+        let mut attempt = 1;
+        loop {
+            let result = self.send_to_node(url, &method, &body).await;
+            if let Some(policy) = policy {
+                if attempt < policy.max_attempts() && retry::is_retryable(&result) {
+                    let backoff = policy.backoff(attempt);
+                    log::warn!(
+                        "Retrying {} in {:?}, attempt {} failed",
+                        url,
+                        backoff,
+                        attempt
+                    );
+                    wait(backoff).await;
+                    attempt += 1;
+                    continue;
+                }
+            }
+            return result.and_then(crate::do_req);
+        }
+    }
+
+    /// Send a request to the next available node, see `Client::send_to_node`.
+    async fn send_to_node(
+        &self,
+        url: &str,
+        method: &Method,
+        body: &Option<Vec<u8>>,
+    ) -> Result<TransportResponse, EsError> {
+        let mut last_error = None;
+        for _ in 0..self.nodes.len() {
+            let selected = self.nodes.next_node();
+            let node = &selected.node;
+            if selected.needs_ping && !self.ping(node).await {
+                continue;
+            }
+
+            let request = TransportRequest::new(
+                method.clone(),
+                node.url().join(url).expect("Invalid URL created"),
+                body.clone(),
+            );
+            match self.transport.send(request).await {
+                Ok(response) => {
+                    self.nodes.mark_alive(node);
+                    return Ok(response);
+                }
+                Err(err) => {
+                    if !error::is_connection_error(&err) {
+                        return Err(err);
+                    }
+                    self.nodes.mark_dead(node);
+                    if !error::is_connection_refused(&err) {
+                        // The request may have reached the server, so it is
+                        // not safe to send it again
+                        return Err(err);
+                    }
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| EsError::EsError("No live nodes available".to_owned())))
+    }
+
+    /// Check a previously dead node, marking it alive if it responds
+    async fn ping(&self, node: &Node) -> bool {
+        let request = TransportRequest::new(Method::HEAD, node.url().clone(), None);
+        match self.transport.send(request).await {
+            Ok(ref response) if response.status().is_success() => {
+                self.nodes.mark_alive(node);
+                true
+            }
+            _ => {
+                self.nodes.mark_dead(node);
+                false
+            }
+        }
+    }
+
+    async fn do_es_body_op<E>(
+        &self,
+        url: &str,
+        body: &E,
+        retry: Retry<'_>,
+        method: Method,
+    ) -> Result<TransportResponse, EsError>
+    where
+        E: Serialize,
+    {
+        let json_string = serde_json::to_string(body)?;
+        log::debug!("With body: {}", &json_string);
+
+        self.do_es_op(url, retry, method, Some(json_string.into_bytes()))
+            .await
+    }
+
+    async_es_op!(get_op, GET, Retry::Default);
+    async_es_op!(get_op_with_retry, GET);
+
+    async_es_op!(post_op, POST, Retry::Never);
+    async_es_body_op!(post_body_op, POST, Retry::Never);
+    async_es_body_op!(post_body_op_with_retry, POST);
+    async_es_op!(put_op, PUT, Retry::Default);
+    async_es_body_op!(put_body_op, PUT, Retry::Default);
+    async_es_op!(delete_op, DELETE, Retry::Default);
+    async_es_op!(head_op, HEAD, Retry::Default);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::future::Future;
+
+    use futures::{FutureExt, TryFutureExt};
+
+    use reqwest::{Method, Url};
+
+    use tokio::runtime::current_thread::Runtime;
+
+    use crate::{
+        retry::RetryPolicy,
+        tests::{make_mock_async_client, stub_server},
+        transport::MockTransport,
+        ClientBuilder,
+    };
+
+    /// Run a future to completion on a Tokio 0.1 runtime
+    pub fn block_on<F, T, E>(future: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        Runtime::new()
+            .unwrap()
+            .block_on(future.boxed_local().compat())
+    }
+
+    const COUNT_RESULT: &str =
+        "{\"count\":3,\"_shards\":{\"total\":1,\"successful\":1,\"failed\":0}}";
+
+    #[test]
+    fn test_send() {
+        let url = stub_server(|method, path| {
+            assert_eq!("GET", method);
+            assert_eq!("/test_idx/_count", path);
+            (200, COUNT_RESULT.to_owned())
+        });
+        let client = ClientBuilder::new(url).build_async().unwrap();
+
+        let result = block_on(client.count_uri().with_indexes(&["test_idx"]).send()).unwrap();
+        assert_eq!(3, result.count);
+    }
+
+    #[test]
+    fn test_server_error() {
+        let url = stub_server(|_, _| (500, "{\"error\":\"broken\"}".to_owned()));
+        let client = ClientBuilder::new(url).build_async().unwrap();
+
+        let err = block_on(client.count_uri().send()).unwrap_err();
+        assert_eq!(
+            "500 Internal Server Error - {\"error\":\"broken\"}",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_failover_and_retry() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+        use std::time::Duration;
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let server_count = count.clone();
+        let live = stub_server(move |_, _| {
+            if server_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                (503, "{}".to_owned())
+            } else {
+                (200, COUNT_RESULT.to_owned())
+            }
+        });
+        let client = ClientBuilder::new("http://127.0.0.1:1")
+            .with_node(live.as_str())
+            .with_retry_policy(
+                RetryPolicy::default()
+                    .with_max_attempts(2)
+                    .with_initial_backoff(Duration::from_millis(1)),
+            )
+            .build_async()
+            .unwrap();
+
+        let result = block_on(client.count_uri().send()).unwrap();
+        assert_eq!(3, result.count);
+        assert_eq!(2, count.load(Ordering::SeqCst));
+        assert_eq!(vec![Url::parse(&live).unwrap()], client.live_nodes());
+    }
+
+    #[test]
+    fn test_sniff_on_start() {
+        let seed = stub_server(|_, path| {
+            assert_eq!("/_nodes/http", path);
+            (
+                200,
+                "{\"nodes\":{\"a\":{\"http\":{\"publish_address\":\"127.0.0.1:1\"}}}}".to_owned(),
+            )
+        });
+        let client = ClientBuilder::new(seed)
+            .with_sniff_on_start(true)
+            .build_async()
+            .unwrap();
+
+        // The first request sniffs, then fails as the discovered node does not
+        // exist
+        assert!(block_on(client.get_op("/")).is_err());
+        assert_eq!(1, client.nodes.len());
+        assert_eq!("http://127.0.0.1:1/", client.nodes.first().url().as_str());
+    }
+
+    #[test]
+    fn test_mock_transport() {
+        let transport = MockTransport::new()
+            .with_response(Method::GET, "/test_idx/_count", 200, COUNT_RESULT)
+            .with_response(Method::GET, "/broken_idx/_count", 500, "{}");
+        let client = make_mock_async_client(&transport);
+
+        let result = block_on(client.count_uri().with_indexes(&["test_idx"]).send()).unwrap();
+        assert_eq!(3, result.count);
+        let err = block_on(client.count_uri().with_indexes(&["broken_idx"]).send()).unwrap_err();
+        assert_eq!(
+            Some(reqwest::StatusCode::INTERNAL_SERVER_ERROR),
+            err.server_error().map(|err| err.status)
+        );
+
+        let paths: Vec<String> = transport
+            .requests()
+            .iter()
+            .map(|request| request.path().to_owned())
+            .collect();
+        assert_eq!(vec!["/test_idx/_count", "/broken_idx/_count"], paths);
+    }
+}
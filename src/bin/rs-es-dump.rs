@@ -0,0 +1,131 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Dump the documents of an index to a file, or restore them.
+//!
+//! ```text
+//! rs-es-dump [--url URL] dump INDEX FILE
+//! rs-es-dump [--url URL] [--refresh] restore INDEX FILE
+//! ```
+//!
+//! The file `-` is standard output or input.  See `rs_es::operations::dump`
+//! for the format.
+
+use std::env;
+use std::io;
+use std::process;
+
+use rs_es::{error::EsError, Client};
+
+const USAGE: &str = "Usage:
+    rs-es-dump [--url URL] dump INDEX FILE
+    rs-es-dump [--url URL] [--refresh] restore INDEX FILE
+
+Options:
+    --url URL    The ElasticSearch server [default: http://localhost:9200]
+    --refresh    Refresh the index once restored
+";
+
+#[derive(Debug)]
+struct Args {
+    url: String,
+    refresh: bool,
+    command: String,
+    index: String,
+    file: String,
+}
+
+fn parse_args() -> Option<Args> {
+    let mut url = "http://localhost:9200".to_owned();
+    let mut refresh = false;
+    let mut positional = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--url" => url = args.next()?,
+            "--refresh" => refresh = true,
+            _ if arg.starts_with("--") => return None,
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() != 3 {
+        return None;
+    }
+    let file = positional.pop()?;
+    let index = positional.pop()?;
+    let command = positional.pop()?;
+    Some(Args {
+        url,
+        refresh,
+        command,
+        index,
+        file,
+    })
+}
+
+fn run(args: &Args) -> Result<(), EsError> {
+    let client = Client::init(&args.url)?;
+    match args.command.as_ref() {
+        "dump" => {
+            let dump = client.dump(&args.index);
+            let count = if args.file == "-" {
+                dump.write(io::stdout().lock())?
+            } else {
+                dump.write_file(&args.file)?
+            };
+            eprintln!("Dumped {} documents from {}", count, args.index);
+        }
+        "restore" => {
+            let mut restore = client.restore(&args.index);
+            restore
+                .with_refresh(args.refresh)
+                .with_on_failure(|failure| eprintln!("Failed: {:?}", failure));
+            let result = if args.file == "-" {
+                restore.read(io::stdin().lock())?
+            } else {
+                restore.read_file(&args.file)?
+            };
+            eprintln!(
+                "Restored {} documents into {}, {} failed",
+                result.sent - result.failed,
+                args.index,
+                result.failed
+            );
+            if result.failed > 0 {
+                return Err(EsError::EsError(
+                    "Some documents could not be restored".to_owned(),
+                ));
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = match parse_args() {
+        Some(args) if args.command == "dump" || args.command == "restore" => args,
+        _ => {
+            eprint!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(&args) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
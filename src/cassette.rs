@@ -0,0 +1,408 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Recording requests and responses to a "cassette" file, and replaying them.
+//!
+//! This allows tests which were recorded against a real ElasticSearch to be
+//! run without one.  See
+//! [`ClientBuilder::with_recording`](../builder/struct.ClientBuilder.html#method.with_recording)
+//! and
+//! [`ClientBuilder::with_replay`](../builder/struct.ClientBuilder.html#method.with_replay).
+//!
+//! A cassette is a JSON file containing each request, identified by its
+//! method, its path and query and its body, and the response to it.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::EsError,
+    transport::{Transport, TransportRequest, TransportResponse},
+};
+
+/// A request and the response to it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedRequest {
+    pub method: String,
+
+    /// The path, and query string if any, without the node
+    pub path: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl RecordedRequest {
+    fn from(request: &TransportRequest) -> RecordedRequest {
+        RecordedRequest {
+            method: request.method().to_string(),
+            path: path_and_query(request),
+            body: request
+                .body()
+                .map(|body| String::from_utf8_lossy(body).into_owned()),
+        }
+    }
+
+    fn matches(&self, request: &TransportRequest) -> bool {
+        self.method == request.method().as_str()
+            && self.path == path_and_query(request)
+            && same_body(self.body.as_deref(), request.body())
+    }
+}
+
+/// Whether a recorded body is the same as the body of a request.  Bodies are
+/// compared as JSON, or line by line for the newline-delimited JSON of e.g.
+/// bulk requests, so the order of the fields of an object does not matter.
+fn same_body(recorded: Option<&str>, body: Option<&[u8]>) -> bool {
+    let body = body.map(String::from_utf8_lossy);
+    match (recorded, body) {
+        (None, None) => true,
+        (Some(recorded), Some(body)) => {
+            recorded == body || same_json(recorded, &body) || {
+                let recorded_lines: Vec<&str> = recorded.lines().collect();
+                let lines: Vec<&str> = body.lines().collect();
+                recorded_lines.len() == lines.len()
+                    && recorded_lines
+                        .iter()
+                        .zip(lines.iter())
+                        .all(|(recorded, line)| same_json(recorded, line))
+            }
+        }
+        _ => false,
+    }
+}
+
+fn same_json(a: &str, b: &str) -> bool {
+    match (
+        serde_json::from_str::<Value>(a),
+        serde_json::from_str::<Value>(b),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    pub body: String,
+}
+
+impl RecordedResponse {
+    fn from(response: &TransportResponse) -> RecordedResponse {
+        RecordedResponse {
+            status: response.status().as_u16(),
+            headers: response
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    value
+                        .to_str()
+                        .ok()
+                        .map(|value| (name.as_str().to_owned(), value.to_owned()))
+                })
+                .collect(),
+            body: String::from_utf8_lossy(response.body()).into_owned(),
+        }
+    }
+
+    fn to_response(&self) -> Result<TransportResponse, EsError> {
+        let status = StatusCode::from_u16(self.status).map_err(|_| {
+            EsError::EsError(format!("Invalid status in cassette: {}", self.status))
+        })?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                _ => {
+                    return Err(EsError::EsError(format!(
+                        "Invalid header in cassette: {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(TransportResponse::new(status, self.body.as_bytes()).with_headers(headers))
+    }
+}
+
+fn path_and_query(request: &TransportRequest) -> String {
+    match request.query() {
+        Some(query) => format!("{}?{}", request.path(), query),
+        None => request.path().to_owned(),
+    }
+}
+
+/// The contents of a cassette file
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Read a cassette from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Cassette, EsError> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Write a cassette to a file, creating any missing directories
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), EsError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path)?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+}
+
+/// A `Transport` which sends requests with another transport, and records
+/// each request and response to a cassette.  The file is re-written after
+/// each response.
+#[derive(Debug)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingTransport {
+    pub fn new<P: Into<PathBuf>>(inner: Arc<dyn Transport>, path: P) -> RecordingTransport {
+        RecordingTransport {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send(&self, request: TransportRequest) -> Result<TransportResponse, EsError> {
+        let recorded_request = RecordedRequest::from(&request);
+        let response = self.inner.send(request)?;
+
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(Interaction {
+            request: recorded_request,
+            response: RecordedResponse::from(&response),
+        });
+        cassette.save(&self.path)?;
+        Ok(response)
+    }
+}
+
+/// A `Transport` which replays the responses recorded in a cassette.
+///
+/// Each request is given the response of the first interaction, not already
+/// replayed, with the same method, path and query, and body.  A request with
+/// no such interaction is an error.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> ReplayTransport {
+        ReplayTransport {
+            interactions: Mutex::new(cassette.interactions),
+        }
+    }
+
+    /// Replay the cassette in the given file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ReplayTransport, EsError> {
+        Ok(ReplayTransport::new(Cassette::load(path)?))
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, request: TransportRequest) -> Result<TransportResponse, EsError> {
+        let mut interactions = self.interactions.lock().unwrap();
+        match interactions
+            .iter()
+            .position(|interaction| interaction.request.matches(&request))
+        {
+            Some(idx) => interactions.remove(idx).response.to_response(),
+            None => Err(EsError::EsError(format!(
+                "No recorded response for: {} {}",
+                request.method(),
+                path_and_query(&request)
+            ))),
+        }
+    }
+}
+
+/// Whether requests are being recorded or replayed
+#[derive(Clone, Debug)]
+pub(crate) enum CassetteMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+impl CassetteMode {
+    /// Wrap, or replace, the transport of a client
+    pub(crate) fn transport(
+        &self,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Arc<dyn Transport>, EsError> {
+        Ok(match *self {
+            CassetteMode::Record(ref path) => Arc::new(RecordingTransport::new(transport, path)),
+            CassetteMode::Replay(ref path) => Arc::new(ReplayTransport::load(path)?),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::env;
+    use std::fs;
+
+    use reqwest::Method;
+
+    use crate::{query::Query, transport::MockTransport, ClientBuilder};
+
+    use super::{same_body, Cassette};
+
+    const COUNT_RESULT: &str =
+        "{\"count\":2,\"_shards\":{\"total\":1,\"successful\":1,\"failed\":0}}";
+
+    #[test]
+    fn test_record_and_replay() {
+        let path = env::temp_dir().join(format!("rs-es-cassette-{}.json", std::process::id()));
+
+        let transport =
+            MockTransport::new().with_response(Method::GET, "/test_idx/_count", 200, COUNT_RESULT);
+        let client = ClientBuilder::new("http://localhost:9200")
+            .with_transport(transport)
+            .with_recording(&path)
+            .build()
+            .unwrap();
+        assert_eq!(
+            2,
+            client
+                .count_uri()
+                .with_indexes(&["test_idx"])
+                .with_query("a:b")
+                .send()
+                .unwrap()
+                .count
+        );
+
+        let cassette = Cassette::load(&path).unwrap();
+        assert_eq!(1, cassette.interactions.len());
+        assert_eq!("GET", cassette.interactions[0].request.method);
+        assert_eq!(
+            "/test_idx/_count?q=a:b",
+            cassette.interactions[0].request.path
+        );
+        assert_eq!(COUNT_RESULT, cassette.interactions[0].response.body);
+
+        let client = ClientBuilder::new("http://localhost:9200")
+            .with_replay(&path)
+            .build()
+            .unwrap();
+        let count = || {
+            client
+                .count_uri()
+                .with_indexes(&["test_idx"])
+                .with_query("a:b")
+                .send()
+        };
+        assert_eq!(2, count().unwrap().count);
+        // Each interaction is only replayed once
+        assert!(count().is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_matches_body() {
+        let path = env::temp_dir().join(format!("rs-es-cassette-body-{}.json", std::process::id()));
+
+        let transport =
+            MockTransport::new().with_response(Method::POST, "/test_idx/_count", 200, COUNT_RESULT);
+        let client = ClientBuilder::new("http://localhost:9200")
+            .with_transport(transport)
+            .with_recording(&path)
+            .build()
+            .unwrap();
+        let a = Query::build_term("str_field", "a").build();
+        client
+            .count_query()
+            .with_indexes(&["test_idx"])
+            .with_query(&a)
+            .send()
+            .unwrap();
+
+        let client = ClientBuilder::new("http://localhost:9200")
+            .with_replay(&path)
+            .build()
+            .unwrap();
+        let b = Query::build_term("str_field", "b").build();
+        let count = |query| {
+            client
+                .count_query()
+                .with_indexes(&["test_idx"])
+                .with_query(query)
+                .send()
+        };
+        assert!(count(&b).is_err());
+        assert_eq!(2, count(&a).unwrap().count);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_same_body() {
+        assert!(same_body(None, None));
+        assert!(!same_body(Some("{}"), None));
+        assert!(same_body(
+            Some(r#"{"a":1,"b":2}"#),
+            Some(br#"{"b":2,"a":1}"#)
+        ));
+        assert!(same_body(
+            Some("{\"index\":{}}\n{\"a\":1,\"b\":2}\n"),
+            Some(b"{\"index\":{}}\n{\"b\":2,\"a\":1}\n")
+        ));
+        assert!(!same_body(Some(r#"{"a":1}"#), Some(br#"{"a":2}"#)));
+    }
+}
@@ -20,7 +20,11 @@ use std::error::Error;
 use std::fmt;
 use std::io::{self, Read};
 
-use serde_json;
+use reqwest::StatusCode;
+
+use serde::Deserialize;
+
+use crate::{retry, EsResponse};
 
 // Error handling
 
@@ -32,7 +36,7 @@ pub enum EsError {
     EsError(String),
 
     /// An error reported in a JSON response from the ElasticSearch server
-    EsServerError(String),
+    EsServerError(Box<ServerError>),
 
     /// Miscellaneous error from the HTTP library
     HttpError(reqwest::Error),
@@ -56,6 +60,12 @@ impl From<reqwest::Error> for EsError {
     }
 }
 
+impl From<reqwest::UrlError> for EsError {
+    fn from(err: reqwest::UrlError) -> EsError {
+        EsError::EsError(format!("Invalid URL: {}", err))
+    }
+}
+
 impl From<serde_json::error::Error> for EsError {
     fn from(err: serde_json::error::Error) -> EsError {
         EsError::JsonError(err)
@@ -68,22 +78,251 @@ impl<'a> From<&'a mut reqwest::Response> for EsError {
         match err.read_to_string(&mut body) {
             Ok(_) => (),
             Err(_) => {
-                return EsError::EsServerError(format!(
-                    "{} - cannot read response - {:?}",
+                return EsError::EsServerError(Box::new(ServerError::new(
                     err.status(),
-                    err
-                ));
+                    &format!("cannot read response - {:?}", err),
+                )));
             }
         }
-        EsError::EsServerError(format!("{} - {}", err.status(), body))
+        EsError::EsServerError(Box::new(ServerError::new(err.status(), &body)))
     }
 }
 
+impl EsError {
+    /// The error reported by the ElasticSearch server, if this is one
+    pub fn server_error(&self) -> Option<&ServerError> {
+        match *self {
+            EsError::EsServerError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// See `ServerError::is_not_found`, false if this is not a server error
+    pub fn is_not_found(&self) -> bool {
+        match self.server_error() {
+            Some(err) => err.is_not_found(),
+            None => false,
+        }
+    }
+
+    /// See `ServerError::is_conflict`, false if this is not a server error
+    pub fn is_conflict(&self) -> bool {
+        match self.server_error() {
+            Some(err) => err.is_conflict(),
+            None => false,
+        }
+    }
+
+    /// See `ServerError::is_index_missing`, false if this is not a server
+    /// error
+    pub fn is_index_missing(&self) -> bool {
+        match self.server_error() {
+            Some(err) => err.is_index_missing(),
+            None => false,
+        }
+    }
+
+    /// Might the request succeed if sent again, either the server said so or
+    /// the connection was reset
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            EsError::EsServerError(ref err) => err.is_retryable(),
+            _ => is_connection_reset(self),
+        }
+    }
+}
+
+/// An individual cause of a `ServerError`
+#[derive(Clone, Debug, Deserialize)]
+pub struct ErrorCause {
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub reason: Option<String>,
+    pub index: Option<String>,
+    pub caused_by: Option<Box<ErrorCause>>,
+}
+
+/// The `error` of an error response.  Since ElasticSearch 2.0 this is an
+/// object, before then it was only a message.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ErrorDetail {
+    Structured {
+        #[serde(rename = "type")]
+        error_type: String,
+        reason: Option<String>,
+        index: Option<String>,
+        #[serde(default)]
+        root_cause: Vec<ErrorCause>,
+        caused_by: Option<ErrorCause>,
+    },
+    Message(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+/// An error response from the ElasticSearch server.
+///
+/// The fields are parsed from the JSON body of the response, if it is in the
+/// usual format, e.g.:
+///
+/// ```json
+/// {"error": {"root_cause": [...], "type": "index_not_found_exception",
+///            "reason": "no such index", "index": "my_index"},
+///  "status": 404}
+/// ```
+#[derive(Clone, Debug)]
+pub struct ServerError {
+    pub status: StatusCode,
+    pub error_type: Option<String>,
+    pub reason: Option<String>,
+    pub root_cause: Vec<ErrorCause>,
+    pub caused_by: Option<ErrorCause>,
+    pub index: Option<String>,
+    body: String,
+}
+
+impl ServerError {
+    pub(crate) fn new(status: StatusCode, body: &str) -> ServerError {
+        let mut error = ServerError {
+            status,
+            error_type: None,
+            reason: None,
+            root_cause: Vec::new(),
+            caused_by: None,
+            index: None,
+            body: body.to_owned(),
+        };
+        match serde_json::from_str::<ErrorResponse>(body) {
+            Ok(ErrorResponse {
+                error:
+                    ErrorDetail::Structured {
+                        error_type,
+                        reason,
+                        index,
+                        root_cause,
+                        caused_by,
+                    },
+            }) => {
+                error.error_type = Some(error_type);
+                error.reason = reason;
+                error.index = index;
+                error.root_cause = root_cause;
+                error.caused_by = caused_by;
+            }
+            Ok(ErrorResponse {
+                error: ErrorDetail::Message(message),
+            }) => error.reason = Some(message),
+            Err(_) => (),
+        }
+        error
+    }
+
+    /// The body of the response, as returned by the server
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// The error, or any of its root causes, is of the given type
+    pub fn has_type(&self, error_type: &str) -> bool {
+        self.error_type.as_deref() == Some(error_type)
+            || self
+                .root_cause
+                .iter()
+                .any(|cause| cause.error_type == error_type)
+    }
+
+    /// The document, index or other resource does not exist
+    pub fn is_not_found(&self) -> bool {
+        self.status == StatusCode::NOT_FOUND
+    }
+
+    /// A version conflict, e.g. the document was modified concurrently
+    pub fn is_conflict(&self) -> bool {
+        self.status == StatusCode::CONFLICT || self.has_type("version_conflict_engine_exception")
+    }
+
+    /// The request may succeed if sent again later, e.g. the server is
+    /// overloaded
+    pub fn is_retryable(&self) -> bool {
+        retry::is_retryable_status(self.status)
+    }
+
+    /// The index does not exist
+    pub fn is_index_missing(&self) -> bool {
+        let reason = self.reason.as_deref().unwrap_or("");
+        self.has_type("index_not_found_exception") || reason.starts_with("IndexMissingException")
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} - {}", self.status, self.body)
+    }
+}
+
+/// The error for a response with a status the operation did not expect
+pub(crate) fn unexpected_status<R: EsResponse>(response: R) -> EsError {
+    let status = response.status_code();
+    let body = response.read_text().unwrap_or_default();
+    EsError::EsServerError(Box::new(ServerError::new(status, &body)))
+}
+
+/// Is the body in the format of an error response
+pub(crate) fn is_error_body(body: &[u8]) -> bool {
+    serde_json::from_slice::<ErrorResponse>(body).is_ok()
+}
+
+/// The underlying IO error, if any, that caused an error
+fn io_error_kind(err: &EsError) -> Option<io::ErrorKind> {
+    let mut source = match *err {
+        EsError::HttpError(ref err) => err.get_ref().map(|err| err as &(dyn Error + 'static)),
+        EsError::IoError(ref err) => Some(err as &(dyn Error + 'static)),
+        _ => None,
+    };
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<io::Error>() {
+            return Some(io_err.kind());
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Is the error the result of a problem connecting to, or communicating with,
+/// the server; rather than a problem with the request itself.
+pub(crate) fn is_connection_error(err: &EsError) -> bool {
+    match *err {
+        EsError::HttpError(ref err) => err.is_http() || err.is_timeout(),
+        EsError::IoError(_) => true,
+        _ => false,
+    }
+}
+
+/// Was the connection refused, i.e. the request never reached the server
+pub(crate) fn is_connection_refused(err: &EsError) -> bool {
+    io_error_kind(err) == Some(io::ErrorKind::ConnectionRefused)
+}
+
+/// Was the connection dropped while the request was in progress
+pub(crate) fn is_connection_reset(err: &EsError) -> bool {
+    matches!(
+        io_error_kind(err),
+        Some(io::ErrorKind::ConnectionReset)
+            | Some(io::ErrorKind::ConnectionAborted)
+            | Some(io::ErrorKind::BrokenPipe)
+            | Some(io::ErrorKind::UnexpectedEof)
+    )
+}
+
 impl Error for EsError {
     fn description(&self) -> &str {
         match *self {
             EsError::EsError(ref err) => err,
-            EsError::EsServerError(ref err) => err,
+            EsError::EsServerError(ref err) => err.reason.as_ref().unwrap_or(&err.body),
             EsError::HttpError(ref err) => err.description(),
             EsError::IoError(ref err) => err.description(),
             EsError::JsonError(ref err) => err.description(),
@@ -112,3 +351,74 @@ impl fmt::Display for EsError {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::StatusCode;
+
+    use crate::transport::TransportResponse;
+
+    use super::{is_error_body, unexpected_status, ServerError};
+
+    #[test]
+    fn test_server_error() {
+        let body = r#"{"error": {
+            "root_cause": [{"type": "index_not_found_exception", "reason": "no such index",
+                            "index": "missing_idx"}],
+            "type": "index_not_found_exception", "reason": "no such index",
+            "index": "missing_idx"}, "status": 404}"#;
+        let err = ServerError::new(StatusCode::NOT_FOUND, body);
+        assert!(err.is_not_found());
+        assert!(err.is_index_missing());
+        assert!(!err.is_conflict());
+        assert!(!err.is_retryable());
+        assert_eq!(Some("missing_idx".to_owned()), err.index);
+        assert_eq!(1, err.root_cause.len());
+        assert_eq!(format!("404 Not Found - {}", body), err.to_string());
+
+        let body = r#"{"error": {
+            "root_cause": [{"type": "version_conflict_engine_exception", "reason": "conflict"}],
+            "type": "version_conflict_engine_exception", "reason": "conflict",
+            "caused_by": {"type": "other_exception", "reason": "cause"}}, "status": 409}"#;
+        let err = ServerError::new(StatusCode::CONFLICT, body);
+        assert!(err.is_conflict());
+        assert_eq!(
+            Some("other_exception".to_owned()),
+            err.caused_by.map(|cause| cause.error_type)
+        );
+
+        let body = r#"{"error": {"type": "es_rejected_execution_exception", "reason": "full"}}"#;
+        assert!(ServerError::new(StatusCode::TOO_MANY_REQUESTS, body).is_retryable());
+    }
+
+    #[test]
+    fn test_server_error_formats() {
+        // ElasticSearch 1.x
+        let err = ServerError::new(
+            StatusCode::NOT_FOUND,
+            r#"{"error": "IndexMissingException[[missing_idx] missing]", "status": 404}"#,
+        );
+        assert!(err.is_index_missing());
+        assert_eq!(None, err.error_type);
+
+        // Not JSON
+        let err = ServerError::new(StatusCode::BAD_GATEWAY, "Bad Gateway");
+        assert!(err.is_retryable());
+        assert_eq!(None, err.reason);
+        assert_eq!("Bad Gateway", err.body());
+    }
+
+    #[test]
+    fn test_unexpected_status() {
+        let response = TransportResponse::new(StatusCode::BAD_GATEWAY, "Bad Gateway");
+        let err = unexpected_status(response);
+        assert_eq!(
+            Some("Bad Gateway"),
+            err.server_error().map(|err| err.body())
+        );
+
+        assert!(is_error_body(br#"{"error": "broken", "status": 500}"#));
+        assert!(!is_error_body(br#"{"_id": "1", "found": false}"#));
+        assert!(!is_error_body(b""));
+    }
+}
@@ -35,24 +35,49 @@ pub mod util;
 #[macro_use]
 pub mod json;
 
+#[cfg(feature = "async")]
+pub mod async_client;
+pub mod builder;
+pub mod cassette;
 pub mod error;
 pub mod operations;
+mod pool;
 pub mod query;
+pub mod retry;
+mod sniff;
+pub mod transport;
 pub mod units;
 
+use std::sync::Arc;
+use std::thread;
 use std::time;
 
-use reqwest::{header::CONTENT_TYPE, RequestBuilder, StatusCode, Url};
+use reqwest::{Method, StatusCode, Url};
 
 use serde::{de::DeserializeOwned, ser::Serialize};
 
-use crate::error::EsError;
+use crate::error::{EsError, ServerError};
+use crate::pool::{Node, NodePool};
+use crate::retry::{Retry, RetryPolicy};
+use crate::transport::{HttpTransport, Transport, TransportRequest, TransportResponse};
+
+#[cfg(feature = "async")]
+pub use crate::async_client::AsyncClient;
+pub use crate::builder::ClientBuilder;
 
 pub trait EsResponse {
     fn status_code(&self) -> StatusCode;
     fn read_response<R>(self) -> Result<R, EsError>
     where
         R: DeserializeOwned;
+
+    /// The body as text, e.g. for an error response which may not be JSON
+    fn read_text(self) -> Result<String, EsError>
+    where
+        Self: Sized,
+    {
+        Ok(self.read_response::<serde_json::Value>()?.to_string())
+    }
 }
 
 impl EsResponse for reqwest::Response {
@@ -66,6 +91,10 @@ impl EsResponse for reqwest::Response {
     {
         Ok(serde_json::from_reader(self)?)
     }
+
+    fn read_text(mut self) -> Result<String, EsError> {
+        Ok(self.text()?)
+    }
 }
 
 // The client
@@ -74,24 +103,36 @@ impl EsResponse for reqwest::Response {
 /// `Json` result (if the result had a body) or an `EsError` if there were any
 /// errors
 ///
+/// A 404 with an error body, e.g. a missing index, is an error; other 404s,
+/// e.g. a missing document or the empty body of a `HEAD` request, are passed
+/// on for the operation to interpret.  Likewise a 409 without an error body,
+/// the statistics of a by-query operation stopped by a version conflict.
+///
 /// This function is exposed to allow extensions to certain operations, it is
 /// not expected to be used by consumers of the library
-fn do_req(resp: reqwest::Response) -> Result<reqwest::Response, EsError> {
-    let mut resp = resp;
-    let status = resp.status();
-    match status {
-        StatusCode::OK | StatusCode::CREATED | StatusCode::NOT_FOUND => Ok(resp),
-        _ => Err(EsError::from(&mut resp)),
+fn do_req(resp: TransportResponse) -> Result<TransportResponse, EsError> {
+    match resp.status() {
+        StatusCode::OK | StatusCode::CREATED => Ok(resp),
+        StatusCode::NOT_FOUND | StatusCode::CONFLICT if !error::is_error_body(resp.body()) => {
+            Ok(resp)
+        }
+        status => Err(EsError::EsServerError(Box::new(ServerError::new(
+            status,
+            &String::from_utf8_lossy(resp.body()),
+        )))),
     }
 }
 
-/// The core of the ElasticSearch client, owns a HTTP connection.
+/// The core of the ElasticSearch client, owns a pool of HTTP connections.
 ///
-/// Each instance of `Client` is reusable, but only one thread can use each one
-/// at once.  This will be enforced by the borrow-checker as most methods are
-/// defined on `&mut self`.
+/// Each instance of `Client` is reusable, and can be used by many threads at
+/// once, e.g. shared in an `Arc`, as the operations are defined on `&self`.
+/// Cloning a `Client` is cheap, clones share the same connections and nodes.
 ///
-/// To create a `Client`, the URL needs to be specified.
+/// To create a `Client`, the URL needs to be specified.  Further options, e.g.
+/// timeouts, authentication and TLS settings, can be set using a
+/// [`ClientBuilder`](builder/struct.ClientBuilder.html).  The requests
+/// themselves are sent by a [`Transport`](transport/trait.Transport.html).
 ///
 /// Each ElasticSearch API operation is defined as a method on `Client`.  Any
 /// compulsory parameters must be given as arguments to this method.  It returns
@@ -104,60 +145,156 @@ fn do_req(resp: reqwest::Response) -> Result<reqwest::Response, EsError> {
 /// ```
 /// use rs_es::Client;
 ///
-/// let mut client = Client::init("http://localhost:9200");
+/// let client = Client::init("http://localhost:9200");
 /// ```
 ///
 /// See the specific operations and their builder objects for details.
 #[derive(Debug, Clone)]
 pub struct Client {
-    base_url: Url,
-    http_client: reqwest::Client,
+    nodes: Arc<NodePool>,
+    transport: Arc<dyn Transport>,
+    retry_policy: RetryPolicy,
 }
 
 impl Client {
+    /// Send a request, retrying according to the retry policy if the request is
+    /// safe to repeat.
+    ///
+    /// If periodic sniffing is enabled and due, the nodes are refreshed first.
     fn do_es_op(
         &self,
         url: &str,
-        action: impl FnOnce(Url) -> RequestBuilder,
-    ) -> Result<reqwest::Response, EsError> {
-        let url = self.full_url(url);
-        let username = self.base_url.username();
-        let mut method = action(url);
-        if !username.is_empty() {
-            method = method.basic_auth(username, self.base_url.password());
+        retry: Retry,
+        method: Method,
+        body: Option<Vec<u8>>,
+    ) -> Result<TransportResponse, EsError> {
+        self.sniff_if_due();
+
+        let policy = match retry {
+            Retry::Never => None,
+            Retry::Default => Some(&self.retry_policy),
+            Retry::Policy(policy) => Some(policy),
+        };
+        let mut attempt = 1;
+        loop {
+            let result = self.send_to_node(url, &method, &body);
+            if let Some(policy) = policy {
+                if attempt < policy.max_attempts() && retry::is_retryable(&result) {
+                    let backoff = policy.backoff(attempt);
+                    log::warn!(
+                        "Retrying {} in {:?}, attempt {} failed",
+                        url,
+                        backoff,
+                        attempt
+                    );
+                    thread::sleep(backoff);
+                    attempt += 1;
+                    continue;
+                }
+            }
+            return result.and_then(do_req);
+        }
+    }
+
+    /// Send a request to the next available node.
+    ///
+    /// Nodes which cannot be connected to are marked as dead, and the request
+    /// is sent to the next node instead.
+    fn send_to_node(
+        &self,
+        url: &str,
+        method: &Method,
+        body: &Option<Vec<u8>>,
+    ) -> Result<TransportResponse, EsError> {
+        let mut last_error = None;
+        for _ in 0..self.nodes.len() {
+            let selected = self.nodes.next_node();
+            let node = &selected.node;
+            if selected.needs_ping && !self.ping(node) {
+                continue;
+            }
+
+            let request = TransportRequest::new(
+                method.clone(),
+                node.url().join(url).expect("Invalid URL created"),
+                body.clone(),
+            );
+            match self.transport.send(request) {
+                Ok(result) => {
+                    self.nodes.mark_alive(node);
+                    return Ok(result);
+                }
+                Err(err) => {
+                    if !error::is_connection_error(&err) {
+                        return Err(err);
+                    }
+                    self.nodes.mark_dead(node);
+                    if !error::is_connection_refused(&err) {
+                        // The request may have reached the server, so it is not
+                        // safe to send it again
+                        return Err(err);
+                    }
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| EsError::EsError("No live nodes available".to_owned())))
+    }
+
+    /// Check a previously dead node, marking it alive if it responds
+    fn ping(&self, node: &Node) -> bool {
+        let request = TransportRequest::new(Method::HEAD, node.url().clone(), None);
+        match self.transport.send(request) {
+            Ok(ref response) if response.status().is_success() => {
+                self.nodes.mark_alive(node);
+                true
+            }
+            _ => {
+                self.nodes.mark_dead(node);
+                false
+            }
         }
-        let result = method.header(CONTENT_TYPE, "application/json").send()?;
-        do_req(result)
     }
 }
 
-/// Create a HTTP function for the given method (GET/PUT/POST/DELETE)
+/// Create a HTTP function for the given method (GET/PUT/POST/DELETE/HEAD),
+/// either with a fixed `Retry`, or taking a `Retry` argument
 macro_rules! es_op {
+    ($n:ident,$cn:ident,$retry:expr) => {
+        fn $n(&self, url: &str) -> Result<TransportResponse, EsError> {
+            log::info!("Doing {} on {}", stringify!($n), url);
+            self.do_es_op(url, $retry, Method::$cn, None)
+        }
+    };
     ($n:ident,$cn:ident) => {
-        fn $n(&self, url: &str) -> Result<reqwest::Response, EsError> {
+        fn $n(&self, url: &str, retry: Retry) -> Result<TransportResponse, EsError> {
             log::info!("Doing {} on {}", stringify!($n), url);
-            self.do_es_op(url, |url| self.http_client.$cn(url.clone()))
+            self.do_es_op(url, retry, Method::$cn, None)
         }
-    }
+    };
 }
 
 /// Create a HTTP function with a request body for the given method
-/// (GET/PUT/POST/DELETE)
+/// (GET/PUT/POST/DELETE), either with a fixed `Retry`, or taking a `Retry`
+/// argument
 ///
 macro_rules! es_body_op {
-    ($n:ident,$cn:ident) => {
-        fn $n<E>(&mut self, url: &str, body: &E) -> Result<reqwest::Response, EsError>
+    ($n:ident,$cn:ident,$retry:expr) => {
+        fn $n<E>(&self, url: &str, body: &E) -> Result<TransportResponse, EsError>
             where E: Serialize {
 
             log::info!("Doing {} on {}", stringify!($n), url);
-            let json_string = serde_json::to_string(body)?;
-            log::debug!("With body: {}", &json_string);
+            self.do_es_body_op(url, body, $retry, Method::$cn)
+        }
+    };
+    ($n:ident,$cn:ident) => {
+        fn $n<E>(&self, url: &str, body: &E, retry: Retry) -> Result<TransportResponse, EsError>
+            where E: Serialize {
 
-            self.do_es_op(url, |url| {
-                self.http_client.$cn(url.clone()).body(json_string)
-            })
+            log::info!("Doing {} on {}", stringify!($n), url);
+            self.do_es_body_op(url, body, retry, Method::$cn)
         }
-    }
+    };
 }
 
 impl Client {
@@ -166,13 +303,23 @@ impl Client {
         let url = Url::parse(url_s)?;
 
         Ok(Client {
-            http_client: reqwest::Client::new(),
-            base_url: url,
+            transport: Arc::new(HttpTransport::new(reqwest::Client::new())),
+            nodes: Arc::new(NodePool::new(
+                vec![url],
+                pool::DEFAULT_DEAD_TIMEOUT,
+                pool::DEFAULT_MAX_DEAD_TIMEOUT,
+            )),
+            retry_policy: RetryPolicy::default(),
         })
     }
 
-    // TODO - this should be replaced with a builder object, especially if more options are going
-    // to be allowed
+    /// Create a builder, to configure a new client
+    pub fn builder<S: Into<String>>(url_s: S) -> ClientBuilder {
+        ClientBuilder::new(url_s)
+    }
+
+    /// Create a new client, with the given timeout
+    #[deprecated(since = "0.12.4", note = "use `ClientBuilder::with_timeout` instead")]
     pub fn init_with_timeout(
         url_s: &str,
         timeout: Option<time::Duration>,
@@ -180,45 +327,247 @@ impl Client {
         let url = Url::parse(url_s)?;
 
         Ok(Client {
-            http_client: reqwest::Client::builder()
-                .timeout(timeout)
-                .build()
-                .expect("Failed to build client"),
-            base_url: url,
+            transport: Arc::new(HttpTransport::new(
+                reqwest::Client::builder()
+                    .timeout(timeout)
+                    .build()
+                    .expect("Failed to build client"),
+            )),
+            nodes: Arc::new(NodePool::new(
+                vec![url],
+                pool::DEFAULT_DEAD_TIMEOUT,
+                pool::DEFAULT_MAX_DEAD_TIMEOUT,
+            )),
+            retry_policy: RetryPolicy::default(),
         })
     }
 
     /// Take a nearly complete ElasticSearch URL, and stick
-    /// the URL on the front.
+    /// the URL of the first node on the front.
     pub fn full_url(&self, suffix: &str) -> Url {
-        self.base_url.join(suffix).expect("Invalid URL created")
+        self.nodes
+            .first()
+            .url()
+            .join(suffix)
+            .expect("Invalid URL created")
+    }
+
+    /// The URLs of each node currently thought to be alive
+    pub fn live_nodes(&self) -> Vec<Url> {
+        self.nodes.live_urls()
     }
 
-    es_op!(get_op, get);
+    fn do_es_body_op<E>(
+        &self,
+        url: &str,
+        body: &E,
+        retry: Retry,
+        method: Method,
+    ) -> Result<TransportResponse, EsError>
+    where
+        E: Serialize,
+    {
+        let json_string = serde_json::to_string(body)?;
+        log::debug!("With body: {}", &json_string);
+
+        self.do_es_op(url, retry, method, Some(json_string.into_bytes()))
+    }
 
-    es_op!(post_op, post);
-    es_body_op!(post_body_op, post);
-    es_op!(put_op, put);
-    es_body_op!(put_body_op, put);
-    es_op!(delete_op, delete);
+    es_op!(get_op, GET, Retry::Default);
+    es_op!(get_op_with_retry, GET);
+
+    es_op!(post_op, POST, Retry::Never);
+    es_body_op!(post_body_op, POST, Retry::Never);
+    es_body_op!(post_body_op_with_retry, POST);
+    es_op!(put_op, PUT, Retry::Default);
+    es_body_op!(put_body_op, PUT, Retry::Default);
+    es_op!(delete_op, DELETE, Retry::Default);
+    es_body_op!(delete_body_op, DELETE, Retry::Default);
+    es_op!(head_op, HEAD, Retry::Default);
 }
 
 #[cfg(test)]
 pub mod tests {
     use std::env;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::thread;
 
     use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
 
-    use super::{error::EsError, Client};
+    use super::{
+        transport::{MockTransport, TransportRequest},
+        Client,
+    };
+
+    #[cfg(feature = "async")]
+    use super::AsyncClient;
 
     // test setup
 
-    pub fn make_client() -> Client {
+    /// A client which replays the requests of the test from its cassette in
+    /// `tests/cassettes`, named after the test, so no ElasticSearch is needed.
+    ///
+    /// If `ES_CASSETTE` is `record` the client instead sends the requests to
+    /// the ElasticSearch at `ES_HOST`, or localhost, and records them to the
+    /// cassette.
+    pub fn make_client(test_name: &str) -> Client {
         let hostname = match env::var("ES_HOST") {
             Ok(val) => val,
             Err(_) => "http://localhost:9200".to_owned(),
         };
-        Client::init(&hostname).unwrap()
+        let builder = Client::builder(hostname);
+        let cassette = cassette_path(test_name);
+        let builder = match env::var("ES_CASSETTE") {
+            Ok(ref val) if val == "record" => builder.with_recording(cassette),
+            _ if cassette.exists() => builder.with_replay(cassette),
+            _ => panic!(
+                "No cassette at {}, record it with ES_CASSETTE=record",
+                cassette.display()
+            ),
+        };
+        builder.build().unwrap()
+    }
+
+    /// The cassette for the given test
+    fn cassette_path(test_name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("cassettes")
+            .join(format!("{}.json", test_name))
+    }
+
+    /// A client which sends requests to the given mock, rather than a real
+    /// ElasticSearch
+    pub fn make_mock_client(transport: &MockTransport) -> Client {
+        Client::builder("http://localhost:9200")
+            .with_transport(transport.clone())
+            .build()
+            .unwrap()
+    }
+
+    /// An `AsyncClient` which sends requests to the given mock
+    #[cfg(feature = "async")]
+    pub fn make_mock_async_client(transport: &MockTransport) -> AsyncClient {
+        Client::builder("http://localhost:9200")
+            .with_transport(transport.clone())
+            .build_async()
+            .unwrap()
+    }
+
+    /// The search result for a mock, with a hit in `test_idx` for each
+    /// `(id, int_field)`, sorted by `int_field` then `_uid`.  The `fields` are
+    /// merged into the result, e.g. a `_scroll_id`.
+    pub fn make_hits_page(fields: Value, hits: &[(&str, i64)]) -> String {
+        let hits: Vec<Value> = hits
+            .iter()
+            .map(|&(id, int_field)| {
+                json!({"_index": "test_idx", "_type": "test_type", "_id": id,
+                       "_source": {"int_field": int_field},
+                       "sort": [int_field, format!("test_type#{}", id)]})
+            })
+            .collect();
+        let mut page = json!({"took": 1, "timed_out": false,
+                              "_shards": {"total": 1, "successful": 1, "failed": 0},
+                              "hits": {"total": hits.len(), "hits": hits}});
+        merge_json(&mut page, fields);
+        page.to_string()
+    }
+
+    fn merge_json(into: &mut Value, from: Value) {
+        match (into, from) {
+            (Value::Object(into), Value::Object(from)) => {
+                for (key, value) in from {
+                    merge_json(into.entry(key).or_insert(Value::Null), value);
+                }
+            }
+            (into, from) => *into = from,
+        }
+    }
+
+    /// The JSON body of a request sent to a mock
+    pub fn request_body(request: &TransportRequest) -> Value {
+        serde_json::from_slice(request.body().unwrap()).unwrap()
+    }
+
+    /// A minimal HTTP server for tests which do not need a real ElasticSearch.
+    /// Each request is passed to `handler` as the method and path, which
+    /// returns the status code and JSON body of the response.
+    ///
+    /// Returns the URL of the server.
+    pub fn stub_server<F>(handler: F) -> String
+    where
+        F: Fn(&str, &str) -> (u16, String) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or("").to_owned();
+                let path = parts.next().unwrap_or("").to_owned();
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let lower = line.to_lowercase();
+                    if let Some(value) = lower.strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let (status, body) = handler(&method, &path);
+                let response = format!(
+                    "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_shared_client() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Client>();
+
+        let url = stub_server(|_, _| {
+            (
+                200,
+                "{\"count\":1,\"_shards\":{\"total\":1,\"successful\":1,\"failed\":0}}".to_owned(),
+            )
+        });
+        let client = Arc::new(Client::init(&url).unwrap());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let client = client.clone();
+                thread::spawn(move || client.count_uri().send().unwrap().count)
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(1, handle.join().unwrap());
+        }
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -254,7 +603,7 @@ pub mod tests {
         }
     }
 
-    pub fn setup_test_data(client: &mut Client, index_name: &str) {
+    pub fn setup_test_data(client: &Client, index_name: &str) {
         // TODO - this should use the Bulk API
         let documents = vec![
             TestDocument::new()
@@ -277,10 +626,10 @@ pub mod tests {
         client.refresh().with_indexes(&[index_name]).send().unwrap();
     }
 
-    pub fn clean_db(client: &mut Client, test_idx: &str) {
+    pub fn clean_db(client: &Client, test_idx: &str) {
         match client.delete_index(test_idx) {
             // Ignore indices which don't exist yet
-            Err(EsError::EsError(ref msg)) if msg == "Unexpected status: 404 Not Found" => {}
+            Err(ref err) if err.is_index_missing() => {}
             Ok(_) => {}
             e => {
                 e.unwrap_or_else(|_| panic!("Failed to clean db for index {:?}", test_idx));
@@ -17,25 +17,31 @@
 
 //! Implementation of ElasticSearch Analyze operation
 
+use reqwest::Method;
+
 use serde::Deserialize;
 
 use crate::{
     error::EsError,
+    retry::Retry,
     {Client, EsResponse},
 };
 
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
 #[derive(Debug)]
-pub struct AnalyzeOperation<'a, 'b> {
+pub struct AnalyzeOperation<'a, 'b, C = Client> {
     /// The HTTP client that this operation will use
-    client: &'a mut Client,
+    client: &'a C,
 
     body: &'b str,
     index: Option<&'b str>,
     analyzer: Option<&'b str>,
 }
 
-impl<'a, 'b> AnalyzeOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client, body: &'b str) -> AnalyzeOperation<'a, 'b> {
+impl<'a, 'b, C> AnalyzeOperation<'a, 'b, C> {
+    pub fn new(client: &'a C, body: &'b str) -> AnalyzeOperation<'a, 'b, C> {
         AnalyzeOperation {
             client,
             body,
@@ -54,7 +60,7 @@ impl<'a, 'b> AnalyzeOperation<'a, 'b> {
         self
     }
 
-    pub fn send(&mut self) -> Result<AnalyzeResult, EsError> {
+    fn url(&self) -> String {
         let mut url = match self.index {
             None => "/_analyze".to_owned(),
             Some(index) => format!("{}/_analyze", index),
@@ -63,19 +69,55 @@ impl<'a, 'b> AnalyzeOperation<'a, 'b> {
             None => (),
             Some(analyzer) => url.push_str(&format!("?analyzer={}", analyzer)),
         }
-        let response = self.client.do_es_op(&url, |url| {
-            self.client.http_client.post(url).body(self.body.to_owned())
-        })?;
+        url
+    }
+}
+
+impl<'a, 'b> AnalyzeOperation<'a, 'b> {
+    pub fn send(&mut self) -> Result<AnalyzeResult, EsError> {
+        let response = self.client.do_es_op(
+            &self.url(),
+            Retry::Default,
+            Method::POST,
+            Some(self.body.as_bytes().to_vec()),
+        )?;
 
         Ok(response.read_response()?)
     }
 }
 
+#[cfg(feature = "async")]
+impl<'a, 'b> AnalyzeOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&mut self) -> Result<AnalyzeResult, EsError> {
+        let response = self
+            .client
+            .do_es_op(
+                &self.url(),
+                Retry::Default,
+                Method::POST,
+                Some(self.body.as_bytes().to_vec()),
+            )
+            .await?;
+
+        response.read_response()
+    }
+}
+
 impl Client {
     /// Analyze
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-analyze.html
-    pub fn analyze<'a>(&'a mut self, body: &'a str) -> AnalyzeOperation {
+    pub fn analyze<'a>(&'a self, body: &'a str) -> AnalyzeOperation {
+        AnalyzeOperation::new(self, body)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Analyze
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-analyze.html
+    pub fn analyze<'a>(&'a self, body: &'a str) -> AnalyzeOperation<'a, 'a, AsyncClient> {
         AnalyzeOperation::new(self, body)
     }
 }
@@ -17,25 +17,32 @@
 //! Implementation of the Bulk API
 
 use std::fmt;
+use std::thread;
+use std::time::Duration as StdDuration;
 
-use reqwest::StatusCode;
+use reqwest::{Method, StatusCode};
 
 use serde::{
     de::{Error, MapAccess, Visitor},
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use serde_json;
+use serde_json::{self, Value};
 
 use crate::{
-    error::EsError,
+    error::{unexpected_status, ErrorCause, EsError},
     json::{FieldBased, NoOuter, ShouldSkip},
+    retry::{self, Retry, RetryPolicy},
     units::Duration,
     Client, EsResponse,
 };
 
+#[cfg(feature = "async")]
+use crate::{async_client, AsyncClient};
+
 use super::{
     common::{OptionVal, Options, VersionType},
+    update::{Script, Update},
     ShardCountResult,
 };
 
@@ -44,7 +51,6 @@ pub enum ActionType {
     Index,
     Create,
     Delete,
-    /// WARNING - currently un-implemented
     Update,
 }
 
@@ -99,8 +105,19 @@ pub struct ActionOptions {
     retry_on_conflict: Option<u64>,
 }
 
+/// The line following the action line, for actions which have one
 #[derive(Debug, Serialize)]
-pub struct Action<X>(FieldBased<ActionType, ActionOptions, NoOuter>, Option<X>);
+#[serde(untagged)]
+enum ActionSource<X> {
+    Document(X),
+    Update(Update<X>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct Action<X>(
+    FieldBased<ActionType, ActionOptions, NoOuter>,
+    Option<ActionSource<X>>,
+);
 
 impl<S> Action<S>
 where
@@ -113,7 +130,7 @@ where
     pub fn index(document: S) -> Self {
         Action(
             FieldBased::new(ActionType::Index, Default::default(), NoOuter),
-            Some(document),
+            Some(ActionSource::Document(document)),
         )
     }
 
@@ -121,12 +138,12 @@ where
     pub fn create(document: S) -> Self {
         Action(
             FieldBased::new(ActionType::Create, Default::default(), NoOuter),
-            Some(document),
+            Some(ActionSource::Document(document)),
         )
     }
 
     /// Add the serialized version of this action to the bulk `String`.
-    fn add(&self, actstr: &mut String) -> Result<(), EsError> {
+    pub(crate) fn add(&self, actstr: &mut String) -> Result<(), EsError> {
         let command_str = serde_json::to_string(&self.0)?;
 
         actstr.push_str(&command_str);
@@ -166,7 +183,43 @@ impl<S> Action<S> {
         )
     }
 
-    // TODO - implement update
+    /// Update a document based on ID, with either a partial document or a
+    /// script.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use serde_json::json;
+    ///
+    /// use rs_es::operations::{bulk::Action, update::Update};
+    ///
+    /// let update_action: Action<()> =
+    ///     Action::update("doc_id", Update::doc(json!({"field": "value"})).with_doc_as_upsert(true))
+    ///         .with_retry_on_conflict(3u64);
+    /// ```
+    pub fn update<A: Into<String>>(id: A, update: Update<S>) -> Self {
+        Action(
+            FieldBased::new(
+                ActionType::Update,
+                ActionOptions {
+                    id: Some(id.into()),
+                    ..Default::default()
+                },
+                NoOuter,
+            ),
+            Some(ActionSource::Update(update)),
+        )
+    }
+
+    /// Update a document based on ID, by merging a partial document.
+    pub fn update_doc<A: Into<String>>(id: A, doc: Value) -> Self {
+        Action::update(id, Update::doc(doc))
+    }
+
+    /// Update a document based on ID, with a script.
+    pub fn update_script<A: Into<String>>(id: A, script: Script) -> Self {
+        Action::update(id, Update::script(script))
+    }
 
     add_inner_field!(with_index, index, String);
     add_inner_field!(with_doc_type, doc_type, String);
@@ -181,25 +234,27 @@ impl<S> Action<S> {
 }
 
 #[derive(Debug)]
-pub struct BulkOperation<'a, 'b, S: 'b> {
-    client: &'a mut Client,
+pub struct BulkOperation<'a, 'b, S: 'b, C = Client> {
+    client: &'a C,
     index: Option<&'b str>,
     doc_type: Option<&'b str>,
     actions: &'b [Action<S>],
     options: Options<'b>,
+    retry_policy: Option<&'b RetryPolicy>,
 }
 
-impl<'a, 'b, S> BulkOperation<'a, 'b, S>
+impl<'a, 'b, S, C> BulkOperation<'a, 'b, S, C>
 where
     S: Serialize,
 {
-    pub fn new(client: &'a mut Client, actions: &'b [Action<S>]) -> Self {
+    pub fn new(client: &'a C, actions: &'b [Action<S>]) -> Self {
         BulkOperation {
             client,
             index: None,
             doc_type: None,
             actions,
             options: Options::default(),
+            retry_policy: None,
         }
     }
 
@@ -216,20 +271,19 @@ where
     add_option!(with_consistency, "consistency");
     add_option!(with_refresh, "refresh");
 
+    /// Retry the bulk request according to the given policy.
+    ///
+    /// Unlike other operations, bulk requests are not retried by the client's
+    /// default policy, as not every action is safe to repeat (e.g. indexing a
+    /// document without an ID).  Setting a policy here is a declaration that
+    /// these actions are safe to repeat.
+    pub fn with_retry_policy(&'b mut self, retry_policy: &'b RetryPolicy) -> &'b mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
     fn format_url(&self) -> String {
-        let mut url = String::new();
-        url.push_str("/");
-        if let Some(index) = self.index {
-            url.push_str(index);
-            url.push_str("/");
-        }
-        if let Some(doc_type) = self.doc_type {
-            url.push_str(doc_type);
-            url.push_str("/");
-        }
-        url.push_str("_bulk");
-        url.push_str(&self.options.to_string());
-        url
+        bulk_url(self.index, self.doc_type, &self.options)
     }
 
     fn format_actions(&self) -> String {
@@ -240,6 +294,27 @@ where
         actstr
     }
 
+    /// The actions with the given indexes, to be retried
+    fn format_some_actions(&self, idxs: &[usize]) -> Result<String, EsError> {
+        let mut actstr = String::new();
+        for &idx in idxs {
+            self.actions[idx].add(&mut actstr)?;
+        }
+        Ok(actstr)
+    }
+
+    fn retry(&self) -> Retry<'b> {
+        match self.retry_policy {
+            Some(retry_policy) => Retry::Policy(retry_policy),
+            None => Retry::Never,
+        }
+    }
+}
+
+impl<'a, 'b, S> BulkOperation<'a, 'b, S>
+where
+    S: Serialize,
+{
     pub fn send(&self) -> Result<BulkResult, EsError> {
         //
         // This function does not use the standard GET/POST/DELETE functions of
@@ -248,20 +323,196 @@ where
         //
         // Various parts of the client are reused where it makes sense.
         //
-        let response = self.client.do_es_op(&self.format_url(), |url| {
-            self.client
-                .http_client
-                .post(url)
-                .body(self.format_actions())
-        })?;
-
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
+        let actions = self.format_actions();
+        let response = self.client.do_es_op(
+            &self.format_url(),
+            self.retry(),
+            Method::POST,
+            Some(actions.into_bytes()),
+        )?;
+        read_result(response)
+    }
+
+    /// Send the actions, then re-send any which were rejected but may succeed
+    /// if tried again, e.g. because the cluster is too busy.  Each re-send
+    /// only includes the rejected actions, and waits according to the policy,
+    /// until the maximum number of attempts.
+    ///
+    /// If a re-send fails entirely, the error is returned.
+    pub fn send_with_item_retry(
+        &self,
+        retry_policy: &RetryPolicy,
+    ) -> Result<BulkRetryResult, EsError> {
+        let mut state = ItemRetry::new(self.actions.len());
+        loop {
+            let actions = self.format_some_actions(&state.pending)?;
+            let response = self.client.do_es_op(
+                &self.format_url(),
+                self.retry(),
+                Method::POST,
+                Some(actions.into_bytes()),
+            )?;
+            state.record(read_result(response)?)?;
+            match state.backoff(retry_policy) {
+                Some(backoff) => thread::sleep(backoff),
+                None => return Ok(state.finish()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b, S> BulkOperation<'a, 'b, S, AsyncClient>
+where
+    S: Serialize,
+{
+    pub async fn send(&self) -> Result<BulkResult, EsError> {
+        let actions = self.format_actions();
+        let response = self
+            .client
+            .do_es_op(
+                &self.format_url(),
+                self.retry(),
+                Method::POST,
+                Some(actions.into_bytes()),
+            )
+            .await?;
+        read_result(response)
+    }
+
+    /// See `BulkOperation<Client>::send_with_item_retry`
+    pub async fn send_with_item_retry(
+        &self,
+        retry_policy: &RetryPolicy,
+    ) -> Result<BulkRetryResult, EsError> {
+        let mut state = ItemRetry::new(self.actions.len());
+        loop {
+            let actions = self.format_some_actions(&state.pending)?;
+            let response = self
+                .client
+                .do_es_op(
+                    &self.format_url(),
+                    self.retry(),
+                    Method::POST,
+                    Some(actions.into_bytes()),
+                )
+                .await?;
+            state.record(read_result(response)?)?;
+            match state.backoff(retry_policy) {
+                Some(backoff) => async_client::wait(backoff).await,
+                None => return Ok(state.finish()),
+            }
+        }
+    }
+}
+
+/// The outcome of each action, over each attempt of `send_with_item_retry`
+struct ItemRetry {
+    items: Vec<Option<ActionResult>>,
+    retried: Vec<bool>,
+    /// The indexes of the actions to send in the next attempt
+    pending: Vec<usize>,
+    attempts: u32,
+    took: u64,
+}
+
+impl ItemRetry {
+    fn new(actions: usize) -> ItemRetry {
+        ItemRetry {
+            items: (0..actions).map(|_| None).collect(),
+            retried: vec![false; actions],
+            pending: (0..actions).collect(),
+            attempts: 0,
+            took: 0,
+        }
+    }
+
+    /// Record the result of sending the pending actions, the items of which
+    /// are in the same order
+    fn record(&mut self, result: BulkResult) -> Result<(), EsError> {
+        if result.items.len() != self.pending.len() {
+            return Err(EsError::EsError(format!(
+                "Expected {} bulk items, received {}",
+                self.pending.len(),
+                result.items.len()
+            )));
+        }
+        self.attempts += 1;
+        self.took += result.took;
+
+        let mut pending = Vec::new();
+        for (&idx, item) in self.pending.iter().zip(result.items) {
+            if item.is_retryable() {
+                pending.push(idx);
+            }
+            self.retried[idx] = self.attempts > 1;
+            self.items[idx] = Some(item);
+        }
+        self.pending = pending;
+        Ok(())
+    }
+
+    /// How long to wait before the next attempt, or `None` if there should
+    /// not be one
+    fn backoff(&self, retry_policy: &RetryPolicy) -> Option<StdDuration> {
+        if self.pending.is_empty() || self.attempts >= retry_policy.max_attempts() {
+            return None;
+        }
+        let backoff = retry_policy.backoff(self.attempts);
+        log::warn!(
+            "Retrying {} rejected bulk actions in {:?}, attempt {} failed",
+            self.pending.len(),
+            backoff,
+            self.attempts
+        );
+        Some(backoff)
+    }
+
+    fn finish(self) -> BulkRetryResult {
+        let mut result = BulkRetryResult {
+            took: self.took,
+            attempts: self.attempts,
+            items: Vec::with_capacity(self.items.len()),
+            retried: Vec::new(),
+            failed: Vec::new(),
+            exhausted: self.pending,
+        };
+        for (idx, item) in self.items.into_iter().enumerate() {
+            let item = item.expect("Every action is sent at least once");
+            if item.is_error() {
+                if !item.is_retryable() {
+                    result.failed.push(idx);
+                }
+            } else if self.retried[idx] {
+                result.retried.push(idx);
+            }
+            result.items.push(item);
         }
+        result
+    }
+}
+
+/// The URL of a bulk request, with the default index and type, if any
+pub(crate) fn bulk_url(index: Option<&str>, doc_type: Option<&str>, options: &Options) -> String {
+    let mut url = String::new();
+    url.push_str("/");
+    if let Some(index) = index {
+        url.push_str(index);
+        url.push_str("/");
+    }
+    if let Some(doc_type) = doc_type {
+        url.push_str(doc_type);
+        url.push_str("/");
+    }
+    url.push_str("_bulk");
+    url.push_str(&options.to_string());
+    url
+}
+
+pub(crate) fn read_result<R: EsResponse>(response: R) -> Result<BulkResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
     }
 }
 
@@ -269,7 +520,23 @@ impl Client {
     /// Bulk
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
-    pub fn bulk<'a, 'b, S>(&'a mut self, actions: &'b [Action<S>]) -> BulkOperation<'a, 'b, S>
+    pub fn bulk<'a, 'b, S>(&'a self, actions: &'b [Action<S>]) -> BulkOperation<'a, 'b, S>
+    where
+        S: Serialize,
+    {
+        BulkOperation::new(self, actions)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Bulk
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
+    pub fn bulk<'a, 'b, S>(
+        &'a self,
+        actions: &'b [Action<S>],
+    ) -> BulkOperation<'a, 'b, S, AsyncClient>
     where
         S: Serialize,
     {
@@ -333,12 +600,41 @@ pub struct ActionResultInner {
     pub index: String,
     #[serde(rename = "_type")]
     pub doc_type: String,
-    #[serde(rename = "_version")]
+    #[serde(rename = "_id")]
+    pub id: Option<String>,
+    /// Zero if the action failed
+    #[serde(rename = "_version", default)]
     pub version: u64,
     pub status: u64,
-    #[serde(rename = "_shards")]
+    /// Zero if the action failed
+    #[serde(rename = "_shards", default)]
     pub shards: ShardCountResult,
     pub found: Option<bool>,
+    /// Why the action failed
+    pub error: Option<ErrorCause>,
+}
+
+impl ActionResult {
+    /// Whether this action failed, but may succeed if tried again, e.g.
+    /// because it was rejected by a busy node, or the shard was unavailable
+    pub fn is_retryable(&self) -> bool {
+        match self.inner.error {
+            Some(ref error) => {
+                error.error_type == "es_rejected_execution_exception"
+                    || match StatusCode::from_u16(self.inner.status as u16) {
+                        Ok(status) => retry::is_retryable_status(status),
+                        Err(_) => false,
+                    }
+            }
+            None => false,
+        }
+    }
+
+    /// Whether this action failed.  Deleting a missing document is not a
+    /// failure, it has `found` set to false.
+    pub fn is_error(&self) -> bool {
+        self.inner.error.is_some()
+    }
 }
 
 /// The result of a bulk operation
@@ -349,18 +645,56 @@ pub struct BulkResult {
     pub took: u64,
 }
 
+/// The result of a bulk operation where rejected actions were retried, see
+/// `BulkOperation::send_with_item_retry`
+#[derive(Debug)]
+pub struct BulkRetryResult {
+    /// The total time taken by ElasticSearch over all attempts
+    pub took: u64,
+    /// The number of requests sent
+    pub attempts: u32,
+    /// The final result of each action, in the same order as the actions
+    pub items: Vec<ActionResult>,
+    /// The indexes of the actions which succeeded after being retried
+    pub retried: Vec<usize>,
+    /// The indexes of the actions which failed with an error which will not
+    /// succeed if retried, e.g. a mapping error
+    pub failed: Vec<usize>,
+    /// The indexes of the actions which were still being rejected after the
+    /// last attempt
+    pub exhausted: Vec<usize>,
+}
+
+impl BulkRetryResult {
+    /// Whether any action failed
+    pub fn errors(&self) -> bool {
+        !(self.failed.is_empty() && self.exhausted.is_empty())
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
-    use crate::tests::{clean_db, make_client, TestDocument};
+    use std::time::Duration;
+
+    use reqwest::Method;
+
+    use serde_json::json;
+
+    use crate::{
+        operations::update::{Script, Update},
+        retry::RetryPolicy,
+        tests::{clean_db, make_client, make_mock_client, TestDocument},
+        transport::MockTransport,
+    };
 
-    use super::Action;
+    use super::{Action, ActionType};
 
     #[test]
     fn test_bulk() {
         let index_name = "test_bulk";
-        let mut client = make_client();
+        let client = make_client("test_bulk");
 
-        clean_db(&mut client, index_name);
+        clean_db(&client, index_name);
 
         let actions: Vec<Action<TestDocument>> = (1..10)
             .map(|i| {
@@ -381,4 +715,213 @@ pub mod tests {
         assert_eq!(false, result.errors);
         assert_eq!(9, result.items.len());
     }
+
+    #[test]
+    fn test_bulk_mocked() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_bulk",
+            200,
+            r#"{"took": 3, "errors": false, "items": [
+                {"index": {"_index": "test_idx", "_type": "test_type", "_id": "1",
+                           "_version": 1, "status": 201,
+                           "_shards": {"total": 2, "successful": 1, "failed": 0}}},
+                {"delete": {"_index": "test_idx", "_type": "test_type", "_id": "2",
+                            "_version": 2, "status": 200, "found": true,
+                            "_shards": {"total": 2, "successful": 1, "failed": 0}}}
+            ]}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let actions = vec![
+            Action::index(TestDocument::new()).with_id("1"),
+            Action::delete("2"),
+        ];
+        let result = client.bulk(&actions).with_index("test_idx").send().unwrap();
+
+        assert!(!result.errors);
+        assert_eq!(2, result.items.len());
+        assert!(matches!(result.items[0].action, ActionType::Index));
+        assert_eq!(201, result.items[0].inner.status);
+        assert!(matches!(result.items[1].action, ActionType::Delete));
+        assert_eq!(Some(true), result.items[1].inner.found);
+
+        let requests = transport.requests();
+        let body = String::from_utf8(requests[0].body().unwrap().to_vec()).unwrap();
+        assert_eq!(3, body.lines().count());
+        assert!(body.starts_with("{\"index\":{\"_id\":\"1\"}}\n"));
+    }
+
+    #[test]
+    fn test_bulk_update_mocked() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/_bulk",
+            200,
+            r#"{"took": 2, "errors": false, "items": [
+                {"update": {"_index": "test_idx", "_type": "test_type", "_id": "1",
+                            "_version": 2, "status": 200,
+                            "_shards": {"total": 2, "successful": 1, "failed": 0}}},
+                {"update": {"_index": "test_idx", "_type": "test_type", "_id": "2",
+                            "_version": 1, "status": 201,
+                            "_shards": {"total": 2, "successful": 1, "failed": 0}}}
+            ]}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let actions = vec![
+            Action::update_doc("1", json!({"int_field": 5})).with_retry_on_conflict(2u64),
+            Action::update(
+                "2",
+                Update::script(Script::new("ctx._source.int_field += 1"))
+                    .with_upsert(TestDocument::new()),
+            ),
+        ];
+        let result = client
+            .bulk(&actions)
+            .with_index("test_idx")
+            .with_doc_type("test_type")
+            .send()
+            .unwrap();
+
+        assert!(!result.errors);
+        assert!(matches!(result.items[0].action, ActionType::Update));
+        assert_eq!(201, result.items[1].inner.status);
+
+        let requests = transport.requests();
+        let body = String::from_utf8(requests[0].body().unwrap().to_vec()).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(4, lines.len());
+        assert_eq!(
+            "{\"update\":{\"_id\":\"1\",\"_retry_on_conflict\":2}}",
+            lines[0]
+        );
+        assert_eq!("{\"doc\":{\"int_field\":5}}", lines[1]);
+        assert_eq!("{\"update\":{\"_id\":\"2\"}}", lines[2]);
+        assert!(lines[3]
+            .starts_with("{\"script\":{\"inline\":\"ctx._source.int_field += 1\"},\"upsert\":{"));
+    }
+
+    fn item(id: &str, status: u64, error: Option<&str>) -> String {
+        match error {
+            Some(error_type) => format!(
+                r#"{{"index": {{"_index": "test_idx", "_type": "test_type", "_id": "{}",
+                                "status": {}, "error": {{"type": "{}", "reason": "..."}}}}}}"#,
+                id, status, error_type
+            ),
+            None => format!(
+                r#"{{"index": {{"_index": "test_idx", "_type": "test_type", "_id": "{}",
+                                "_version": 1, "status": {},
+                                "_shards": {{"total": 2, "successful": 1, "failed": 0}}}}}}"#,
+                id, status
+            ),
+        }
+    }
+
+    fn bulk_result(items: &[String]) -> String {
+        format!(
+            r#"{{"took": 5, "errors": true, "items": [{}]}}"#,
+            items.join(",")
+        )
+    }
+
+    #[test]
+    fn test_bulk_item_retry() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/test_type/_bulk",
+                200,
+                bulk_result(&[
+                    item("1", 201, None),
+                    item("2", 429, Some("es_rejected_execution_exception")),
+                    item("3", 400, Some("mapper_parsing_exception")),
+                    item("4", 503, Some("unavailable_shards_exception")),
+                ]),
+            )
+            .with_response(
+                Method::POST,
+                "/test_idx/test_type/_bulk",
+                200,
+                bulk_result(&[
+                    item("2", 201, None),
+                    item("4", 429, Some("es_rejected_execution_exception")),
+                ]),
+            )
+            .with_response(
+                Method::POST,
+                "/test_idx/test_type/_bulk",
+                200,
+                bulk_result(&[item("4", 201, None)]),
+            );
+        let client = make_mock_client(&transport);
+
+        let actions: Vec<Action<TestDocument>> = (1..5)
+            .map(|i| Action::index(TestDocument::new().with_int_field(i)).with_id(i.to_string()))
+            .collect();
+        let retry_policy = RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_initial_backoff(Duration::from_millis(1));
+        let result = client
+            .bulk(&actions)
+            .with_index("test_idx")
+            .with_doc_type("test_type")
+            .send_with_item_retry(&retry_policy)
+            .unwrap();
+
+        assert_eq!(3, result.attempts);
+        assert_eq!(15, result.took);
+        assert!(result.errors());
+        assert_eq!(vec![1, 3], result.retried);
+        assert_eq!(vec![2], result.failed);
+        assert!(result.exhausted.is_empty());
+        let ids: Vec<&str> = result
+            .items
+            .iter()
+            .map(|item| item.inner.id.as_ref().unwrap().as_str())
+            .collect();
+        assert_eq!(vec!["1", "2", "3", "4"], ids);
+        assert_eq!(201, result.items[3].inner.status);
+
+        let bodies: Vec<String> = transport
+            .requests()
+            .iter()
+            .map(|request| String::from_utf8(request.body().unwrap().to_vec()).unwrap())
+            .collect();
+        assert_eq!(8, bodies[0].lines().count());
+        assert!(bodies[1].starts_with("{\"index\":{\"_id\":\"2\"}}\n"));
+        assert!(bodies[1].contains("{\"index\":{\"_id\":\"4\"}}\n"));
+        assert_eq!(
+            "{\"index\":{\"_id\":\"4\"}}",
+            bodies[2].lines().next().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bulk_item_retry_exhausted() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_bulk",
+            200,
+            bulk_result(&[item("1", 429, Some("es_rejected_execution_exception"))]),
+        );
+        let client = make_mock_client(&transport);
+
+        let actions = vec![Action::index(TestDocument::new())
+            .with_index("test_idx")
+            .with_id("1")];
+        let retry_policy = RetryPolicy::default()
+            .with_max_attempts(2)
+            .with_initial_backoff(Duration::from_millis(1));
+        let result = client
+            .bulk(&actions)
+            .send_with_item_retry(&retry_policy)
+            .unwrap();
+
+        assert_eq!(2, result.attempts);
+        assert!(result.errors());
+        assert!(result.failed.is_empty());
+        assert_eq!(vec![0], result.exhausted);
+        assert_eq!(2, transport.requests().len());
+    }
 }
@@ -0,0 +1,611 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A processor which collects bulk actions, sending them in batches.
+//!
+//! Actions are added one at a time with
+//! [`BulkProcessor::add`](struct.BulkProcessor.html#method.add).  They are
+//! sent when the number of actions, or the size of the request, reaches a
+//! limit; and optionally at a regular interval.  Anything left is sent when the
+//! processor is closed or dropped.
+//!
+//! Failed actions, and failed requests, are passed to a callback.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::{
+    mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender},
+    Arc, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use reqwest::Method;
+
+use serde::Serialize;
+
+use crate::{
+    error::EsError,
+    retry::{Retry, RetryPolicy},
+    Client,
+};
+
+use super::{
+    bulk::{bulk_url, read_result, Action, ActionResult},
+    common::Options,
+};
+
+/// Something that went wrong when sending actions
+#[derive(Debug)]
+pub enum BulkFailure<'a> {
+    /// An action which was rejected
+    Item(&'a ActionResult),
+
+    /// A request which failed entirely, none of its actions, the number of
+    /// which is given, were applied
+    Request(&'a EsError, usize),
+}
+
+pub(crate) type FailureHandler = Arc<dyn Fn(BulkFailure) + Send + Sync>;
+
+/// The settings shared by `BulkProcessor` and
+/// [`BulkStreamOperation`](../bulk_stream/struct.BulkStreamOperation.html),
+/// both of which send actions in requests of a limited number and size
+pub(crate) struct ChunkSettings {
+    pub(crate) index: Option<String>,
+    pub(crate) doc_type: Option<String>,
+    pub(crate) options: Options<'static>,
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    pub(crate) bulk_actions: usize,
+    pub(crate) bulk_size: usize,
+    pub(crate) on_failure: Option<FailureHandler>,
+}
+
+impl Default for ChunkSettings {
+    fn default() -> Self {
+        ChunkSettings {
+            index: None,
+            doc_type: None,
+            options: Options::default(),
+            retry_policy: None,
+            bulk_actions: 1000,
+            bulk_size: 5 * 1024 * 1024,
+            on_failure: None,
+        }
+    }
+}
+
+impl ChunkSettings {
+    pub(crate) fn url(&self) -> String {
+        bulk_url(
+            self.index.as_deref(),
+            self.doc_type.as_deref(),
+            &self.options,
+        )
+    }
+
+    pub(crate) fn retry(&self) -> Retry<'_> {
+        match self.retry_policy {
+            Some(ref retry_policy) => Retry::Policy(retry_policy),
+            None => Retry::Never,
+        }
+    }
+
+    /// Should a request with this many actions, of this size, be sent
+    pub(crate) fn is_full(&self, actions: usize, bytes: usize) -> bool {
+        actions >= self.bulk_actions || bytes >= self.bulk_size
+    }
+
+    pub(crate) fn failed(&self, failure: BulkFailure) {
+        match self.on_failure {
+            Some(ref on_failure) => on_failure(failure),
+            None => log::warn!("Bulk failure: {:?}", failure),
+        }
+    }
+}
+
+impl fmt::Debug for ChunkSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChunkSettings")
+            .field("url", &self.url())
+            .field("retry_policy", &self.retry_policy)
+            .field("bulk_actions", &self.bulk_actions)
+            .field("bulk_size", &self.bulk_size)
+            .finish()
+    }
+}
+
+/// The setters of `ChunkSettings`, for a builder with a `settings` field
+macro_rules! chunk_settings {
+    () => {
+        /// The index of actions which do not specify one
+        pub fn with_index<T: Into<String>>(mut self, index: T) -> Self {
+            self.settings.index = Some(index.into());
+            self
+        }
+
+        /// The type of actions which do not specify one
+        pub fn with_doc_type<T: Into<String>>(mut self, doc_type: T) -> Self {
+            self.settings.doc_type = Some(doc_type.into());
+            self
+        }
+
+        /// The `refresh` option of each request
+        pub fn with_refresh(mut self, refresh: bool) -> Self {
+            self.settings.options.push("refresh", refresh);
+            self
+        }
+
+        /// Retry each request according to the given policy.  As with
+        /// `BulkOperation::with_retry_policy` this should only be set if the
+        /// actions are safe to repeat.
+        pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+            self.settings.retry_policy = Some(retry_policy);
+            self
+        }
+
+        /// Send a request once it has this many actions, the default is 1000
+        pub fn with_bulk_actions(mut self, bulk_actions: usize) -> Self {
+            self.settings.bulk_actions = bulk_actions;
+            self
+        }
+
+        /// Send a request once its actions reach this size in bytes, the
+        /// default is 5MB.  A request may be larger than this by up to one
+        /// action.
+        pub fn with_bulk_size(mut self, bulk_size: usize) -> Self {
+            self.settings.bulk_size = bulk_size;
+            self
+        }
+
+        /// Called for each action which fails, and each request which fails
+        /// entirely.  Otherwise failures are logged.
+        pub fn with_on_failure<F>(mut self, on_failure: F) -> Self
+        where
+            F: Fn(BulkFailure) + Send + Sync + 'static,
+        {
+            self.settings.on_failure = Some(Arc::new(on_failure));
+            self
+        }
+    };
+}
+
+/// Actions waiting to be sent, already serialized
+#[derive(Debug, Default)]
+struct Batch {
+    body: String,
+    actions: usize,
+}
+
+/// The parts of a `BulkProcessor` shared with its threads
+struct Shared {
+    client: Client,
+    url: String,
+    settings: ChunkSettings,
+    batch: Mutex<Batch>,
+    /// For sending batches to the worker threads, `None` if the batches are
+    /// sent by the thread flushing them, or the processor is closed
+    workers: Mutex<Option<SyncSender<Batch>>>,
+}
+
+impl Shared {
+    fn add(&self, body: &str) {
+        let full = {
+            let mut batch = self.batch.lock().unwrap();
+            batch.body.push_str(body);
+            batch.actions += 1;
+            if self.settings.is_full(batch.actions, batch.body.len()) {
+                Some(mem::take(&mut *batch))
+            } else {
+                None
+            }
+        };
+        if let Some(batch) = full {
+            self.dispatch(batch);
+        }
+    }
+
+    fn flush(&self) {
+        let batch = mem::take(&mut *self.batch.lock().unwrap());
+        if batch.actions > 0 {
+            self.dispatch(batch);
+        }
+    }
+
+    /// Send the batch with a worker thread, waiting for one to be free, or
+    /// with this thread if there are none
+    fn dispatch(&self, batch: Batch) {
+        let workers = self.workers.lock().unwrap().clone();
+        let batch = match workers {
+            Some(workers) => match workers.send(batch) {
+                Ok(()) => return,
+                Err(mpsc::SendError(batch)) => batch,
+            },
+            None => batch,
+        };
+        self.send(batch);
+    }
+
+    fn send(&self, batch: Batch) {
+        let result = self
+            .client
+            .do_es_op(
+                &self.url,
+                self.settings.retry(),
+                Method::POST,
+                Some(batch.body.into_bytes()),
+            )
+            .and_then(read_result);
+        match result {
+            Ok(result) => {
+                if result.errors {
+                    for item in result.items.iter().filter(|item| item.is_error()) {
+                        self.settings.failed(BulkFailure::Item(item));
+                    }
+                }
+            }
+            Err(err) => self
+                .settings
+                .failed(BulkFailure::Request(&err, batch.actions)),
+        }
+    }
+}
+
+/// Builds a `BulkProcessor`, see
+/// [`Client::bulk_processor`](../../struct.Client.html#method.bulk_processor).
+pub struct BulkProcessorBuilder<'a> {
+    client: &'a Client,
+    settings: ChunkSettings,
+    flush_interval: Option<Duration>,
+    concurrent_requests: usize,
+}
+
+impl<'a> BulkProcessorBuilder<'a> {
+    fn new(client: &'a Client) -> Self {
+        BulkProcessorBuilder {
+            client,
+            settings: ChunkSettings::default(),
+            flush_interval: None,
+            concurrent_requests: 1,
+        }
+    }
+
+    chunk_settings!();
+
+    /// Send any actions added at this interval, regardless of how many there
+    /// are.  By default there is no interval.
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = Some(flush_interval);
+        self
+    }
+
+    /// The number of requests which can be sent at the same time, by
+    /// background threads.  If all are busy, adding an action which would
+    /// cause another request waits until one is finished.
+    ///
+    /// The default is one.  Zero means each request is sent by the thread
+    /// which caused it, as part of `add`, `flush` or `close`.
+    pub fn with_concurrent_requests(mut self, concurrent_requests: usize) -> Self {
+        self.concurrent_requests = concurrent_requests;
+        self
+    }
+
+    pub fn build<S: Serialize>(self) -> BulkProcessor<S> {
+        let (worker_tx, worker_rx) = mpsc::sync_channel(0);
+        let shared = Arc::new(Shared {
+            client: self.client.clone(),
+            url: self.settings.url(),
+            settings: self.settings,
+            batch: Mutex::new(Batch::default()),
+            workers: Mutex::new(if self.concurrent_requests > 0 {
+                Some(worker_tx)
+            } else {
+                None
+            }),
+        });
+
+        let worker_rx = Arc::new(Mutex::new(worker_rx));
+        let mut threads: Vec<JoinHandle<()>> = (0..self.concurrent_requests)
+            .map(|_| {
+                let shared = shared.clone();
+                let worker_rx = worker_rx.clone();
+                thread::spawn(move || run_worker(&shared, &worker_rx))
+            })
+            .collect();
+
+        let stop = self.flush_interval.map(|flush_interval| {
+            let (stop_tx, stop_rx) = mpsc::channel();
+            let shared = shared.clone();
+            threads.push(thread::spawn(move || {
+                run_flusher(&shared, &stop_rx, flush_interval)
+            }));
+            stop_tx
+        });
+
+        BulkProcessor {
+            shared,
+            threads,
+            stop,
+            closed: false,
+            phantom: PhantomData,
+        }
+    }
+}
+
+fn run_worker(shared: &Shared, batches: &Mutex<Receiver<Batch>>) {
+    loop {
+        let batch = batches.lock().unwrap().recv();
+        match batch {
+            Ok(batch) => shared.send(batch),
+            Err(_) => return,
+        }
+    }
+}
+
+fn run_flusher(shared: &Shared, stop: &Receiver<()>, flush_interval: Duration) {
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(flush_interval) {
+        shared.flush();
+    }
+}
+
+/// Collects bulk actions, and sends them in batches.
+///
+/// Actions can be added from many threads at once.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rs_es::{operations::bulk::Action, Client};
+///
+/// let client = Client::init("http://localhost:9200").unwrap();
+/// let processor = client
+///     .bulk_processor()
+///     .with_index("index_name")
+///     .with_doc_type("type_name")
+///     .with_bulk_actions(500)
+///     .with_on_failure(|failure| eprintln!("Failed: {:?}", failure))
+///     .build();
+///
+/// for i in 0..10_000 {
+///     processor.add(Action::index(i)).unwrap();
+/// }
+/// processor.close();
+/// ```
+pub struct BulkProcessor<S> {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+    stop: Option<Sender<()>>,
+    closed: bool,
+    phantom: PhantomData<fn(S)>,
+}
+
+impl<S: Serialize> BulkProcessor<S> {
+    /// Add an action, this may send a request.  An error is only returned if
+    /// the action cannot be serialized, failures to send are passed to the
+    /// callback.
+    pub fn add(&self, action: Action<S>) -> Result<(), EsError> {
+        let mut body = String::new();
+        action.add(&mut body)?;
+        self.shared.add(&body);
+        Ok(())
+    }
+}
+
+impl<S> BulkProcessor<S> {
+    /// Send the actions added so far
+    pub fn flush(&self) {
+        self.shared.flush();
+    }
+
+    /// Send any remaining actions, and wait for all requests to finish
+    pub fn close(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if self.closed {
+            return;
+        }
+        self.closed = true;
+        self.stop.take();
+        self.shared.flush();
+        self.shared.workers.lock().unwrap().take();
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                log::error!("Bulk processor thread panicked");
+            }
+        }
+    }
+}
+
+impl<S> Drop for BulkProcessor<S> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl<S> fmt::Debug for BulkProcessor<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BulkProcessor")
+            .field("url", &self.shared.url)
+            .field("threads", &self.threads.len())
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+impl Client {
+    /// A processor to collect bulk actions and send them in batches
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
+    pub fn bulk_processor(&self) -> BulkProcessorBuilder<'_> {
+        BulkProcessorBuilder::new(self)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use reqwest::Method;
+
+    use crate::{
+        operations::bulk::Action,
+        tests::{make_mock_client, TestDocument},
+        transport::MockTransport,
+    };
+
+    use super::BulkFailure;
+
+    const OK_RESULT: &str = r#"{"took": 1, "errors": false, "items": []}"#;
+
+    fn action_counts(transport: &MockTransport) -> Vec<usize> {
+        transport
+            .requests()
+            .iter()
+            .map(|request| {
+                String::from_utf8_lossy(request.body().unwrap())
+                    .lines()
+                    .count()
+                    / 2
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_flush_on_count_and_close() {
+        let transport =
+            MockTransport::new().with_response(Method::POST, "/test_idx/_bulk", 200, OK_RESULT);
+        let client = make_mock_client(&transport);
+
+        let processor = client
+            .bulk_processor()
+            .with_index("test_idx")
+            .with_bulk_actions(3)
+            .with_concurrent_requests(0)
+            .build();
+        for i in 0..7 {
+            processor
+                .add(Action::index(TestDocument::new().with_int_field(i)))
+                .unwrap();
+        }
+        assert_eq!(vec![3, 3], action_counts(&transport));
+
+        processor.close();
+        assert_eq!(vec![3, 3, 1], action_counts(&transport));
+    }
+
+    #[test]
+    fn test_flush_on_size_and_drop() {
+        let transport = MockTransport::new().with_response(Method::POST, "/_bulk", 200, OK_RESULT);
+        let client = make_mock_client(&transport);
+        {
+            let processor = client
+                .bulk_processor()
+                .with_bulk_size(150)
+                .with_concurrent_requests(2)
+                .build();
+            for _ in 0..5 {
+                // Each action is 91 bytes
+                processor
+                    .add(Action::index(TestDocument::new()).with_index("test_idx"))
+                    .unwrap();
+            }
+        }
+        let mut counts = action_counts(&transport);
+        counts.sort();
+        assert_eq!(vec![1, 2, 2], counts);
+    }
+
+    #[test]
+    fn test_flush_interval() {
+        let transport =
+            MockTransport::new().with_response(Method::POST, "/test_idx/_bulk", 200, OK_RESULT);
+        let client = make_mock_client(&transport);
+
+        let processor = client
+            .bulk_processor()
+            .with_index("test_idx")
+            .with_flush_interval(Duration::from_millis(10))
+            .build();
+        processor.add(Action::index(TestDocument::new())).unwrap();
+        for _ in 0..100 {
+            if !transport.requests().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(vec![1], action_counts(&transport));
+        processor.close();
+        assert_eq!(1, transport.requests().len());
+    }
+
+    #[test]
+    fn test_failures() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/_bulk",
+                200,
+                r#"{"took": 3, "errors": true, "items": [
+                    {"index": {"_index": "test_idx", "_type": "test_type", "_id": "1",
+                               "_version": 1, "status": 201,
+                               "_shards": {"total": 2, "successful": 1, "failed": 0}}},
+                    {"index": {"_index": "test_idx", "_type": "test_type", "_id": "2",
+                               "status": 400,
+                               "error": {"type": "mapper_parsing_exception",
+                                         "reason": "failed to parse [int_field]"}}}
+                ]}"#,
+            )
+            .with_response(Method::POST, "/test_idx/_bulk", 400, "{}");
+        let client = make_mock_client(&transport);
+
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let processor = {
+            let failures = failures.clone();
+            client
+                .bulk_processor()
+                .with_index("test_idx")
+                .with_bulk_actions(2)
+                .with_on_failure(move |failure| {
+                    let description = match failure {
+                        BulkFailure::Item(item) => format!(
+                            "{} {}",
+                            item.inner.id.as_ref().unwrap(),
+                            item.inner.error.as_ref().unwrap().error_type
+                        ),
+                        BulkFailure::Request(_, actions) => format!("request {}", actions),
+                    };
+                    failures.lock().unwrap().push(description);
+                })
+                .build()
+        };
+        for i in 1..4 {
+            processor
+                .add(Action::index(TestDocument::new()).with_id(i.to_string()))
+                .unwrap();
+        }
+        processor.close();
+
+        assert_eq!(
+            vec!["2 mapper_parsing_exception", "request 1"],
+            *failures.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,393 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Sending a stream of bulk actions in chunks.
+//!
+//! Unlike [`BulkOperation`](../bulk/struct.BulkOperation.html), which
+//! serializes every action into a single request, the actions are taken from
+//! an iterator and serialized only when the chunk they belong to is about to
+//! be sent.  At most one chunk is held in memory, so the stream can be much
+//! larger than the memory available, e.g. the rows of a database cursor.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+
+use serde::Serialize;
+
+use crate::{error::EsError, retry::RetryPolicy, Client};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{
+    bulk::{read_result, Action, BulkResult},
+    bulk_processor::{BulkFailure, ChunkSettings},
+};
+
+/// Serialized actions, to be sent in one request
+#[derive(Debug)]
+struct Chunk {
+    body: String,
+    actions: usize,
+}
+
+/// Sends the actions of an iterator in chunks, see `Client::bulk_stream`
+#[derive(Debug)]
+pub struct BulkStreamOperation<'a, I, C = Client> {
+    client: &'a C,
+    actions: I,
+    settings: ChunkSettings,
+}
+
+impl<'a, I, C, S> BulkStreamOperation<'a, I, C>
+where
+    I: Iterator<Item = Action<S>>,
+    S: Serialize,
+{
+    fn new(client: &'a C, actions: I) -> Self {
+        BulkStreamOperation {
+            client,
+            actions,
+            settings: ChunkSettings::default(),
+        }
+    }
+
+    chunk_settings!();
+
+    /// Serialize the next actions from the iterator, or `None` if there are
+    /// no more
+    fn next_chunk(&mut self) -> Result<Option<Chunk>, EsError> {
+        let mut chunk = Chunk {
+            body: String::new(),
+            actions: 0,
+        };
+        while chunk.actions == 0 || !self.settings.is_full(chunk.actions, chunk.body.len()) {
+            match self.actions.next() {
+                Some(action) => {
+                    action.add(&mut chunk.body)?;
+                    chunk.actions += 1;
+                }
+                None => break,
+            }
+        }
+        if chunk.actions == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(chunk))
+        }
+    }
+}
+
+impl<'a, I, S> BulkStreamOperation<'a, I>
+where
+    I: Iterator<Item = Action<S>>,
+    S: Serialize,
+{
+    /// Send every action, one chunk at a time.
+    ///
+    /// Failed actions are passed to the callback.  If a request fails
+    /// entirely, it is also passed to the callback, sending stops and the
+    /// error is returned; the actions of any earlier chunks will have been
+    /// applied.
+    pub fn send(mut self) -> Result<BulkStreamResult, EsError> {
+        let url = self.settings.url();
+        let mut result = BulkStreamResult::default();
+        while let Some(chunk) = self.next_chunk()? {
+            let start = Instant::now();
+            let bytes = chunk.body.len();
+            let response = self
+                .client
+                .do_es_op(
+                    &url,
+                    self.settings.retry(),
+                    Method::POST,
+                    Some(chunk.body.into_bytes()),
+                )
+                .and_then(read_result);
+            result.record(&self.settings, chunk.actions, bytes, start, response)?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, I, S> BulkStreamOperation<'a, I, AsyncClient>
+where
+    I: Iterator<Item = Action<S>>,
+    S: Serialize,
+{
+    /// See `BulkStreamOperation<Client>::send`
+    pub async fn send(mut self) -> Result<BulkStreamResult, EsError> {
+        let url = self.settings.url();
+        let mut result = BulkStreamResult::default();
+        while let Some(chunk) = self.next_chunk()? {
+            let start = Instant::now();
+            let bytes = chunk.body.len();
+            let response = self
+                .client
+                .do_es_op(
+                    &url,
+                    self.settings.retry(),
+                    Method::POST,
+                    Some(chunk.body.into_bytes()),
+                )
+                .await
+                .and_then(read_result);
+            result.record(&self.settings, chunk.actions, bytes, start, response)?;
+        }
+        Ok(result)
+    }
+}
+
+impl Client {
+    /// Send bulk actions from an iterator, in chunks of a limited number of
+    /// actions and size.  Each action is serialized only when its chunk is
+    /// about to be sent.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
+    pub fn bulk_stream<I, S>(&self, actions: I) -> BulkStreamOperation<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = Action<S>>,
+        S: Serialize,
+    {
+        BulkStreamOperation::new(self, actions.into_iter())
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Send bulk actions from an iterator, in chunks of a limited number of
+    /// actions and size.  Each action is serialized only when its chunk is
+    /// about to be sent.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
+    pub fn bulk_stream<I, S>(&self, actions: I) -> BulkStreamOperation<'_, I::IntoIter, AsyncClient>
+    where
+        I: IntoIterator<Item = Action<S>>,
+        S: Serialize,
+    {
+        BulkStreamOperation::new(self, actions.into_iter())
+    }
+}
+
+/// The statistics of one request of a bulk stream
+#[derive(Debug)]
+pub struct BulkChunkStats {
+    /// The number of actions sent
+    pub actions: usize,
+    /// The size of the request in bytes
+    pub bytes: usize,
+    /// The number of actions which failed
+    pub failed: usize,
+    /// The time taken by ElasticSearch, in milliseconds
+    pub took: u64,
+    /// The time taken to send the request and read the response
+    pub elapsed: Duration,
+}
+
+/// The result of a bulk stream
+#[derive(Debug, Default)]
+pub struct BulkStreamResult {
+    /// The number of actions sent
+    pub sent: u64,
+    /// The number of actions which failed
+    pub failed: u64,
+    /// Each request, in the order they were sent
+    pub chunks: Vec<BulkChunkStats>,
+}
+
+impl BulkStreamResult {
+    /// Record the result of a request, passing any failures to the callback
+    fn record(
+        &mut self,
+        settings: &ChunkSettings,
+        actions: usize,
+        bytes: usize,
+        start: Instant,
+        result: Result<BulkResult, EsError>,
+    ) -> Result<(), EsError> {
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                settings.failed(BulkFailure::Request(&err, actions));
+                return Err(err);
+            }
+        };
+        let mut failed = 0;
+        for item in result.items.iter().filter(|item| item.is_error()) {
+            failed += 1;
+            settings.failed(BulkFailure::Item(item));
+        }
+        self.sent += actions as u64;
+        self.failed += failed as u64;
+        self.chunks.push(BulkChunkStats {
+            actions,
+            bytes,
+            failed,
+            took: result.took,
+            elapsed: start.elapsed(),
+        });
+        Ok(())
+    }
+
+    /// The total time taken by ElasticSearch, in milliseconds
+    pub fn took(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.took).sum()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use reqwest::Method;
+
+    use crate::{
+        operations::{bulk::Action, bulk_processor::BulkFailure},
+        tests::{make_mock_client, TestDocument},
+        transport::MockTransport,
+    };
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
+
+    const OK_RESULT: &str = r#"{"took": 2, "errors": false, "items": []}"#;
+
+    #[test]
+    fn test_bulk_stream_chunks() {
+        let transport =
+            MockTransport::new().with_response(Method::POST, "/test_idx/_bulk", 200, OK_RESULT);
+        let client = make_mock_client(&transport);
+
+        let actions = (0..7).map(|i| Action::index(TestDocument::new().with_int_field(i)));
+        let result = client
+            .bulk_stream(actions)
+            .with_index("test_idx")
+            .with_bulk_actions(3)
+            .send()
+            .unwrap();
+
+        assert_eq!(7, result.sent);
+        assert_eq!(0, result.failed);
+        assert_eq!(6, result.took());
+        let counts: Vec<usize> = result.chunks.iter().map(|chunk| chunk.actions).collect();
+        assert_eq!(vec![3, 3, 1], counts);
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        for (request, chunk) in requests.iter().zip(result.chunks.iter()) {
+            let body = request.body().unwrap();
+            assert_eq!(chunk.bytes, body.len());
+            assert_eq!(
+                chunk.actions * 2,
+                String::from_utf8_lossy(body).lines().count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_bulk_stream_size_and_failures() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/_bulk",
+                200,
+                r#"{"took": 3, "errors": true, "items": [
+                    {"index": {"_index": "test_idx", "_type": "test_type", "_id": "1",
+                               "_version": 1, "status": 201,
+                               "_shards": {"total": 1, "successful": 1, "failed": 0}}},
+                    {"index": {"_index": "test_idx", "_type": "test_type", "_id": "2",
+                               "status": 400,
+                               "error": {"type": "mapper_parsing_exception",
+                                         "reason": "failed to parse"}}}
+                ]}"#,
+            )
+            .with_response(Method::POST, "/_bulk", 200, OK_RESULT);
+        let client = make_mock_client(&transport);
+
+        let action = || Action::index(TestDocument::new()).with_index("test_idx");
+        let mut one_action = String::new();
+        action().add(&mut one_action).unwrap();
+
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let result = {
+            let failures = failures.clone();
+            client
+                .bulk_stream(vec![action(), action(), action()])
+                .with_bulk_size(one_action.len() + 1)
+                .with_on_failure(move |failure| {
+                    if let BulkFailure::Item(item) = failure {
+                        failures
+                            .lock()
+                            .unwrap()
+                            .push(item.inner.id.clone().unwrap());
+                    }
+                })
+                .send()
+                .unwrap()
+        };
+
+        assert_eq!(3, result.sent);
+        assert_eq!(1, result.failed);
+        assert_eq!(vec!["2"], *failures.lock().unwrap());
+        let counts: Vec<usize> = result.chunks.iter().map(|chunk| chunk.actions).collect();
+        assert_eq!(vec![2, 1], counts);
+        let failed: Vec<usize> = result.chunks.iter().map(|chunk| chunk.failed).collect();
+        assert_eq!(vec![1, 0], failed);
+    }
+
+    #[test]
+    fn test_bulk_stream_error() {
+        let transport = MockTransport::new().with_response(Method::POST, "/_bulk", 500, "");
+        let client = make_mock_client(&transport);
+
+        let requests_failed = Arc::new(Mutex::new(0));
+        let actions = vec![Action::index(TestDocument::new()).with_index("test_idx")];
+        let result = {
+            let requests_failed = requests_failed.clone();
+            client
+                .bulk_stream(actions)
+                .with_on_failure(move |failure| {
+                    if let BulkFailure::Request(_, actions) = failure {
+                        *requests_failed.lock().unwrap() += actions;
+                    }
+                })
+                .send()
+        };
+        assert!(result.is_err());
+        assert_eq!(1, *requests_failed.lock().unwrap());
+
+        let empty: Vec<Action<TestDocument>> = Vec::new();
+        let result = client.bulk_stream(empty).send().unwrap();
+        assert_eq!(0, result.sent);
+        assert!(result.chunks.is_empty());
+        assert_eq!(1, transport.requests().len());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_bulk_stream_async() {
+        let transport = MockTransport::new().with_response(Method::POST, "/_bulk", 200, OK_RESULT);
+        let client = make_mock_async_client(&transport);
+
+        let actions = (0..3).map(|_| Action::index(TestDocument::new()).with_index("test_idx"));
+        let result = block_on(client.bulk_stream(actions).with_bulk_actions(2).send()).unwrap();
+        assert_eq!(3, result.sent);
+        assert_eq!(2, transport.requests().len());
+    }
+}
@@ -18,7 +18,7 @@
 
 use std::fmt;
 
-use serde::ser::{Serialize, Serializer};
+use serde::{Serialize, Serializer};
 
 use crate::util::StrJoin;
 
@@ -88,7 +88,7 @@ impl<'a> fmt::Display for Options<'a> {
 /// operations builder interface.
 macro_rules! add_option {
     ($n:ident, $e:expr) => (
-        pub fn $n<T: Into<OptionVal>>(&'a mut self, val: T) -> &'a mut Self {
+        pub fn $n<T: Into<OptionVal>>(&mut self, val: T) -> &mut Self {
             self.options.push($e, val);
             self
         }
@@ -168,3 +168,23 @@ impl From<DefaultOperator> for OptionVal {
         )
     }
 }
+
+/// Values for the `conflicts` option of the by-query and reindex APIs
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conflicts {
+    Abort,
+    Proceed,
+}
+
+impl From<Conflicts> for OptionVal {
+    fn from(from: Conflicts) -> OptionVal {
+        OptionVal(
+            match from {
+                Conflicts::Abort => "abort",
+                Conflicts::Proceed => "proceed",
+            }
+            .to_owned(),
+        )
+    }
+}
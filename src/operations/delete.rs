@@ -18,16 +18,26 @@
 
 use reqwest::StatusCode;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{error::EsError, Client, EsResponse};
+use crate::{
+    error::{unexpected_status, EsError},
+    query::Query,
+    Client, EsResponse,
+};
 
-use super::common::{OptionVal, Options};
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{
+    common::{OptionVal, Options},
+    format_indexes_and_types, ByQueryResult,
+};
 
 #[derive(Debug)]
-pub struct DeleteOperation<'a, 'b> {
+pub struct DeleteOperation<'a, 'b, C = Client> {
     /// The HTTP client
-    client: &'a mut Client,
+    client: &'a C,
 
     /// The index
     index: &'b str,
@@ -42,13 +52,13 @@ pub struct DeleteOperation<'a, 'b> {
     options: Options<'b>,
 }
 
-impl<'a, 'b> DeleteOperation<'a, 'b> {
+impl<'a, 'b, C> DeleteOperation<'a, 'b, C> {
     pub fn new(
-        client: &'a mut Client,
+        client: &'a C,
         index: &'b str,
         doc_type: &'b str,
         id: &'b str,
-    ) -> DeleteOperation<'a, 'b> {
+    ) -> DeleteOperation<'a, 'b, C> {
         DeleteOperation {
             client,
             index,
@@ -66,36 +76,174 @@ impl<'a, 'b> DeleteOperation<'a, 'b> {
     add_option!(with_refresh, "refresh");
     add_option!(with_timeout, "timeout");
 
-    pub fn send(&'a mut self) -> Result<DeleteResult, EsError> {
-        let url = format!(
+    fn url(&self) -> String {
+        format!(
             "/{}/{}/{}{}",
             self.index, self.doc_type, self.id, self.options
-        );
-        let response = self.client.delete_op(&url)?;
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        )
+    }
+}
+
+impl<'a, 'b> DeleteOperation<'a, 'b> {
+    pub fn send(&'a mut self) -> Result<DeleteResult, EsError> {
+        let response = self.client.delete_op(&self.url())?;
+        read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> DeleteOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&mut self) -> Result<DeleteResult, EsError> {
+        let response = self.client.delete_op(&self.url()).await?;
+        read_result(response)
+    }
+}
+
+fn read_result<R: EsResponse>(response: R) -> Result<DeleteResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
     }
 }
 
 impl Client {
+    /// Delete by ID
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/docs-delete.html
+    pub fn delete<'a>(&'a self, index: &'a str, doc_type: &'a str, id: &'a str) -> DeleteOperation {
+        DeleteOperation::new(self, index, doc_type, id)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
     /// Delete by ID
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/docs-delete.html
     pub fn delete<'a>(
-        &'a mut self,
+        &'a self,
         index: &'a str,
         doc_type: &'a str,
         id: &'a str,
-    ) -> DeleteOperation {
+    ) -> DeleteOperation<'a, 'a, AsyncClient> {
         DeleteOperation::new(self, index, doc_type, id)
     }
 }
 
+#[derive(Debug, Serialize)]
+struct DeleteByQueryBody<'b> {
+    query: &'b Query,
+}
+
+/// Delete each document matching a query
+#[derive(Debug)]
+pub struct DeleteByQueryOperation<'a, 'b, C = Client> {
+    /// The HTTP client
+    client: &'a C,
+
+    /// The indexes
+    indexes: &'b [&'b str],
+
+    /// The types
+    doc_types: &'b [&'b str],
+
+    /// Optional options
+    options: Options<'b>,
+
+    /// The query
+    body: DeleteByQueryBody<'b>,
+}
+
+impl<'a, 'b, C> DeleteByQueryOperation<'a, 'b, C> {
+    pub fn new(client: &'a C, query: &'b Query) -> DeleteByQueryOperation<'a, 'b, C> {
+        DeleteByQueryOperation {
+            client,
+            indexes: &[],
+            doc_types: &[],
+            options: Options::default(),
+            body: DeleteByQueryBody { query },
+        }
+    }
+
+    pub fn with_indexes(&'b mut self, indexes: &'b [&'b str]) -> &'b mut Self {
+        self.indexes = indexes;
+        self
+    }
+
+    pub fn with_types(&'b mut self, doc_types: &'b [&'b str]) -> &'b mut Self {
+        self.doc_types = doc_types;
+        self
+    }
+
+    add_option!(with_conflicts, "conflicts");
+    add_option!(with_slices, "slices");
+    add_option!(with_requests_per_second, "requests_per_second");
+    add_option!(with_scroll_size, "scroll_size");
+    add_option!(with_size, "size");
+    add_option!(with_wait_for_completion, "wait_for_completion");
+    add_option!(with_routing, "routing");
+    add_option!(with_refresh, "refresh");
+    add_option!(with_timeout, "timeout");
+
+    fn url(&self) -> String {
+        format!(
+            "/{}/_delete_by_query{}",
+            format_indexes_and_types(self.indexes, self.doc_types),
+            self.options
+        )
+    }
+}
+
+impl<'a, 'b> DeleteByQueryOperation<'a, 'b> {
+    pub fn send(&self) -> Result<ByQueryResult, EsError> {
+        let response = self.client.post_body_op(&self.url(), &self.body)?;
+        read_by_query_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> DeleteByQueryOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&self) -> Result<ByQueryResult, EsError> {
+        let response = self.client.post_body_op(&self.url(), &self.body).await?;
+        read_by_query_result(response)
+    }
+}
+
+/// Shared with update-by-query and reindex.  With `conflicts=abort` a version
+/// conflict stops the operation with a 409, but the body is still the
+/// statistics of the documents changed so far, with the conflicts in
+/// `failures`.
+pub(crate) fn read_by_query_result<R: EsResponse>(response: R) -> Result<ByQueryResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK | StatusCode::CONFLICT => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+impl Client {
+    /// Delete each document matching the query.  Requires ElasticSearch 5 or
+    /// later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-delete-by-query.html
+    pub fn delete_by_query<'a, 'b>(&'a self, query: &'b Query) -> DeleteByQueryOperation<'a, 'b> {
+        DeleteByQueryOperation::new(self, query)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Delete each document matching the query.  Requires ElasticSearch 5 or
+    /// later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-delete-by-query.html
+    pub fn delete_by_query<'a, 'b>(
+        &'a self,
+        query: &'b Query,
+    ) -> DeleteByQueryOperation<'a, 'b, AsyncClient> {
+        DeleteByQueryOperation::new(self, query)
+    }
+}
+
 /// Result of a DELETE operation
 #[derive(Debug, Deserialize)]
 pub struct DeleteResult {
@@ -112,14 +260,24 @@ pub struct DeleteResult {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::tests::{clean_db, make_client, TestDocument};
+    use reqwest::Method;
+
+    use crate::{
+        operations::{common::Conflicts, ByQueryResult},
+        query::Query,
+        tests::{clean_db, make_client, make_mock_client, TestDocument},
+        transport::MockTransport,
+    };
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
 
     #[test]
     fn test_delete() {
         let index_name = "test_delete";
-        let mut client = make_client();
+        let client = make_client("test_delete");
 
-        clean_db(&mut client, index_name);
+        clean_db(&client, index_name);
         let id = {
             let doc = TestDocument::new().with_int_field(4);
             let result = client
@@ -134,4 +292,118 @@ pub mod tests {
         assert_eq!(id, delete_result.id);
         assert_eq!(true, delete_result.found);
     }
+
+    #[test]
+    fn test_delete_by_query() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/_delete_by_query?conflicts=proceed&slices=2",
+                200,
+                r#"{"took": 147, "timed_out": false, "total": 3, "deleted": 2,
+                    "batches": 1, "version_conflicts": 1, "noops": 0,
+                    "retries": {"bulk": 0, "search": 0}, "throttled_millis": 0,
+                    "requests_per_second": -1.0, "throttled_until_millis": 0,
+                    "failures": [{"index": "test_idx", "type": "test_type", "id": "3",
+                                  "status": 409,
+                                  "cause": {"type": "version_conflict_engine_exception",
+                                            "reason": "version conflict"}}]}"#,
+            )
+            .with_response(
+                Method::POST,
+                "/test_idx/_delete_by_query?wait_for_completion=false",
+                200,
+                r#"{"task": "oTUltX4IQMOUUVeiohTt8A:12345"}"#,
+            );
+        let client = make_mock_client(&transport);
+        let query = Query::build_term("int_field", 4).build();
+
+        let result = client
+            .delete_by_query(&query)
+            .with_indexes(&["test_idx"])
+            .with_conflicts(Conflicts::Proceed)
+            .with_slices(2)
+            .send()
+            .unwrap();
+        let stats = result.stats().unwrap();
+        assert_eq!(3, stats.total);
+        assert_eq!(2, stats.deleted);
+        assert_eq!(1, stats.version_conflicts);
+        assert_eq!(Some(409), stats.failures[0].status);
+        assert_eq!(
+            "version_conflict_engine_exception",
+            stats.failures[0].cause.as_ref().unwrap().error_type
+        );
+
+        let result = client
+            .delete_by_query(&query)
+            .with_indexes(&["test_idx"])
+            .with_wait_for_completion(false)
+            .send()
+            .unwrap();
+        assert!(matches!(result, ByQueryResult::Task { .. }));
+        assert_eq!(Some("oTUltX4IQMOUUVeiohTt8A:12345"), result.task());
+
+        let requests = transport.requests();
+        assert_eq!(
+            "{\"query\":{\"term\":{\"int_field\":{\"value\":4}}}}",
+            String::from_utf8_lossy(requests[0].body().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_delete_by_query_conflict() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_delete_by_query?conflicts=abort",
+            409,
+            r#"{"took": 8, "timed_out": false, "total": 3, "deleted": 1,
+                "batches": 1, "version_conflicts": 1, "noops": 0,
+                "retries": {"bulk": 0, "search": 0}, "throttled_millis": 0,
+                "requests_per_second": -1.0, "throttled_until_millis": 0,
+                "failures": [{"index": "test_idx", "type": "test_type", "id": "2",
+                              "status": 409,
+                              "cause": {"type": "version_conflict_engine_exception",
+                                        "reason": "version conflict"}}]}"#,
+        );
+        let client = make_mock_client(&transport);
+        let query = Query::build_term("int_field", 4).build();
+
+        let result = client
+            .delete_by_query(&query)
+            .with_indexes(&["test_idx"])
+            .with_conflicts(Conflicts::Abort)
+            .send()
+            .unwrap();
+        let stats = result.stats().unwrap();
+        assert_eq!(1, stats.deleted);
+        assert_eq!(1, stats.version_conflicts);
+        assert_eq!(Some(409), stats.failures[0].status);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_delete_by_query_async() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_delete_by_query",
+            200,
+            r#"{"took": 12, "timed_out": false, "total": 1, "deleted": 1,
+                "batches": 1, "version_conflicts": 0, "noops": 0,
+                "retries": {"bulk": 0, "search": 0}, "throttled_millis": 0,
+                "requests_per_second": -1.0, "throttled_until_millis": 0,
+                "failures": []}"#,
+        );
+        let client = make_mock_async_client(&transport);
+        let query = Query::build_term("int_field", 4).build();
+
+        let result = block_on(
+            client
+                .delete_by_query(&query)
+                .with_indexes(&["test_idx"])
+                .send(),
+        )
+        .unwrap();
+        assert_eq!(1, result.stats().unwrap().deleted);
+    }
 }
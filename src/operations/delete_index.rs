@@ -18,7 +18,13 @@
 
 use reqwest::StatusCode;
 
-use crate::{error::EsError, Client, EsResponse};
+use crate::{
+    error::{unexpected_status, EsError},
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
 
 use super::GenericResult;
 
@@ -29,30 +35,47 @@ impl Client {
     /// follow the pattern defined elsewhere.
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/2.x/indices-delete-index.html
-    pub fn delete_index<'a>(&'a mut self, index: &'a str) -> Result<GenericResult, EsError> {
+    pub fn delete_index<'a>(&'a self, index: &'a str) -> Result<GenericResult, EsError> {
         let url = format!("/{}/", index);
         let response = self.delete_op(&url)?;
+        read_result(response)
+    }
+}
 
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Delete given index
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/2.x/indices-delete-index.html
+    pub async fn delete_index<'a>(&'a self, index: &'a str) -> Result<GenericResult, EsError> {
+        let url = format!("/{}/", index);
+        let response = self.delete_op(&url).await?;
+        read_result(response)
+    }
+}
+
+fn read_result<R: EsResponse>(response: R) -> Result<GenericResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::tests::{clean_db, make_client, TestDocument};
+    use reqwest::Method;
+
+    use crate::{
+        tests::{clean_db, make_client, make_mock_client, TestDocument},
+        transport::MockTransport,
+    };
 
     #[test]
     fn test_delete_index() {
         let index_name = "test_delete_index";
-        let mut client = make_client();
+        let client = make_client("test_delete_index");
 
-        clean_db(&mut client, index_name);
+        clean_db(&client, index_name);
         {
             let result = client
                 .index(index_name, "test_type")
@@ -70,4 +93,24 @@ pub mod tests {
             assert!(result_wrapped.acknowledged);
         }
     }
+
+    #[test]
+    fn test_delete_missing_index() {
+        let transport = MockTransport::new().with_response(
+            Method::DELETE,
+            "/missing_idx/",
+            404,
+            r#"{"error": {"root_cause": [{"type": "index_not_found_exception",
+                "reason": "no such index", "index": "missing_idx"}],
+                "type": "index_not_found_exception", "reason": "no such index",
+                "index": "missing_idx"}, "status": 404}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let err = client.delete_index("missing_idx").unwrap_err();
+        assert!(err.is_not_found());
+        assert!(err.is_index_missing());
+        let server_error = err.server_error().unwrap();
+        assert_eq!(Some("missing_idx"), server_error.index.as_deref());
+    }
 }
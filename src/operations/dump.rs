@@ -0,0 +1,545 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Dumping the documents of an index to a file, and restoring them.
+//!
+//! The file is newline-delimited JSON, one document per line, with the ID,
+//! type, routing and parent of the document as well as its source, e.g.:
+//!
+//! ```text
+//! {"_id":"1","_type":"comment","_routing":"user1","_parent":"7","_source":{"text":"..."}}
+//! ```
+//!
+//! Files with names ending in `.gz` are compressed, this requires the `gzip`
+//! feature.
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{error::EsError, json::ShouldSkip, query::Query, units::Duration, Client};
+
+use super::{
+    bulk::Action,
+    bulk_processor::{BulkFailure, FailureHandler},
+    bulk_stream::BulkStreamResult,
+    search::{ScanResult, SearchHitsHitsResult},
+};
+
+/// A line of a dump file
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DumpDoc {
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_type")]
+    pub doc_type: String,
+    #[serde(
+        rename = "_routing",
+        default,
+        skip_serializing_if = "ShouldSkip::should_skip"
+    )]
+    pub routing: Option<String>,
+    #[serde(
+        rename = "_parent",
+        default,
+        skip_serializing_if = "ShouldSkip::should_skip"
+    )]
+    pub parent: Option<String>,
+    #[serde(rename = "_source")]
+    pub source: Value,
+}
+
+/// The value of a metadata field of a hit, which may be in the hit itself, or
+/// in its fields, depending on the version of ElasticSearch
+fn hit_metadata(value: Option<String>, fields: &Option<Value>, name: &str) -> Option<String> {
+    value.or_else(|| match *fields {
+        Some(ref fields) => fields
+            .get(name)
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned),
+        None => None,
+    })
+}
+
+impl DumpDoc {
+    fn from_hit(hit: SearchHitsHitsResult<Value>) -> Result<DumpDoc, EsError> {
+        let source = match hit.source {
+            Some(source) => *source,
+            None => {
+                return Err(EsError::EsError(format!(
+                    "Document {} has no source, it cannot be dumped",
+                    hit.id
+                )));
+            }
+        };
+        Ok(DumpDoc {
+            routing: hit_metadata(hit.routing, &hit.fields, "_routing"),
+            parent: hit_metadata(hit.parent, &hit.fields, "_parent"),
+            id: hit.id,
+            doc_type: hit.doc_type,
+            source,
+        })
+    }
+
+    /// The action to index this document
+    pub fn into_action(self) -> Action<Value> {
+        let mut action = Action::index(self.source)
+            .with_id(self.id)
+            .with_doc_type(self.doc_type);
+        if let Some(routing) = self.routing {
+            action = action.with_routing(routing);
+        }
+        if let Some(parent) = self.parent {
+            action = action.with_parent(parent);
+        }
+        action
+    }
+}
+
+fn has_gzip_extension(path: &Path) -> bool {
+    path.extension().and_then(OsStr::to_str) == Some("gz")
+}
+
+#[cfg(feature = "gzip")]
+fn is_gzip(path: &Path) -> Result<bool, EsError> {
+    Ok(has_gzip_extension(path))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn is_gzip(path: &Path) -> Result<bool, EsError> {
+    if has_gzip_extension(path) {
+        Err(EsError::EsError(format!(
+            "Cannot compress {}, the gzip feature is not enabled",
+            path.display()
+        )))
+    } else {
+        Ok(false)
+    }
+}
+
+/// Create the file and write to it with `write`, compressing it if the name
+/// ends in `.gz`.  The compressed stream is finished, and the file flushed,
+/// here rather than when dropped, so that any error is returned.
+fn write_file<F>(path: &Path, write: F) -> Result<u64, EsError>
+where
+    F: FnOnce(&mut dyn Write) -> Result<u64, EsError>,
+{
+    let gzip = is_gzip(path)?;
+    let mut file = BufWriter::new(File::create(path)?);
+    if gzip {
+        #[cfg(feature = "gzip")]
+        {
+            let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            let count = write(&mut encoder)?;
+            encoder.finish()?.flush()?;
+            return Ok(count);
+        }
+    }
+    let count = write(&mut file)?;
+    file.flush()?;
+    Ok(count)
+}
+
+fn open_file(path: &Path) -> Result<Box<dyn BufRead>, EsError> {
+    let file = BufReader::new(File::open(path)?);
+    if is_gzip(path)? {
+        #[cfg(feature = "gzip")]
+        return Ok(Box::new(BufReader::new(flate2::bufread::GzDecoder::new(
+            file,
+        ))));
+    }
+    Ok(Box::new(file))
+}
+
+/// Dumps the documents of an index, see `Client::dump`
+#[derive(Debug)]
+pub struct DumpOperation<'a, 'b> {
+    client: &'a Client,
+    index: &'b str,
+    doc_types: &'b [&'b str],
+    query: Option<&'b Query>,
+    size: u64,
+    scroll: Duration,
+}
+
+impl<'a, 'b> DumpOperation<'a, 'b> {
+    fn new(client: &'a Client, index: &'b str) -> Self {
+        DumpOperation {
+            client,
+            index,
+            doc_types: &[],
+            query: None,
+            size: 500,
+            scroll: Duration::minutes(1),
+        }
+    }
+
+    /// Only dump documents of these types
+    pub fn with_types(&mut self, doc_types: &'b [&'b str]) -> &mut Self {
+        self.doc_types = doc_types;
+        self
+    }
+
+    /// Only dump documents matching this query
+    pub fn with_query(&mut self, query: &'b Query) -> &mut Self {
+        self.query = Some(query);
+        self
+    }
+
+    /// The number of documents to fetch in each scroll, per shard, the
+    /// default is 500
+    pub fn with_size(&mut self, size: u64) -> &mut Self {
+        self.size = size;
+        self
+    }
+
+    /// How long to keep the scroll open between fetches, the default is one
+    /// minute
+    pub fn with_scroll(&mut self, scroll: Duration) -> &mut Self {
+        self.scroll = scroll;
+        self
+    }
+
+    /// Write each document as a line of the writer, returning the number of
+    /// documents written
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<u64, EsError> {
+        let indexes = [self.index];
+        let mut op = self.client.search_query();
+        op.with_indexes(&indexes)
+            .with_types(self.doc_types)
+            .with_size(self.size);
+        if let Some(query) = self.query {
+            op.with_query(query);
+        }
+        let mut scan: ScanResult<Value> = op.scan(&self.scroll)?;
+
+        // Depending on the version of ElasticSearch, the first page of hits
+        // may be returned when the scan is opened
+        let first_page = mem::take(&mut scan.hits.hits);
+        let hits = first_page
+            .into_iter()
+            .map(Ok)
+            .chain(scan.iter(self.client, self.scroll.clone()));
+
+        let mut count = 0;
+        for hit in hits {
+            let doc = DumpDoc::from_hit(hit?)?;
+            serde_json::to_writer(&mut writer, &doc)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    /// Write each document as a line of the file, which is compressed if the
+    /// name ends in `.gz`, returning the number of documents written
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<u64, EsError> {
+        write_file(path.as_ref(), |writer| self.write(writer))
+    }
+}
+
+/// The actions to index each line of a dump, the first error stops the
+/// iteration
+struct DumpReader<R> {
+    lines: Lines<R>,
+    line: u64,
+    error: Option<EsError>,
+}
+
+impl<R: BufRead> Iterator for DumpReader<R> {
+    type Item = Action<Value>;
+
+    fn next(&mut self) -> Option<Action<Value>> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => {
+                    self.error = Some(err.into());
+                    return None;
+                }
+            };
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<DumpDoc>(&line) {
+                Ok(doc) => return Some(doc.into_action()),
+                Err(err) => {
+                    self.error = Some(EsError::EsError(format!(
+                        "Cannot read line {} of dump: {}",
+                        self.line, err
+                    )));
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Restores documents from a dump, see `Client::restore`
+pub struct RestoreOperation<'a, 'b> {
+    client: &'a Client,
+    index: &'b str,
+    bulk_actions: usize,
+    refresh: bool,
+    on_failure: Option<FailureHandler>,
+}
+
+impl<'a, 'b> RestoreOperation<'a, 'b> {
+    fn new(client: &'a Client, index: &'b str) -> Self {
+        RestoreOperation {
+            client,
+            index,
+            bulk_actions: 1000,
+            refresh: false,
+            on_failure: None,
+        }
+    }
+
+    /// The number of documents in each bulk request, the default is 1000
+    pub fn with_bulk_actions(&mut self, bulk_actions: usize) -> &mut Self {
+        self.bulk_actions = bulk_actions;
+        self
+    }
+
+    /// Refresh the index after the last request
+    pub fn with_refresh(&mut self, refresh: bool) -> &mut Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Called for each document which could not be indexed, and each request
+    /// which fails entirely.  Otherwise failures are logged.
+    pub fn with_on_failure<F>(&mut self, on_failure: F) -> &mut Self
+    where
+        F: Fn(BulkFailure) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(on_failure));
+        self
+    }
+
+    /// Index the document of each line of the reader.
+    ///
+    /// If a line cannot be read, the error is returned, the documents of any
+    /// earlier lines may have been indexed.
+    pub fn read<R: BufRead>(&self, reader: R) -> Result<BulkStreamResult, EsError> {
+        let mut docs = DumpReader {
+            lines: reader.lines(),
+            line: 0,
+            error: None,
+        };
+        let mut op = self
+            .client
+            .bulk_stream(&mut docs)
+            .with_index(self.index)
+            .with_bulk_actions(self.bulk_actions);
+        if let Some(ref on_failure) = self.on_failure {
+            let on_failure = on_failure.clone();
+            op = op.with_on_failure(move |failure| on_failure(failure));
+        }
+        let result = op.send()?;
+        if let Some(err) = docs.error {
+            return Err(err);
+        }
+        if self.refresh {
+            self.client.refresh().with_indexes(&[self.index]).send()?;
+        }
+        Ok(result)
+    }
+
+    /// Index the document of each line of the file, which is decompressed if
+    /// the name ends in `.gz`
+    pub fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<BulkStreamResult, EsError> {
+        self.read(open_file(path.as_ref())?)
+    }
+}
+
+impl<'a, 'b> fmt::Debug for RestoreOperation<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RestoreOperation")
+            .field("index", &self.index)
+            .field("bulk_actions", &self.bulk_actions)
+            .field("refresh", &self.refresh)
+            .finish()
+    }
+}
+
+impl Client {
+    /// Dump the documents of an index, with their metadata, as
+    /// newline-delimited JSON.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-request-scroll.html
+    pub fn dump<'a, 'b>(&'a self, index: &'b str) -> DumpOperation<'a, 'b> {
+        DumpOperation::new(self, index)
+    }
+
+    /// Index the documents of a dump created by `dump`, into the given index.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
+    pub fn restore<'a, 'b>(&'a self, index: &'b str) -> RestoreOperation<'a, 'b> {
+        RestoreOperation::new(self, index)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::Value;
+
+    use crate::{tests::make_mock_client, transport::MockTransport};
+
+    const SCAN_RESULT: &str = r#"{"_scroll_id": "s1", "took": 1, "timed_out": false,
+        "_shards": {"total": 1, "successful": 1, "failed": 0},
+        "hits": {"total": 2, "hits": []}}"#;
+
+    /// Scans two documents from `test_idx`
+    fn dump_transport() -> MockTransport {
+        #[cfg(not(feature = "es5"))]
+        let path = "/test_idx/_search?search_type=scan&scroll=1m";
+        #[cfg(feature = "es5")]
+        let path = "/test_idx/_search?scroll=1m";
+
+        MockTransport::new()
+            .with_response(Method::POST, path, 200, SCAN_RESULT)
+            .with_response(
+                Method::POST,
+                "/_search/scroll",
+                200,
+                r#"{"_scroll_id": "s2", "took": 1, "timed_out": false,
+                    "_shards": {"total": 1, "successful": 1, "failed": 0},
+                    "hits": {"total": 2, "hits": [
+                        {"_index": "test_idx", "_type": "post", "_id": "1",
+                         "_source": {"title": "A"}},
+                        {"_index": "test_idx", "_type": "comment", "_id": "2",
+                         "_routing": "1", "fields": {"_parent": "1"},
+                         "_source": {"text": "B"}}
+                    ]}}"#,
+            )
+            .with_response(
+                Method::POST,
+                "/_search/scroll",
+                200,
+                r#"{"_scroll_id": "s2", "took": 1, "timed_out": false,
+                    "_shards": {"total": 1, "successful": 1, "failed": 0},
+                    "hits": {"total": 2, "hits": []}}"#,
+            )
+            .with_response(Method::DELETE, "/_search/scroll?scroll_id=s2", 200, "{}")
+    }
+
+    #[test]
+    fn test_dump() {
+        let transport = dump_transport();
+        let client = make_mock_client(&transport);
+
+        let mut out = Vec::new();
+        let count = client.dump("test_idx").write(&mut out).unwrap();
+
+        assert_eq!(2, count);
+        assert_eq!(
+            "{\"_id\":\"1\",\"_type\":\"post\",\"_source\":{\"title\":\"A\"}}\n\
+             {\"_id\":\"2\",\"_type\":\"comment\",\"_routing\":\"1\",\"_parent\":\"1\",\
+             \"_source\":{\"text\":\"B\"}}\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!(4, transport.requests().len());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_dump_gzip() {
+        let transport = dump_transport().with_response(
+            Method::POST,
+            "/other_idx/_bulk",
+            200,
+            r#"{"took": 1, "errors": false, "items": []}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let path =
+            std::env::temp_dir().join(format!("rs_es_dump_{}.ndjson.gz", std::process::id()));
+        let count = client.dump("test_idx").write_file(&path).unwrap();
+        let result = client.restore("other_idx").read_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(2, count);
+        assert_eq!(2, result.unwrap().sent);
+        let requests = transport.requests();
+        let body = String::from_utf8_lossy(requests[4].body().unwrap()).into_owned();
+        assert_eq!(4, body.lines().count());
+    }
+
+    #[test]
+    fn test_restore() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/other_idx/_bulk",
+            200,
+            r#"{"took": 1, "errors": false, "items": []}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let dump = "{\"_id\":\"1\",\"_type\":\"post\",\"_source\":{\"title\":\"A\"}}\n\
+                    \n\
+                    {\"_id\":\"2\",\"_type\":\"comment\",\"_routing\":\"1\",\"_parent\":\"1\",\
+                    \"_source\":{\"text\":\"B\"}}\n";
+        let result = client
+            .restore("other_idx")
+            .with_bulk_actions(1)
+            .read(dump.as_bytes())
+            .unwrap();
+        assert_eq!(2, result.sent);
+        assert_eq!(2, result.chunks.len());
+
+        let requests = transport.requests();
+        let body = String::from_utf8_lossy(requests[1].body().unwrap()).into_owned();
+        let lines: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!("2", lines[0]["index"]["_id"]);
+        assert_eq!("comment", lines[0]["index"]["_type"]);
+        assert_eq!("1", lines[0]["index"]["_routing"]);
+        assert_eq!("1", lines[0]["index"]["_parent"]);
+        assert_eq!("B", lines[1]["text"]);
+    }
+
+    #[test]
+    fn test_restore_invalid_line() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_bulk",
+            200,
+            r#"{"took": 1, "errors": false, "items": []}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let dump = "{\"_id\":\"1\",\"_type\":\"post\",\"_source\":{}}\nnot json\n";
+        let err = client
+            .restore("test_idx")
+            .read(dump.as_bytes())
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+}
@@ -0,0 +1,223 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Checks for the existence of documents, indexes, types and aliases.
+//!
+//! Each is a `HEAD` request, so no body is downloaded.
+
+use reqwest::StatusCode;
+
+use crate::{
+    error::{unexpected_status, EsError},
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::format_multi;
+
+fn document_url(index: &str, doc_type: &str, id: &str) -> String {
+    format!("/{}/{}/{}", index, doc_type, id)
+}
+
+fn index_url(indexes: &[&str]) -> String {
+    format!("/{}", format_multi(indexes))
+}
+
+#[cfg(not(feature = "es5"))]
+fn type_url(indexes: &[&str], doc_types: &[&str]) -> String {
+    format!("/{}/{}", format_multi(indexes), format_multi(doc_types))
+}
+
+#[cfg(feature = "es5")]
+fn type_url(indexes: &[&str], doc_types: &[&str]) -> String {
+    format!(
+        "/{}/_mapping/{}",
+        format_multi(indexes),
+        format_multi(doc_types)
+    )
+}
+
+fn alias_url(indexes: &[&str], aliases: &[&str]) -> String {
+    if indexes.is_empty() {
+        format!("/_alias/{}", format_multi(aliases))
+    } else {
+        format!(
+            "/{}/_alias/{}",
+            format_multi(indexes),
+            format_multi(aliases)
+        )
+    }
+}
+
+fn read_result<R: EsResponse>(response: R) -> Result<bool, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(true),
+        StatusCode::NOT_FOUND => Ok(false),
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+impl Client {
+    /// Whether a document exists
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-get.html
+    pub fn document_exists(&self, index: &str, doc_type: &str, id: &str) -> Result<bool, EsError> {
+        read_result(self.head_op(&document_url(index, doc_type, id))?)
+    }
+
+    /// Whether all of the indexes exist, an empty list means any index
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-exists.html
+    pub fn index_exists(&self, indexes: &[&str]) -> Result<bool, EsError> {
+        read_result(self.head_op(&index_url(indexes))?)
+    }
+
+    /// Whether all of the types exist in all of the indexes
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-types-exists.html
+    pub fn type_exists(&self, indexes: &[&str], doc_types: &[&str]) -> Result<bool, EsError> {
+        read_result(self.head_op(&type_url(indexes, doc_types))?)
+    }
+
+    /// Whether any of the aliases exist, for any of the indexes.  An empty
+    /// list of indexes means any index.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-aliases.html
+    pub fn alias_exists(&self, indexes: &[&str], aliases: &[&str]) -> Result<bool, EsError> {
+        read_result(self.head_op(&alias_url(indexes, aliases))?)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Whether a document exists
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-get.html
+    pub async fn document_exists(
+        &self,
+        index: &str,
+        doc_type: &str,
+        id: &str,
+    ) -> Result<bool, EsError> {
+        read_result(self.head_op(&document_url(index, doc_type, id)).await?)
+    }
+
+    /// Whether all of the indexes exist, an empty list means any index
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-exists.html
+    pub async fn index_exists(&self, indexes: &[&str]) -> Result<bool, EsError> {
+        read_result(self.head_op(&index_url(indexes)).await?)
+    }
+
+    /// Whether all of the types exist in all of the indexes
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-types-exists.html
+    pub async fn type_exists(&self, indexes: &[&str], doc_types: &[&str]) -> Result<bool, EsError> {
+        read_result(self.head_op(&type_url(indexes, doc_types)).await?)
+    }
+
+    /// Whether any of the aliases exist, for any of the indexes.  An empty
+    /// list of indexes means any index.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/indices-aliases.html
+    pub async fn alias_exists(&self, indexes: &[&str], aliases: &[&str]) -> Result<bool, EsError> {
+        read_result(self.head_op(&alias_url(indexes, aliases)).await?)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use crate::{tests::make_mock_client, transport::MockTransport};
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
+
+    #[test]
+    fn test_document_exists() {
+        let transport = MockTransport::new()
+            .with_response(Method::HEAD, "/test_idx/test_type/1", 200, "")
+            .with_response(Method::HEAD, "/test_idx/test_type/2", 404, "");
+        let client = make_mock_client(&transport);
+
+        assert!(client
+            .document_exists("test_idx", "test_type", "1")
+            .unwrap());
+        assert!(!client
+            .document_exists("test_idx", "test_type", "2")
+            .unwrap());
+        assert!(transport
+            .requests()
+            .iter()
+            .all(|request| request.body().is_none()));
+    }
+
+    #[test]
+    fn test_index_and_alias_exists() {
+        let transport = MockTransport::new()
+            .with_response(Method::HEAD, "/idx_a,idx_b", 200, "")
+            .with_response(Method::HEAD, "/missing_idx", 404, "")
+            .with_response(Method::HEAD, "/_alias/alias_a", 200, "")
+            .with_response(Method::HEAD, "/idx_a/_alias/alias_b", 404, "");
+        let client = make_mock_client(&transport);
+
+        assert!(client.index_exists(&["idx_a", "idx_b"]).unwrap());
+        assert!(!client.index_exists(&["missing_idx"]).unwrap());
+        assert!(client.alias_exists(&[], &["alias_a"]).unwrap());
+        assert!(!client.alias_exists(&["idx_a"], &["alias_b"]).unwrap());
+    }
+
+    #[test]
+    fn test_type_exists() {
+        #[cfg(not(feature = "es5"))]
+        let path = "/test_idx/test_type";
+        #[cfg(feature = "es5")]
+        let path = "/test_idx/_mapping/test_type";
+
+        let transport = MockTransport::new().with_response(Method::HEAD, path, 200, "");
+        let client = make_mock_client(&transport);
+
+        assert!(client.type_exists(&["test_idx"], &["test_type"]).unwrap());
+        assert!(client.type_exists(&["test_idx"], &["other_type"]).is_err());
+    }
+
+    #[test]
+    fn test_exists_error() {
+        let transport = MockTransport::new().with_response(Method::HEAD, "/test_idx", 500, "");
+        let client = make_mock_client(&transport);
+
+        let err = client.index_exists(&["test_idx"]).unwrap_err();
+        assert_eq!(
+            Some(reqwest::StatusCode::INTERNAL_SERVER_ERROR),
+            err.server_error().map(|err| err.status)
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_exists_async() {
+        let transport = MockTransport::new()
+            .with_response(Method::HEAD, "/test_idx/test_type/1", 200, "")
+            .with_response(Method::HEAD, "/missing_idx", 404, "");
+        let client = make_mock_async_client(&transport);
+
+        assert!(block_on(client.document_exists("test_idx", "test_type", "1")).unwrap());
+        assert!(!block_on(client.index_exists(&["missing_idx"])).unwrap());
+    }
+}
@@ -22,7 +22,14 @@ use serde::Deserialize;
 
 use super::common::{OptionVal, Options};
 
-use crate::{error::EsError, util::StrJoin, Client, EsResponse};
+use crate::{
+    error::{ErrorCause, EsError},
+    util::StrJoin,
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
 
 /// Values for the `preference` query parameter
 pub enum Preference {
@@ -44,9 +51,9 @@ impl From<Preference> for OptionVal {
 
 /// An ES GET operation, to get a document by ID
 #[derive(Debug)]
-pub struct GetOperation<'a, 'b> {
+pub struct GetOperation<'a, 'b, C = Client> {
     /// The HTTP connection
-    client: &'a mut Client,
+    client: &'a C,
 
     /// The index to load the document.
     index: &'b str,
@@ -61,8 +68,8 @@ pub struct GetOperation<'a, 'b> {
     options: Options<'b>,
 }
 
-impl<'a, 'b> GetOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client, index: &'b str, id: &'b str) -> Self {
+impl<'a, 'b, C> GetOperation<'a, 'b, C> {
+    pub fn new(client: &'a C, index: &'b str, id: &'b str) -> Self {
         GetOperation {
             client,
             index,
@@ -95,29 +102,55 @@ impl<'a, 'b> GetOperation<'a, 'b> {
     add_option!(with_version, "version");
     add_option!(with_version_type, "version_type");
 
-    pub fn send<T>(&'b mut self) -> Result<GetResult<T>, EsError>
-    where
-        T: DeserializeOwned,
-    {
-        let url = format!(
+    fn url(&self) -> String {
+        format!(
             "/{}/{}/{}{}",
             self.index,
             self.doc_type.expect("No doc_type specified"),
             self.id,
             self.options
-        );
+        )
+    }
+}
+
+impl<'a, 'b> GetOperation<'a, 'b> {
+    pub fn send<T>(&'b mut self) -> Result<GetResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
         // We're ignoring status_code as all valid codes should return a value,
         // so anything else is an error.
-        let response = self.client.get_op(&url)?;
+        let response = self.client.get_op(&self.url())?;
         Ok(response.read_response()?)
     }
 }
 
+#[cfg(feature = "async")]
+impl<'a, 'b> GetOperation<'a, 'b, AsyncClient> {
+    pub async fn send<T>(&mut self) -> Result<GetResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.client.get_op(&self.url()).await?;
+        response.read_response()
+    }
+}
+
 impl Client {
     /// Implementation of the ES GET API
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/docs-get.html
-    pub fn get<'a>(&'a mut self, index: &'a str, id: &'a str) -> GetOperation {
+    pub fn get<'a>(&'a self, index: &'a str, id: &'a str) -> GetOperation {
+        GetOperation::new(self, index, id)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Implementation of the ES GET API
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/docs-get.html
+    pub fn get<'a>(&'a self, index: &'a str, id: &'a str) -> GetOperation<'a, 'a, AsyncClient> {
         GetOperation::new(self, index, id)
     }
 }
@@ -133,20 +166,30 @@ pub struct GetResult<T> {
     pub id: String,
     #[serde(rename = "_version")]
     pub version: Option<u64>,
+    /// False if the document does not exist, or, in a multi-get, could not be
+    /// fetched
+    #[serde(default)]
     pub found: bool,
     #[serde(rename = "_source")]
     pub source: Option<T>,
+    /// In a multi-get, why the document could not be fetched
+    pub error: Option<ErrorCause>,
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::tests::{clean_db, make_client, TestDocument};
+    use reqwest::Method;
+
+    use crate::{
+        tests::{clean_db, make_client, make_mock_client, TestDocument},
+        transport::MockTransport,
+    };
 
     #[test]
     fn test_get() {
         let index_name = "test_get";
-        let mut client = make_client();
-        clean_db(&mut client, index_name);
+        let client = make_client("test_get");
+        clean_db(&client, index_name);
         {
             let doc = TestDocument::new().with_int_field(3).with_bool_field(false);
             client
@@ -168,4 +211,41 @@ pub mod tests {
             assert_eq!(source.bool_field, false);
         }
     }
+
+    #[test]
+    fn test_get_missing() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::GET,
+                "/test_idx/test_type/1",
+                404,
+                r#"{"_index": "test_idx", "_type": "test_type", "_id": "1", "found": false}"#,
+            )
+            .with_response(
+                Method::GET,
+                "/missing_idx/test_type/1",
+                404,
+                r#"{"error": {"root_cause": [{"type": "index_not_found_exception",
+                                              "reason": "no such index"}],
+                              "type": "index_not_found_exception",
+                              "reason": "no such index", "index": "missing_idx"},
+                    "status": 404}"#,
+            );
+        let client = make_mock_client(&transport);
+
+        let result = client
+            .get("test_idx", "1")
+            .with_doc_type("test_type")
+            .send::<TestDocument>()
+            .unwrap();
+        assert!(!result.found);
+
+        let err = client
+            .get("missing_idx", "1")
+            .with_doc_type("test_type")
+            .send::<TestDocument>()
+            .unwrap_err();
+        assert!(err.is_not_found());
+        assert!(err.is_index_missing());
+    }
 }
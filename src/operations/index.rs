@@ -16,15 +16,18 @@
 
 //! Implementation of ElasticSearch Index operation
 
-use serde::ser::Serialize;
-
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{error::EsError, Client, EsResponse};
 
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
 use super::common::{OptionVal, Options};
 
 /// Values for the op_type option
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OpType {
     Create,
 }
@@ -39,9 +42,9 @@ impl From<OpType> for OptionVal {
 
 /// An indexing operation
 #[derive(Debug)]
-pub struct IndexOperation<'a, 'b, E: Serialize + 'b> {
+pub struct IndexOperation<'a, 'b, E: Serialize + 'b, C = Client> {
     /// The HTTP client that this operation will use
-    client: &'a mut Client,
+    client: &'a C,
 
     /// The index into which the document will be added
     index: &'b str,
@@ -59,12 +62,8 @@ pub struct IndexOperation<'a, 'b, E: Serialize + 'b> {
     document: Option<&'b E>,
 }
 
-impl<'a, 'b, E: Serialize + 'b> IndexOperation<'a, 'b, E> {
-    pub fn new(
-        client: &'a mut Client,
-        index: &'b str,
-        doc_type: &'b str,
-    ) -> IndexOperation<'a, 'b, E> {
+impl<'a, 'b, E: Serialize + 'b, C> IndexOperation<'a, 'b, E, C> {
+    pub fn new(client: &'a C, index: &'b str, doc_type: &'b str) -> IndexOperation<'a, 'b, E, C> {
         IndexOperation {
             client,
             index,
@@ -94,7 +93,9 @@ impl<'a, 'b, E: Serialize + 'b> IndexOperation<'a, 'b, E> {
     add_option!(with_timestamp, "timestamp");
     add_option!(with_refresh, "refresh");
     add_option!(with_timeout, "timeout");
+}
 
+impl<'a, 'b, E: Serialize + 'b> IndexOperation<'a, 'b, E> {
     pub fn send(&'b mut self) -> Result<IndexResult, EsError> {
         // Ignoring status_code as everything should return an IndexResult or
         // already be an error
@@ -118,12 +119,35 @@ impl<'a, 'b, E: Serialize + 'b> IndexOperation<'a, 'b, E> {
     }
 }
 
+#[cfg(feature = "async")]
+impl<'a, 'b, E: Serialize + 'b> IndexOperation<'a, 'b, E, AsyncClient> {
+    pub async fn send(&mut self) -> Result<IndexResult, EsError> {
+        let response = match self.id {
+            Some(ref id) => {
+                let url = format!("/{}/{}/{}{}", self.index, self.doc_type, id, self.options);
+                match self.document {
+                    Some(ref doc) => self.client.put_body_op(&url, doc).await,
+                    None => self.client.put_op(&url).await,
+                }
+            }
+            None => {
+                let url = format!("/{}/{}{}", self.index, self.doc_type, self.options);
+                match self.document {
+                    Some(ref doc) => self.client.post_body_op(&url, doc).await,
+                    None => self.client.post_op(&url).await,
+                }
+            }
+        }?;
+        response.read_response()
+    }
+}
+
 impl Client {
     /// An index operation to index a document in the specified index.
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/docs-index_.html
     pub fn index<'a, 'b, E: Serialize>(
-        &'a mut self,
+        &'a self,
         index: &'b str,
         doc_type: &'b str,
     ) -> IndexOperation<'a, 'b, E> {
@@ -131,6 +155,20 @@ impl Client {
     }
 }
 
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// An index operation to index a document in the specified index.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/docs-index_.html
+    pub fn index<'a, 'b, E: Serialize>(
+        &'a self,
+        index: &'b str,
+        doc_type: &'b str,
+    ) -> IndexOperation<'a, 'b, E, AsyncClient> {
+        IndexOperation::new(self, index, doc_type)
+    }
+}
+
 /// The result of an index operation
 #[derive(Debug, Deserialize)]
 pub struct IndexResult {
@@ -156,8 +194,8 @@ pub mod tests {
     #[test]
     fn test_indexing() {
         let index_name = "test_indexing";
-        let mut client = make_client();
-        clean_db(&mut client, index_name);
+        let client = make_client("test_indexing");
+        clean_db(&client, index_name);
         {
             let result_wrapped = client
                 .index(index_name, "test_type")
@@ -29,7 +29,14 @@ use reqwest::StatusCode;
 use serde::Serialize;
 use serde_json::{Map, Value};
 
-use crate::{error::EsError, operations::GenericResult, Client, EsResponse};
+use crate::{
+    error::{unexpected_status, EsError},
+    operations::GenericResult,
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
 
 pub type DocType<'a> = HashMap<&'a str, HashMap<&'a str, &'a str>>;
 pub type Mapping<'a> = HashMap<&'a str, DocType<'a>>;
@@ -50,9 +57,9 @@ pub struct Analysis {
 
 /// An indexing operation
 #[derive(Debug)]
-pub struct MappingOperation<'a, 'b> {
+pub struct MappingOperation<'a, 'b, C = Client> {
     /// The HTTP client that this operation will use
-    client: &'a mut Client,
+    client: &'a C,
 
     /// The index that will be created and eventually mapped
     index: &'b str,
@@ -65,8 +72,8 @@ pub struct MappingOperation<'a, 'b> {
     settings: Option<&'b Settings>,
 }
 
-impl<'a, 'b> MappingOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client, index: &'b str) -> MappingOperation<'a, 'b> {
+impl<'a, 'b, C> MappingOperation<'a, 'b, C> {
+    pub fn new(client: &'a C, index: &'b str) -> MappingOperation<'a, 'b, C> {
         MappingOperation {
             client,
             index,
@@ -86,7 +93,9 @@ impl<'a, 'b> MappingOperation<'a, 'b> {
         self.settings = Some(settings);
         self
     }
+}
 
+impl<'a, 'b> MappingOperation<'a, 'b> {
     /// If settings have been provided, the index will be created with them. If the index already
     /// exists, an `Err(EsError)` will be returned.
     /// If mapping have been set too, the properties will be applied. The index will be unavailable
@@ -122,55 +131,107 @@ impl<'a, 'b> MappingOperation<'a, 'b> {
     }
 }
 
+#[cfg(feature = "async")]
+impl<'a, 'b> MappingOperation<'a, 'b, AsyncClient> {
+    /// See the synchronous version of `send`
+    pub async fn send(&mut self) -> Result<MappingResult, EsError> {
+        // Return earlier if there is nothing to do
+        if self.mapping.is_none() && self.settings.is_none() {
+            return Ok(MappingResult);
+        }
+
+        if let Some(settings) = self.settings {
+            let body = hashmap("settings", settings);
+            let url = self.index.to_owned();
+            let _ = self.client.put_body_op(&url, &body).await?;
+
+            let _ = self.client.wait_for_status("yellow", "5s").await;
+        }
+
+        if let Some(mapping) = self.mapping {
+            let _ = self.client.close_index(self.index).await;
+
+            for (entity, properties) in mapping.iter() {
+                let body = hashmap("properties", properties);
+                let url = format!("{}/_mapping/{}", self.index, entity);
+                let _ = self.client.put_body_op(&url, &body).await?;
+            }
+
+            let _ = self.client.open_index(self.index).await;
+        }
+
+        Ok(MappingResult)
+    }
+}
+
+fn read_generic_result<R: EsResponse>(response: R) -> Result<GenericResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+fn health_url(status: &str, timeout: &str) -> String {
+    format!(
+        "_cluster/health?wait_for_status={}&timeout={}",
+        status, timeout
+    )
+}
+
+fn read_health_result<R: EsResponse>(response: R) -> Result<(), EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(()),
+        _ => Err(unexpected_status(response)),
+    }
+}
+
 impl Client {
     /// Open the index, making it available.
-    pub fn open_index<'a>(&'a mut self, index: &'a str) -> Result<GenericResult, EsError> {
+    pub fn open_index<'a>(&'a self, index: &'a str) -> Result<GenericResult, EsError> {
         let url = format!("{}/_open", index);
         let response = self.post_op(&url)?;
-
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        read_generic_result(response)
     }
 
     /// Close the index, making it unavailable and modifiable.
-    pub fn close_index<'a>(&'a mut self, index: &'a str) -> Result<GenericResult, EsError> {
+    pub fn close_index<'a>(&'a self, index: &'a str) -> Result<GenericResult, EsError> {
         let url = format!("{}/_close", index);
         let response = self.post_op(&url)?;
-
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        read_generic_result(response)
     }
 
     /// TODO: Return proper health data from
     /// https://www.elastic.co/guide/en/elasticsearch/reference/current/cluster-health.html
-    pub fn wait_for_status<'a>(
-        &'a mut self,
+    pub fn wait_for_status<'a>(&'a self, status: &'a str, timeout: &'a str) -> Result<(), EsError> {
+        let response = self.get_op(&health_url(status, timeout))?;
+        read_health_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Open the index, making it available.
+    pub async fn open_index<'a>(&'a self, index: &'a str) -> Result<GenericResult, EsError> {
+        let url = format!("{}/_open", index);
+        let response = self.post_op(&url).await?;
+        read_generic_result(response)
+    }
+
+    /// Close the index, making it unavailable and modifiable.
+    pub async fn close_index<'a>(&'a self, index: &'a str) -> Result<GenericResult, EsError> {
+        let url = format!("{}/_close", index);
+        let response = self.post_op(&url).await?;
+        read_generic_result(response)
+    }
+
+    /// Wait for the cluster to reach the given status
+    pub async fn wait_for_status<'a>(
+        &'a self,
         status: &'a str,
         timeout: &'a str,
     ) -> Result<(), EsError> {
-        let url = format!(
-            "_cluster/health?wait_for_status={}&timeout={}",
-            status, timeout
-        );
-        let response = self.get_op(&url)?;
-
-        match response.status_code() {
-            StatusCode::OK => Ok(()),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        let response = self.get_op(&health_url(status, timeout)).await?;
+        read_health_result(response)
     }
 }
 
@@ -190,7 +251,7 @@ pub mod tests {
     #[test]
     fn test_mapping() {
         let index_name = "tests_test_mapping";
-        let mut client = crate::tests::make_client();
+        let client = crate::tests::make_client("test_mapping");
 
         // TODO - this fails in many cases (specifically on TravisCI), but we ignore the
         // failures anyway
@@ -251,7 +312,7 @@ pub mod tests {
         };
 
         // TODO add appropriate functions to the `Client` struct
-        let result = MappingOperation::new(&mut client, index_name)
+        let result = MappingOperation::new(&client, index_name)
             .with_mapping(&mapping)
             .with_settings(&settings)
             .send();
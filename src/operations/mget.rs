@@ -0,0 +1,422 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of the Multi Get API
+
+use reqwest::StatusCode;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    error::{unexpected_status, EsError},
+    json::ShouldSkip,
+    retry::{Retry, RetryPolicy},
+    util::StrJoin,
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{
+    common::{OptionVal, Options},
+    get::GetResult,
+    search::Source,
+};
+
+/// A document to get, with options specific to that document
+#[derive(Debug, Serialize)]
+pub struct MultiGetDoc<'a> {
+    #[serde(rename = "_index", skip_serializing_if = "ShouldSkip::should_skip")]
+    index: Option<&'a str>,
+    #[serde(rename = "_type", skip_serializing_if = "ShouldSkip::should_skip")]
+    doc_type: Option<&'a str>,
+    #[serde(rename = "_id")]
+    id: &'a str,
+    #[serde(rename = "_source", skip_serializing_if = "ShouldSkip::should_skip")]
+    source: Option<Source<'a>>,
+    #[serde(rename = "_routing", skip_serializing_if = "ShouldSkip::should_skip")]
+    routing: Option<&'a str>,
+    #[cfg_attr(not(feature = "es5"), serde(rename = "fields"))]
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    stored_fields: Option<&'a [&'a str]>,
+}
+
+impl<'a> MultiGetDoc<'a> {
+    /// A document with the given ID, in the index and type of the operation
+    /// unless specified
+    pub fn new(id: &'a str) -> MultiGetDoc<'a> {
+        MultiGetDoc {
+            index: None,
+            doc_type: None,
+            id,
+            source: None,
+            routing: None,
+            stored_fields: None,
+        }
+    }
+
+    add_field!(with_index, index, &'a str);
+    add_field!(with_doc_type, doc_type, &'a str);
+    add_field!(with_source, source, Source<'a>);
+    add_field!(with_routing, routing, &'a str);
+    add_field!(with_stored_fields, stored_fields, &'a [&'a str]);
+}
+
+/// An `(index, type, id)` triple
+impl<'a> From<(&'a str, &'a str, &'a str)> for MultiGetDoc<'a> {
+    fn from((index, doc_type, id): (&'a str, &'a str, &'a str)) -> MultiGetDoc<'a> {
+        MultiGetDoc::new(id)
+            .with_index(index)
+            .with_doc_type(doc_type)
+    }
+}
+
+/// Either individual documents, or IDs in the index of the operation
+#[derive(Debug, Serialize)]
+enum MultiGetBody<'b> {
+    #[serde(rename = "docs")]
+    Docs(&'b [MultiGetDoc<'b>]),
+    #[serde(rename = "ids")]
+    Ids(&'b [&'b str]),
+}
+
+/// A multi-get operation, to get many documents in one request
+#[derive(Debug)]
+pub struct MultiGetOperation<'a, 'b, C = Client> {
+    /// The HTTP client
+    client: &'a C,
+
+    /// The default index
+    index: Option<&'b str>,
+
+    /// The default type
+    doc_type: Option<&'b str>,
+
+    /// The documents to get
+    body: MultiGetBody<'b>,
+
+    /// Optional options
+    options: Options<'b>,
+
+    /// Override the client's retry policy
+    retry_policy: Option<&'b RetryPolicy>,
+}
+
+impl<'a, 'b, C> MultiGetOperation<'a, 'b, C> {
+    fn new(client: &'a C, body: MultiGetBody<'b>) -> Self {
+        MultiGetOperation {
+            client,
+            index: None,
+            doc_type: None,
+            body,
+            options: Options::default(),
+            retry_policy: None,
+        }
+    }
+
+    /// The index of any documents which do not specify one
+    pub fn with_index(&'b mut self, index: &'b str) -> &'b mut Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// The type of any documents which do not specify one
+    pub fn with_doc_type(&'b mut self, doc_type: &'b str) -> &'b mut Self {
+        self.doc_type = Some(doc_type);
+        self
+    }
+
+    /// The stored fields to return for each document, unless specified by the
+    /// document
+    pub fn with_stored_fields(&'b mut self, fields: &[&'b str]) -> &'b mut Self {
+        #[cfg(not(feature = "es5"))]
+        self.options.push("fields", fields.iter().join(","));
+        #[cfg(feature = "es5")]
+        self.options.push("stored_fields", fields.iter().join(","));
+        self
+    }
+
+    add_option!(with_realtime, "realtime");
+    add_option!(with_source, "_source");
+    add_option!(with_routing, "routing");
+    add_option!(with_preference, "preference");
+    add_option!(with_refresh, "refresh");
+
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&'b mut self, retry_policy: &'b RetryPolicy) -> &'b mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// The default type is part of the path, so can only be given together
+    /// with a default index
+    fn url(&self) -> Result<String, EsError> {
+        let mut url = String::from("/");
+        match (self.index, self.doc_type) {
+            (Some(index), doc_type) => {
+                url.push_str(index);
+                url.push('/');
+                if let Some(doc_type) = doc_type {
+                    url.push_str(doc_type);
+                    url.push('/');
+                }
+            }
+            (None, Some(_)) => {
+                return Err(EsError::EsError(
+                    "A default doc_type requires a default index".to_owned(),
+                ));
+            }
+            (None, None) => (),
+        }
+        url.push_str("_mget");
+        url.push_str(&self.options.to_string());
+        Ok(url)
+    }
+}
+
+impl<'a, 'b> MultiGetOperation<'a, 'b> {
+    /// Get the documents, in the order they were requested.  Documents which
+    /// do not exist are `found: false`, documents which could not be fetched
+    /// also have an `error`.
+    pub fn send<T>(&self) -> Result<Vec<GetResult<T>>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.client.post_body_op_with_retry(
+            &self.url()?,
+            &self.body,
+            Retry::from(self.retry_policy),
+        )?;
+        read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> MultiGetOperation<'a, 'b, AsyncClient> {
+    /// Get the documents, in the order they were requested.  Documents which
+    /// do not exist are `found: false`, documents which could not be fetched
+    /// also have an `error`.
+    pub async fn send<T>(&self) -> Result<Vec<GetResult<T>>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self
+            .client
+            .post_body_op_with_retry(&self.url()?, &self.body, Retry::from(self.retry_policy))
+            .await?;
+        read_result(response)
+    }
+}
+
+fn read_result<R, T>(response: R) -> Result<Vec<GetResult<T>>, EsError>
+where
+    R: EsResponse,
+    T: DeserializeOwned,
+{
+    match response.status_code() {
+        StatusCode::OK => {
+            let result: MultiGetResult<T> = response.read_response()?;
+            Ok(result.docs)
+        }
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+impl Client {
+    /// Get many documents, each specified by a `MultiGetDoc`, or an
+    /// `(index, type, id)` triple converted into one.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-multi-get.html
+    pub fn mget<'a, 'b>(&'a self, docs: &'b [MultiGetDoc<'b>]) -> MultiGetOperation<'a, 'b> {
+        MultiGetOperation::new(self, MultiGetBody::Docs(docs))
+    }
+
+    /// Get many documents by ID from a single index.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-multi-get.html
+    pub fn mget_ids<'a, 'b>(
+        &'a self,
+        index: &'b str,
+        ids: &'b [&'b str],
+    ) -> MultiGetOperation<'a, 'b> {
+        let mut op = MultiGetOperation::new(self, MultiGetBody::Ids(ids));
+        op.index = Some(index);
+        op
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Get many documents, each specified by a `MultiGetDoc`, or an
+    /// `(index, type, id)` triple converted into one.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-multi-get.html
+    pub fn mget<'a, 'b>(
+        &'a self,
+        docs: &'b [MultiGetDoc<'b>],
+    ) -> MultiGetOperation<'a, 'b, AsyncClient> {
+        MultiGetOperation::new(self, MultiGetBody::Docs(docs))
+    }
+
+    /// Get many documents by ID from a single index.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-multi-get.html
+    pub fn mget_ids<'a, 'b>(
+        &'a self,
+        index: &'b str,
+        ids: &'b [&'b str],
+    ) -> MultiGetOperation<'a, 'b, AsyncClient> {
+        let mut op = MultiGetOperation::new(self, MultiGetBody::Ids(ids));
+        op.index = Some(index);
+        op
+    }
+}
+
+/// The result of a multi-get operation
+#[derive(Debug, Deserialize)]
+struct MultiGetResult<T> {
+    docs: Vec<GetResult<T>>,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::Value;
+
+    use crate::{
+        operations::search::Source,
+        tests::{make_mock_client, request_body, TestDocument},
+        transport::MockTransport,
+    };
+
+    use super::MultiGetDoc;
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
+
+    #[test]
+    fn test_mget() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_mget",
+            200,
+            r#"{"docs": [
+                {"_index": "idx_a", "_type": "type_a", "_id": "2", "found": false},
+                {"_index": "idx_b", "_type": "type_b", "_id": "1", "_version": 3,
+                 "found": true, "_source": {"str_field": "I am a test",
+                                            "int_field": 7, "bool_field": true}},
+                {"_index": "idx_c", "_type": "type_c", "_id": "3",
+                 "error": {"type": "index_not_found_exception", "reason": "no such index"}}
+            ]}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let include = ["int_field"];
+        let docs = vec![
+            ("idx_a", "type_a", "2").into(),
+            MultiGetDoc::new("1")
+                .with_index("idx_b")
+                .with_doc_type("type_b")
+                .with_source(Source::include(&include))
+                .with_routing("user1"),
+            ("idx_c", "type_c", "3").into(),
+        ];
+        let results = client.mget(&docs).send::<TestDocument>().unwrap();
+
+        assert_eq!(3, results.len());
+        assert_eq!("2", results[0].id);
+        assert!(!results[0].found);
+        assert!(results[0].source.is_none());
+        assert!(results[1].found);
+        assert_eq!(Some(3), results[1].version);
+        assert_eq!(7, results[1].source.as_ref().unwrap().int_field);
+        assert!(!results[2].found);
+        let error = results[2].error.as_ref().unwrap();
+        assert_eq!("index_not_found_exception", error.error_type);
+        assert!(results[0].error.is_none());
+
+        let requests = transport.requests();
+        let body = request_body(&requests[0]);
+        assert_eq!("idx_a", body["docs"][0]["_index"]);
+        assert_eq!("2", body["docs"][0]["_id"]);
+        assert_eq!("user1", body["docs"][1]["_routing"]);
+        assert_eq!("int_field", body["docs"][1]["_source"]["include"][0]);
+    }
+
+    #[test]
+    fn test_mget_ids() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/_mget?_source=false",
+            200,
+            r#"{"docs": [
+                {"_index": "test_idx", "_type": "test_type", "_id": "1", "_version": 1, "found": true},
+                {"_index": "test_idx", "_type": "test_type", "_id": "2", "found": false}
+            ]}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let results = client
+            .mget_ids("test_idx", &["1", "2"])
+            .with_doc_type("test_type")
+            .with_source(false)
+            .send::<Value>()
+            .unwrap();
+        let found: Vec<bool> = results.iter().map(|result| result.found).collect();
+        assert_eq!(vec![true, false], found);
+
+        let requests = transport.requests();
+        assert_eq!(
+            "{\"ids\":[\"1\",\"2\"]}",
+            String::from_utf8_lossy(requests[0].body().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_mget_doc_type_without_index() {
+        let transport = MockTransport::new();
+        let client = make_mock_client(&transport);
+
+        let docs = vec![MultiGetDoc::new("1")];
+        let result = client
+            .mget(&docs)
+            .with_doc_type("test_type")
+            .send::<Value>();
+        assert!(result.is_err());
+        assert!(transport.requests().is_empty());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_mget_async() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_mget",
+            200,
+            r#"{"docs": [{"_index": "test_idx", "_type": "test_type", "_id": "1",
+                          "found": true, "_source": {"str_field": "I am a test",
+                                                     "int_field": 1, "bool_field": true}}]}"#,
+        );
+        let client = make_mock_async_client(&transport);
+
+        let ids = ["1"];
+        let results = block_on(client.mget_ids("test_idx", &ids).send::<TestDocument>()).unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(1, results[0].source.as_ref().unwrap().int_field);
+    }
+}
@@ -24,7 +24,7 @@ use std::borrow::Cow;
 
 use serde::{Serialize, Deserialize};
 
-use crate::util::StrJoin;
+use crate::{error::ErrorCause, util::StrJoin};
 
 // Specific operations
 #[macro_use]
@@ -32,13 +32,22 @@ pub mod common;
 
 pub mod analyze;
 pub mod bulk;
+#[macro_use]
+pub mod bulk_processor;
+pub mod bulk_stream;
 pub mod delete;
 pub mod delete_index;
+pub mod dump;
+pub mod exists;
 pub mod get;
 pub mod index;
 pub mod mapping;
+pub mod mget;
 pub mod refresh;
+pub mod reindex;
+pub mod script;
 pub mod search;
+pub mod update;
 pub mod version;
 
 // Common utility functions
@@ -68,7 +77,7 @@ fn format_indexes_and_types<'a>(indexes: &[&'a str], types: &[&str]) -> Cow<'a,
 
 /// Shared struct for operations that include counts of success/failed shards.
 /// This is returned within various other result structs.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct ShardCountResult {
     pub total: u64,
     pub successful: u64,
@@ -79,3 +88,72 @@ pub struct ShardCountResult {
 pub struct GenericResult {
     pub acknowledged: bool,
 }
+
+/// The result of a delete-by-query, update-by-query or reindex operation
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ByQueryResult {
+    /// The operation has finished
+    Completed(ByQueryStats),
+
+    /// The operation is running in the background, as the task with this ID.
+    /// Only when `wait_for_completion` is false.
+    Task { task: String },
+}
+
+impl ByQueryResult {
+    /// The statistics of the operation, if it has finished
+    pub fn stats(&self) -> Option<&ByQueryStats> {
+        match *self {
+            ByQueryResult::Completed(ref stats) => Some(stats),
+            ByQueryResult::Task { .. } => None,
+        }
+    }
+
+    /// The ID of the background task, if the operation has not finished
+    pub fn task(&self) -> Option<&str> {
+        match *self {
+            ByQueryResult::Completed(_) => None,
+            ByQueryResult::Task { ref task } => Some(task),
+        }
+    }
+}
+
+/// The statistics of a finished by-query operation
+#[derive(Debug, Deserialize)]
+pub struct ByQueryStats {
+    pub took: u64,
+    pub timed_out: bool,
+    pub total: u64,
+    #[serde(default)]
+    pub deleted: u64,
+    #[serde(default)]
+    pub updated: u64,
+    /// Reindex only
+    #[serde(default)]
+    pub created: u64,
+    pub batches: u64,
+    pub version_conflicts: u64,
+    pub noops: u64,
+    #[serde(default)]
+    pub throttled_millis: u64,
+    #[serde(default)]
+    pub failures: Vec<ByQueryFailure>,
+}
+
+/// A document which could not be changed, or a shard which could not be
+/// searched
+#[derive(Debug, Deserialize)]
+pub struct ByQueryFailure {
+    pub index: Option<String>,
+    #[serde(rename = "type")]
+    pub doc_type: Option<String>,
+    pub id: Option<String>,
+    pub status: Option<u16>,
+    pub shard: Option<i64>,
+    pub node: Option<String>,
+    /// The cause of a document failure
+    pub cause: Option<ErrorCause>,
+    /// The cause of a shard failure
+    pub reason: Option<ErrorCause>,
+}
@@ -20,21 +20,27 @@ use reqwest::StatusCode;
 
 use serde::Deserialize;
 
-use crate::{error::EsError, Client, EsResponse};
+use crate::{
+    error::{unexpected_status, EsError},
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
 
 use super::{format_multi, ShardCountResult};
 
 #[derive(Debug)]
-pub struct RefreshOperation<'a, 'b> {
+pub struct RefreshOperation<'a, 'b, C = Client> {
     /// The HTTP client
-    client: &'a mut Client,
+    client: &'a C,
 
     /// The indexes being refreshed
     indexes: &'b [&'b str],
 }
 
-impl<'a, 'b> RefreshOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client) -> RefreshOperation {
+impl<'a, 'b, C> RefreshOperation<'a, 'b, C> {
+    pub fn new(client: &'a C) -> RefreshOperation<'a, 'b, C> {
         RefreshOperation {
             client,
             indexes: &[],
@@ -46,16 +52,30 @@ impl<'a, 'b> RefreshOperation<'a, 'b> {
         self
     }
 
+    fn url(&self) -> String {
+        format!("/{}/_refresh", format_multi(&self.indexes))
+    }
+}
+
+impl<'a, 'b> RefreshOperation<'a, 'b> {
     pub fn send(&mut self) -> Result<RefreshResult, EsError> {
-        let url = format!("/{}/_refresh", format_multi(&self.indexes));
-        let response = self.client.post_op(&url)?;
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        let response = self.client.post_op(&self.url())?;
+        read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> RefreshOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&mut self) -> Result<RefreshResult, EsError> {
+        let response = self.client.post_op(&self.url()).await?;
+        read_result(response)
+    }
+}
+
+fn read_result<R: EsResponse>(response: R) -> Result<RefreshResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
     }
 }
 
@@ -63,7 +83,17 @@ impl Client {
     /// Refresh
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/indices-refresh.html
-    pub fn refresh(&mut self) -> RefreshOperation {
+    pub fn refresh(&self) -> RefreshOperation {
+        RefreshOperation::new(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Refresh
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/indices-refresh.html
+    pub fn refresh(&self) -> RefreshOperation<'_, '_, AsyncClient> {
         RefreshOperation::new(self)
     }
 }
@@ -0,0 +1,407 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of the Reindex API
+
+use serde::Serialize;
+
+use crate::{error::EsError, json::ShouldSkip, query::Query, Client};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{
+    common::{Conflicts, OptionVal, Options, VersionType},
+    delete::read_by_query_result,
+    index::OpType,
+    update::Script,
+    ByQueryResult,
+};
+
+/// The result of a reindex operation, which is the same as that of the
+/// by-query operations.  `created` is the number of documents created in the
+/// destination index, `updated` the number which replaced an existing
+/// document.
+pub type ReindexResult = ByQueryResult;
+
+/// A remote cluster to reindex from.  The host has to be whitelisted with the
+/// `reindex.remote.whitelist` setting of the destination cluster.
+#[derive(Debug, Serialize)]
+pub struct Remote {
+    host: String,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    username: Option<String>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    password: Option<String>,
+}
+
+impl Remote {
+    /// The remote cluster, e.g. `http://otherhost:9200`
+    pub fn new<S: Into<String>>(host: S) -> Remote {
+        Remote {
+            host: host.into(),
+            username: None,
+            password: None,
+        }
+    }
+
+    add_field!(with_username, username, String);
+    add_field!(with_password, password, String);
+}
+
+#[derive(Debug, Serialize)]
+struct ReindexSource<'b> {
+    index: &'b [&'b str],
+    #[serde(rename = "type", skip_serializing_if = "<[&str]>::is_empty")]
+    doc_types: &'b [&'b str],
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    query: Option<&'b Query>,
+    /// The batch size
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    remote: Option<Remote>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReindexDest<'b> {
+    index: &'b str,
+    #[serde(rename = "type", skip_serializing_if = "ShouldSkip::should_skip")]
+    doc_type: Option<&'b str>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    op_type: Option<OpType>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    version_type: Option<VersionType>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    routing: Option<&'b str>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    pipeline: Option<&'b str>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReindexBody<'b> {
+    source: ReindexSource<'b>,
+    dest: ReindexDest<'b>,
+    /// The maximum number of documents to reindex
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    script: Option<Script>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    conflicts: Option<Conflicts>,
+}
+
+/// Copy documents from one or more indexes to another index
+#[derive(Debug)]
+pub struct ReindexOperation<'a, 'b, C = Client> {
+    /// The HTTP client
+    client: &'a C,
+
+    /// Optional options
+    options: Options<'b>,
+
+    /// The source, destination and what to copy
+    body: ReindexBody<'b>,
+}
+
+impl<'a, 'b, C> ReindexOperation<'a, 'b, C> {
+    pub fn new(
+        client: &'a C,
+        source_indexes: &'b [&'b str],
+        dest_index: &'b str,
+    ) -> ReindexOperation<'a, 'b, C> {
+        ReindexOperation {
+            client,
+            options: Options::default(),
+            body: ReindexBody {
+                source: ReindexSource {
+                    index: source_indexes,
+                    doc_types: &[],
+                    query: None,
+                    size: None,
+                    remote: None,
+                },
+                dest: ReindexDest {
+                    index: dest_index,
+                    doc_type: None,
+                    op_type: None,
+                    version_type: None,
+                    routing: None,
+                    pipeline: None,
+                },
+                size: None,
+                script: None,
+                conflicts: None,
+            },
+        }
+    }
+
+    /// Only copy documents of these types
+    pub fn with_source_types(&'b mut self, doc_types: &'b [&'b str]) -> &'b mut Self {
+        self.body.source.doc_types = doc_types;
+        self
+    }
+
+    /// Only copy documents matching the query
+    pub fn with_query(&'b mut self, query: &'b Query) -> &'b mut Self {
+        self.body.source.query = Some(query);
+        self
+    }
+
+    /// The number of documents read from the source at a time
+    pub fn with_batch_size(&'b mut self, batch_size: u64) -> &'b mut Self {
+        self.body.source.size = Some(batch_size);
+        self
+    }
+
+    /// Copy from an index in another cluster
+    pub fn with_remote(&'b mut self, remote: Remote) -> &'b mut Self {
+        self.body.source.remote = Some(remote);
+        self
+    }
+
+    /// The maximum number of documents to copy
+    pub fn with_size(&'b mut self, size: u64) -> &'b mut Self {
+        self.body.size = Some(size);
+        self
+    }
+
+    /// Change the type of copied documents
+    pub fn with_dest_type(&'b mut self, doc_type: &'b str) -> &'b mut Self {
+        self.body.dest.doc_type = Some(doc_type);
+        self
+    }
+
+    /// `OpType::Create` to only copy documents which are missing from the
+    /// destination
+    pub fn with_op_type(&'b mut self, op_type: OpType) -> &'b mut Self {
+        self.body.dest.op_type = Some(op_type);
+        self
+    }
+
+    /// `VersionType::External` to preserve the version of copied documents,
+    /// only replacing older documents in the destination
+    pub fn with_version_type(&'b mut self, version_type: VersionType) -> &'b mut Self {
+        self.body.dest.version_type = Some(version_type);
+        self
+    }
+
+    pub fn with_dest_routing(&'b mut self, routing: &'b str) -> &'b mut Self {
+        self.body.dest.routing = Some(routing);
+        self
+    }
+
+    pub fn with_pipeline(&'b mut self, pipeline: &'b str) -> &'b mut Self {
+        self.body.dest.pipeline = Some(pipeline);
+        self
+    }
+
+    /// A script to modify each document as it is copied
+    pub fn with_script(&'b mut self, script: Script) -> &'b mut Self {
+        self.body.script = Some(script);
+        self
+    }
+
+    /// `Conflicts::Proceed` to continue after a version conflict
+    pub fn with_conflicts(&'b mut self, conflicts: Conflicts) -> &'b mut Self {
+        self.body.conflicts = Some(conflicts);
+        self
+    }
+
+    add_option!(with_slices, "slices");
+    add_option!(with_requests_per_second, "requests_per_second");
+    add_option!(with_wait_for_completion, "wait_for_completion");
+    add_option!(with_refresh, "refresh");
+    add_option!(with_timeout, "timeout");
+
+    fn url(&self) -> String {
+        format!("/_reindex{}", self.options)
+    }
+}
+
+impl<'a, 'b> ReindexOperation<'a, 'b> {
+    pub fn send(&self) -> Result<ReindexResult, EsError> {
+        let response = self.client.post_body_op(&self.url(), &self.body)?;
+        read_by_query_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> ReindexOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&self) -> Result<ReindexResult, EsError> {
+        let response = self.client.post_body_op(&self.url(), &self.body).await?;
+        read_by_query_result(response)
+    }
+}
+
+impl Client {
+    /// Copy documents from the source indexes to the destination index.
+    /// Requires ElasticSearch 5 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-reindex.html
+    pub fn reindex<'a, 'b>(
+        &'a self,
+        source_indexes: &'b [&'b str],
+        dest_index: &'b str,
+    ) -> ReindexOperation<'a, 'b> {
+        ReindexOperation::new(self, source_indexes, dest_index)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Copy documents from the source indexes to the destination index.
+    /// Requires ElasticSearch 5 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-reindex.html
+    pub fn reindex<'a, 'b>(
+        &'a self,
+        source_indexes: &'b [&'b str],
+        dest_index: &'b str,
+    ) -> ReindexOperation<'a, 'b, AsyncClient> {
+        ReindexOperation::new(self, source_indexes, dest_index)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use crate::{
+        operations::{
+            common::{Conflicts, VersionType},
+            index::OpType,
+            update::Script,
+        },
+        query::Query,
+        tests::{make_mock_client, request_body},
+        transport::MockTransport,
+    };
+
+    use super::Remote;
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
+
+    #[test]
+    fn test_reindex() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_reindex?slices=2&refresh=true",
+            200,
+            r#"{"took": 639, "timed_out": false, "total": 5, "updated": 1, "created": 3,
+                "deleted": 0, "batches": 1, "version_conflicts": 1, "noops": 0,
+                "retries": {"bulk": 0, "search": 0}, "throttled_millis": 0,
+                "requests_per_second": -1.0, "throttled_until_millis": 0,
+                "failures": [{"index": "new_idx", "type": "test_type", "id": "4",
+                              "status": 409,
+                              "cause": {"type": "version_conflict_engine_exception",
+                                        "reason": "[test_type][4]: version conflict",
+                                        "index": "new_idx"}}]}"#,
+        );
+        let client = make_mock_client(&transport);
+        let query = Query::build_term("bool_field", true).build();
+
+        let result = client
+            .reindex(&["old_idx"], "new_idx")
+            .with_source_types(&["test_type"])
+            .with_query(&query)
+            .with_batch_size(500)
+            .with_size(1000)
+            .with_remote(
+                Remote::new("http://otherhost:9200")
+                    .with_username("user")
+                    .with_password("pass"),
+            )
+            .with_op_type(OpType::Create)
+            .with_version_type(VersionType::External)
+            .with_script(Script::new("ctx._source.remove('int_field')"))
+            .with_conflicts(Conflicts::Proceed)
+            .with_slices(2)
+            .with_refresh(true)
+            .send()
+            .unwrap();
+        let stats = result.stats().unwrap();
+        assert_eq!(5, stats.total);
+        assert_eq!(3, stats.created);
+        assert_eq!(1, stats.updated);
+        assert_eq!(Some("4".to_owned()), stats.failures[0].id);
+        assert_eq!(
+            Some("new_idx".to_owned()),
+            stats.failures[0].cause.as_ref().unwrap().index
+        );
+
+        let requests = transport.requests();
+        let body = request_body(&requests[0]);
+        assert_eq!("old_idx", body["source"]["index"][0]);
+        assert_eq!("test_type", body["source"]["type"][0]);
+        assert_eq!(500, body["source"]["size"]);
+        assert_eq!(true, body["source"]["query"]["term"]["bool_field"]["value"]);
+        assert_eq!("http://otherhost:9200", body["source"]["remote"]["host"]);
+        assert_eq!("user", body["source"]["remote"]["username"]);
+        assert_eq!("new_idx", body["dest"]["index"]);
+        assert_eq!("create", body["dest"]["op_type"]);
+        assert_eq!("external", body["dest"]["version_type"]);
+        assert_eq!(1000, body["size"]);
+        assert_eq!("proceed", body["conflicts"]);
+        assert!(body["script"]["inline"].is_string());
+    }
+
+    #[test]
+    fn test_reindex_task() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_reindex?wait_for_completion=false",
+            200,
+            r#"{"task": "r1A2WoRbTwKZ516z6NEs5A:36619"}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let result = client
+            .reindex(&["old_idx", "other_idx"], "new_idx")
+            .with_wait_for_completion(false)
+            .send()
+            .unwrap();
+        assert_eq!(Some("r1A2WoRbTwKZ516z6NEs5A:36619"), result.task());
+
+        let requests = transport.requests();
+        assert_eq!(
+            "{\"source\":{\"index\":[\"old_idx\",\"other_idx\"]},\"dest\":{\"index\":\"new_idx\"}}",
+            String::from_utf8_lossy(requests[0].body().unwrap())
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_reindex_async() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_reindex?wait_for_completion=false",
+            200,
+            r#"{"task": "oTUltX4IQMOUUVeiohTt8A:12345"}"#,
+        );
+        let client = make_mock_async_client(&transport);
+
+        let result = block_on(
+            client
+                .reindex(&["old_idx"], "new_idx")
+                .with_wait_for_completion(false)
+                .send(),
+        )
+        .unwrap();
+        assert_eq!(Some("oTUltX4IQMOUUVeiohTt8A:12345"), result.task());
+    }
+}
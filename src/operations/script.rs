@@ -0,0 +1,249 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Stored scripts, which are kept in the cluster state and used by their ID
+//! rather than being sent with each request.
+//!
+//! A stored script can be used by sorts (`Script::stored`), function score
+//! queries (`Function::build_stored_script_score`) and scripted metric
+//! aggregations (`ScriptedMetric::stored`).  Search templates are stored
+//! scripts in the `mustache` language, see `Client::put_search_template`.
+//!
+//! Requires ElasticSearch 5.6 or later.  The script is given as `source`, as
+//! ElasticSearch expects since 5.6, whereas the inline scripts of
+//! `ScriptScore` and `update::Script` are still given as `inline`, which
+//! ElasticSearch accepts until 7.0.
+//!
+//! See: https://www.elastic.co/guide/en/elasticsearch/reference/current/modules-scripting-using.html#prefer-params
+
+use reqwest::StatusCode;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{unexpected_status, EsError},
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::GenericResult;
+
+/// A script, and the language in which it is written
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StoredScript {
+    pub lang: String,
+    pub source: String,
+}
+
+impl StoredScript {
+    pub fn new<L, S>(lang: L, source: S) -> StoredScript
+    where
+        L: Into<String>,
+        S: Into<String>,
+    {
+        StoredScript {
+            lang: lang.into(),
+            source: source.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PutScriptBody<'a> {
+    script: &'a StoredScript,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetScriptResult {
+    found: bool,
+    script: Option<StoredScript>,
+}
+
+fn script_url(id: &str) -> String {
+    format!("/_scripts/{}", id)
+}
+
+fn read_acknowledged<R: EsResponse>(response: R) -> Result<GenericResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+fn read_get_result<R: EsResponse>(response: R) -> Result<Option<StoredScript>, EsError> {
+    match response.status_code() {
+        StatusCode::OK => {
+            let result: GetScriptResult = response.read_response()?;
+            Ok(if result.found { result.script } else { None })
+        }
+        StatusCode::NOT_FOUND => Ok(None),
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+impl Client {
+    /// Store a script with the given ID, replacing any existing script with
+    /// that ID.  Requires ElasticSearch 5.6 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/modules-scripting-using.html#prefer-params
+    pub fn put_script(&self, id: &str, script: &StoredScript) -> Result<GenericResult, EsError> {
+        let response = self.put_body_op(&script_url(id), &PutScriptBody { script })?;
+        read_acknowledged(response)
+    }
+
+    /// The stored script with the given ID, or `None` if there is no such
+    /// script
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/modules-scripting-using.html#prefer-params
+    pub fn get_script(&self, id: &str) -> Result<Option<StoredScript>, EsError> {
+        read_get_result(self.get_op(&script_url(id))?)
+    }
+
+    /// Delete the stored script with the given ID
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/modules-scripting-using.html#prefer-params
+    pub fn delete_script(&self, id: &str) -> Result<GenericResult, EsError> {
+        read_acknowledged(self.delete_op(&script_url(id))?)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Store a script with the given ID, replacing any existing script with
+    /// that ID.  Requires ElasticSearch 5.6 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/modules-scripting-using.html#prefer-params
+    pub async fn put_script(
+        &self,
+        id: &str,
+        script: &StoredScript,
+    ) -> Result<GenericResult, EsError> {
+        let response = self
+            .put_body_op(&script_url(id), &PutScriptBody { script })
+            .await?;
+        read_acknowledged(response)
+    }
+
+    /// The stored script with the given ID, or `None` if there is no such
+    /// script
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/modules-scripting-using.html#prefer-params
+    pub async fn get_script(&self, id: &str) -> Result<Option<StoredScript>, EsError> {
+        read_get_result(self.get_op(&script_url(id)).await?)
+    }
+
+    /// Delete the stored script with the given ID
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/modules-scripting-using.html#prefer-params
+    pub async fn delete_script(&self, id: &str) -> Result<GenericResult, EsError> {
+        read_acknowledged(self.delete_op(&script_url(id)).await?)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::json;
+
+    use crate::{
+        tests::{make_mock_client, request_body},
+        transport::MockTransport,
+    };
+
+    use super::StoredScript;
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
+
+    #[test]
+    fn test_put_script() {
+        let transport = MockTransport::new().with_response(
+            Method::PUT,
+            "/_scripts/add_n",
+            200,
+            r#"{"acknowledged": true}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let script = StoredScript::new("painless", "doc['int_field'].value + params.n");
+        let result = client.put_script("add_n", &script).unwrap();
+        assert!(result.acknowledged);
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(
+            json!({"script": {"lang": "painless",
+                              "source": "doc['int_field'].value + params.n"}}),
+            body
+        );
+    }
+
+    #[test]
+    fn test_get_script() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::GET,
+                "/_scripts/add_n",
+                200,
+                r#"{"_id": "add_n", "found": true,
+                    "script": {"lang": "painless", "source": "params.n"}}"#,
+            )
+            .with_response(
+                Method::GET,
+                "/_scripts/missing",
+                404,
+                r#"{"_id": "missing", "found": false}"#,
+            )
+            .with_response(
+                Method::DELETE,
+                "/_scripts/add_n",
+                200,
+                r#"{"acknowledged": true}"#,
+            );
+        let client = make_mock_client(&transport);
+
+        assert_eq!(
+            Some(StoredScript::new("painless", "params.n")),
+            client.get_script("add_n").unwrap()
+        );
+        assert_eq!(None, client.get_script("missing").unwrap());
+        assert!(client.delete_script("add_n").unwrap().acknowledged);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_script_async() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::PUT,
+                "/_scripts/add_n",
+                200,
+                r#"{"acknowledged": true}"#,
+            )
+            .with_response(Method::GET, "/_scripts/add_n", 404, r#"{"found": false}"#);
+        let client = make_mock_async_client(&transport);
+
+        let script = StoredScript::new("painless", "params.n");
+        assert!(
+            block_on(client.put_script("add_n", &script))
+                .unwrap()
+                .acknowledged
+        );
+        assert_eq!(None, block_on(client.get_script("add_n")).unwrap());
+    }
+}
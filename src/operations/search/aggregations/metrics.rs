@@ -184,12 +184,21 @@ impl<'a> GeoBounds<'a> {
     add_field!(with_wrap_longitude, wrap_longitude, bool);
 }
 
+/// The map script of a scripted metric aggregation, either inline or stored
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum MapScript<'a> {
+    Inline(&'a str),
+    Stored { id: &'a str },
+}
+
 /// Scripted method aggregation
 #[derive(Debug, Default, Serialize)]
 pub struct ScriptedMetric<'a> {
     #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     init_script: Option<&'a str>,
-    map_script: &'a str,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    map_script: Option<MapScript<'a>>,
     #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     combine_script: Option<&'a str>,
     #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
@@ -221,7 +230,15 @@ pub struct ScriptedMetric<'a> {
 impl<'a> ScriptedMetric<'a> {
     pub fn new(map_script: &'a str) -> Self {
         ScriptedMetric {
-            map_script,
+            map_script: Some(MapScript::Inline(map_script)),
+            ..Default::default()
+        }
+    }
+
+    /// Use the map script stored with the given ID, see `Client::put_script`
+    pub fn stored(map_script_id: &'a str) -> Self {
+        ScriptedMetric {
+            map_script: Some(MapScript::Stored { id: map_script_id }),
             ..Default::default()
         }
     }
@@ -450,7 +467,7 @@ pub mod tests {
     use serde_json;
 
     use super::super::Aggregations;
-    use super::Min;
+    use super::{Min, ScriptedMetric};
 
     #[test]
     fn test_min_aggregation() {
@@ -461,4 +478,19 @@ pub mod tests {
             serde_json::to_string(&aggs).unwrap()
         );
     }
+
+    #[test]
+    fn test_scripted_metric_aggregation() {
+        let inline = ScriptedMetric::new("state.n += 1").with_init_script("state.n = 0");
+        assert_eq!(
+            "{\"init_script\":\"state.n = 0\",\"map_script\":\"state.n += 1\"}",
+            serde_json::to_string(&inline).unwrap()
+        );
+
+        let stored = ScriptedMetric::stored("count_map");
+        assert_eq!(
+            "{\"map_script\":{\"id\":\"count_map\"}}",
+            serde_json::to_string(&stored).unwrap()
+        );
+    }
 }
@@ -21,32 +21,38 @@ use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    error::EsError,
+    error::{unexpected_status, EsError},
     json::ShouldSkip,
     operations::{
         common::{OptionVal, Options},
         format_indexes_and_types, ShardCountResult,
     },
     query::Query,
+    retry::{Retry, RetryPolicy},
     Client, EsResponse,
 };
 
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
 /// Representing a count operation
 #[derive(Debug)]
-pub struct CountURIOperation<'a, 'b> {
-    client: &'a mut Client,
+pub struct CountURIOperation<'a, 'b, C = Client> {
+    client: &'a C,
     indexes: &'b [&'b str],
     doc_types: &'b [&'b str],
     options: Options<'b>,
+    retry_policy: Option<&'b RetryPolicy>,
 }
 
-impl<'a, 'b> CountURIOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client) -> CountURIOperation<'a, 'b> {
+impl<'a, 'b, C> CountURIOperation<'a, 'b, C> {
+    pub fn new(client: &'a C) -> CountURIOperation<'a, 'b, C> {
         CountURIOperation {
             client,
             indexes: &[],
             doc_types: &[],
             options: Options::default(),
+            retry_policy: None,
         }
     }
 
@@ -72,21 +78,49 @@ impl<'a, 'b> CountURIOperation<'a, 'b> {
     add_option!(with_analyze_wildcard, "analyze_wildcard");
     add_option!(with_terminate_after, "terminate_after");
 
-    pub fn send(&'b mut self) -> Result<CountResult, EsError> {
-        let url = format!(
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&'b mut self, retry_policy: &'b RetryPolicy) -> &'b mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    fn url(&self) -> String {
+        format!(
             "/{}/_count{}",
             format_indexes_and_types(&self.indexes, &self.doc_types),
             self.options
-        );
+        )
+    }
+}
+
+impl<'a, 'b> CountURIOperation<'a, 'b> {
+    pub fn send(&'b mut self) -> Result<CountResult, EsError> {
+        let url = self.url();
         log::info!("Counting with: {}", url);
-        let response = self.client.get_op(&url)?;
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        let response = self
+            .client
+            .get_op_with_retry(&url, Retry::from(self.retry_policy))?;
+        read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> CountURIOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&mut self) -> Result<CountResult, EsError> {
+        let url = self.url();
+        log::info!("Counting with: {}", url);
+        let response = self
+            .client
+            .get_op_with_retry(&url, Retry::from(self.retry_policy))
+            .await?;
+        read_result(response)
+    }
+}
+
+fn read_result<R: EsResponse>(response: R) -> Result<CountResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
     }
 }
 
@@ -98,9 +132,9 @@ struct CountQueryOperationBody<'b> {
 }
 
 #[derive(Debug)]
-pub struct CountQueryOperation<'a, 'b> {
+pub struct CountQueryOperation<'a, 'b, C = Client> {
     /// The HTTP client
-    client: &'a mut Client,
+    client: &'a C,
 
     /// The indexes to which this query applies
     indexes: &'b [&'b str],
@@ -113,16 +147,20 @@ pub struct CountQueryOperation<'a, 'b> {
 
     /// The query body
     body: CountQueryOperationBody<'b>,
+
+    /// Overrides the client's retry policy
+    retry_policy: Option<&'b RetryPolicy>,
 }
 
-impl<'a, 'b> CountQueryOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client) -> Self {
+impl<'a, 'b, C> CountQueryOperation<'a, 'b, C> {
+    pub fn new(client: &'a C) -> Self {
         CountQueryOperation {
             client,
             indexes: &[],
             doc_types: &[],
             options: Options::new(),
             body: Default::default(),
+            retry_policy: None,
         }
     }
 
@@ -148,21 +186,42 @@ impl<'a, 'b> CountQueryOperation<'a, 'b> {
     add_option!(with_analyze_wildcard, "analyze_wildcard");
     add_option!(with_terminate_after, "terminate_after");
 
-    /// Performs the count with the specified query and options
-    pub fn send(&'b mut self) -> Result<CountResult, EsError> {
-        let url = format!(
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&'b mut self, retry_policy: &'b RetryPolicy) -> &'b mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    fn url(&self) -> String {
+        format!(
             "/{}/_count{}",
             format_indexes_and_types(&self.indexes, &self.doc_types),
             self.options
-        );
-        let response = self.client.post_body_op(&url, &self.body)?;
-        match response.status_code() {
-            StatusCode::OK => Ok(response.read_response()?),
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        )
+    }
+}
+
+impl<'a, 'b> CountQueryOperation<'a, 'b> {
+    /// Performs the count with the specified query and options
+    pub fn send(&'b mut self) -> Result<CountResult, EsError> {
+        let response = self.client.post_body_op_with_retry(
+            &self.url(),
+            &self.body,
+            Retry::from(self.retry_policy),
+        )?;
+        read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> CountQueryOperation<'a, 'b, AsyncClient> {
+    /// Performs the count with the specified query and options
+    pub async fn send(&mut self) -> Result<CountResult, EsError> {
+        let response = self
+            .client
+            .post_body_op_with_retry(&self.url(), &self.body, Retry::from(self.retry_policy))
+            .await?;
+        read_result(response)
     }
 }
 
@@ -170,14 +229,31 @@ impl Client {
     /// Count via the query parameter
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-uri-request.html
-    pub fn count_uri(&mut self) -> CountURIOperation {
+    pub fn count_uri(&self) -> CountURIOperation {
+        CountURIOperation::new(self)
+    }
+
+    /// Count via the query DSL
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-request-body.html
+    pub fn count_query(&self) -> CountQueryOperation {
+        CountQueryOperation::new(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Count via the query parameter
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-uri-request.html
+    pub fn count_uri(&self) -> CountURIOperation<'_, '_, AsyncClient> {
         CountURIOperation::new(self)
     }
 
     /// Count via the query DSL
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-request-body.html
-    pub fn count_query(&mut self) -> CountQueryOperation {
+    pub fn count_query(&self) -> CountQueryOperation<'_, '_, AsyncClient> {
         CountQueryOperation::new(self)
     }
 }
@@ -202,10 +278,10 @@ mod tests {
     #[test]
     fn test_count_uri() {
         let index_name = "test_count_uri";
-        let mut client = make_client();
+        let client = make_client("test_count_uri");
 
-        clean_db(&mut client, index_name);
-        setup_test_data(&mut client, index_name);
+        clean_db(&client, index_name);
+        setup_test_data(&client, index_name);
 
         let all_results: CountResult = client
             .count_uri()
@@ -234,10 +310,10 @@ mod tests {
     #[test]
     fn test_count_query() {
         let index_name = "test_count_query";
-        let mut client = make_client();
+        let client = make_client("test_count_query");
 
-        clean_db(&mut client, index_name);
-        setup_test_data(&mut client, index_name);
+        clean_db(&client, index_name);
+        setup_test_data(&client, index_name);
 
         let all_results: CountResult = client
             .count_query()
@@ -19,13 +19,24 @@
 pub mod aggregations;
 pub mod count;
 pub mod highlight;
+pub mod msearch;
+pub mod pit;
+pub mod rescore;
+pub mod search_after;
+pub mod sliced_scan;
+pub mod suggest;
+pub mod template;
 
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 
 use reqwest::StatusCode;
 
-use serde::{de::DeserializeOwned, ser::Serializer, Deserialize, Serialize};
+use serde::{
+    de::{DeserializeOwned, Deserializer},
+    ser::{SerializeMap, Serializer},
+    Deserialize, Serialize,
+};
 use serde_json::Value;
 
 use super::{
@@ -33,24 +44,32 @@ use super::{
     format_indexes_and_types, ShardCountResult,
 };
 use crate::{
-    error::EsError,
+    error::{unexpected_status, EsError},
     json::{FieldBased, NoOuter, ShouldSkip},
     query::Query,
+    retry::{Retry, RetryPolicy},
     units::{DistanceType, DistanceUnit, Duration, JsonVal, Location, OneOrMany},
     util::StrJoin,
     Client, EsResponse,
 };
 
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
 use self::aggregations::AggregationsResult;
 use self::highlight::HighlightResult;
+use self::pit::{PitRef, PointInTime};
+use self::rescore::Rescore;
+use self::suggest::{read_suggest, Suggest, SuggestResult};
 
 /// Representing a search-by-uri option
 #[derive(Debug)]
-pub struct SearchURIOperation<'a, 'b> {
-    client: &'a mut Client,
+pub struct SearchURIOperation<'a, 'b, C = Client> {
+    client: &'a C,
     indexes: &'b [&'b str],
     doc_types: &'b [&'b str],
     options: Options<'b>,
+    retry_policy: Option<&'b RetryPolicy>,
 }
 
 /// Options for the various search_type parameters
@@ -267,16 +286,21 @@ impl GeoDistance {
 // TODO - fix structure
 // TODO - there are other 'Script's defined elsewhere, perhaps de-duplicate them
 // if it makes sense.
-#[derive(Debug, Serialize)]
+#[derive(Debug)]
 pub struct Script {
-    script: String,
-    #[serde(rename = "type")]
+    script: ScriptSource,
     script_type: String,
     params: HashMap<String, JsonVal>,
-    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     order: Option<Order>,
 }
 
+/// The script of a `Script` sort, either inline or the ID of a stored script
+#[derive(Debug)]
+enum ScriptSource {
+    Inline(String),
+    Stored(String),
+}
+
 impl Script {
     pub fn new<S, ST>(script: S, script_type: ST) -> Script
     where
@@ -284,7 +308,21 @@ impl Script {
         ST: Into<String>,
     {
         Script {
-            script: script.into(),
+            script: ScriptSource::Inline(script.into()),
+            script_type: script_type.into(),
+            params: HashMap::new(),
+            order: None,
+        }
+    }
+
+    /// Sort by the script stored with the given ID, see `Client::put_script`
+    pub fn stored<S, ST>(id: S, script_type: ST) -> Script
+    where
+        S: Into<String>,
+        ST: Into<String>,
+    {
+        Script {
+            script: ScriptSource::Stored(id.into()),
             script_type: script_type.into(),
             params: HashMap::new(),
             order: None,
@@ -307,6 +345,43 @@ impl Script {
     }
 }
 
+#[derive(Serialize)]
+struct StoredScriptRef<'a> {
+    id: &'a str,
+    params: &'a HashMap<String, JsonVal>,
+}
+
+impl Serialize for Script {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        match self.script {
+            ScriptSource::Inline(ref script) => {
+                map.serialize_entry("script", script)?;
+                map.serialize_entry("type", &self.script_type)?;
+                map.serialize_entry("params", &self.params)?;
+            }
+            // The parameters of a stored script are given with its ID
+            ScriptSource::Stored(ref id) => {
+                map.serialize_entry(
+                    "script",
+                    &StoredScriptRef {
+                        id,
+                        params: &self.params,
+                    },
+                )?;
+                map.serialize_entry("type", &self.script_type)?;
+            }
+        }
+        if let Some(ref order) = self.order {
+            map.serialize_entry("order", order)?;
+        }
+        map.end()
+    }
+}
+
 #[derive(Debug)]
 pub enum SortBy {
     Field(SortField),
@@ -406,13 +481,14 @@ impl<'a> From<&'a Sort> for OptionVal {
     }
 }
 
-impl<'a, 'b> SearchURIOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client) -> SearchURIOperation<'a, 'b> {
+impl<'a, 'b, C> SearchURIOperation<'a, 'b, C> {
+    pub fn new(client: &'a C) -> SearchURIOperation<'a, 'b, C> {
         SearchURIOperation {
             client,
             indexes: &[],
             doc_types: &[],
             options: Options::default(),
+            retry_policy: None,
         }
     }
 
@@ -459,28 +535,92 @@ impl<'a, 'b> SearchURIOperation<'a, 'b> {
         self
     }
 
-    pub fn send<T>(&'b mut self) -> Result<SearchResult<T>, EsError>
-    where
-        T: DeserializeOwned,
-    {
-        let url = format!(
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&'b mut self, retry_policy: &'b RetryPolicy) -> &'b mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    fn url(&self) -> String {
+        format!(
             "/{}/_search{}",
             format_indexes_and_types(&self.indexes, &self.doc_types),
             self.options
-        );
-        log::info!("Searching with: {}", url);
-        let response = self.client.get_op(&url)?;
+        )
+    }
+
+    fn read_result<R, T>(response: R) -> Result<SearchResult<T>, EsError>
+    where
+        R: EsResponse,
+        T: DeserializeOwned,
+    {
         match response.status_code() {
             StatusCode::OK => {
                 let interim: SearchResultInterim<T> = response.read_response()?;
                 Ok(interim.finalize())
             }
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
+            _ => Err(unexpected_status(response)),
+        }
+    }
+}
+
+impl<'a, 'b> SearchURIOperation<'a, 'b> {
+    pub fn send<T>(&'b mut self) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let url = self.url();
+        log::info!("Searching with: {}", url);
+        let response = self
+            .client
+            .get_op_with_retry(&url, Retry::from(self.retry_policy))?;
+        Self::read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> SearchURIOperation<'a, 'b, AsyncClient> {
+    pub async fn send<T>(&mut self) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let url = self.url();
+        log::info!("Searching with: {}", url);
+        let response = self
+            .client
+            .get_op_with_retry(&url, Retry::from(self.retry_policy))
+            .await?;
+        Self::read_result(response)
+    }
+}
+
+/// One of a number of slices of a scroll, each of which can be scrolled
+/// independently.  Requires ElasticSearch 5.0 or later.
+///
+/// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-request-scroll.html#sliced-scroll
+#[derive(Clone, Debug, Serialize)]
+pub struct Slice {
+    id: u32,
+    max: u32,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    field: Option<String>,
+}
+
+impl Slice {
+    /// The slice with the given ID, of `max` slices
+    pub fn new(id: u32, max: u32) -> Slice {
+        Slice {
+            id,
+            max,
+            field: None,
         }
     }
+
+    /// Slice by the value of this field, rather than `_uid`
+    pub fn with_field<S: Into<String>>(mut self, field: S) -> Self {
+        self.field = Some(field.into());
+        self
+    }
 }
 
 /// Options for source filtering
@@ -571,6 +711,18 @@ struct SearchQueryOperationBody<'b> {
     #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     sort: Option<&'b Sort>,
 
+    /// The sort values of the hit after which to start
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    search_after: Option<&'b [Value]>,
+
+    /// The slice of a scroll
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    slice: Option<Slice>,
+
+    /// The point in time to search
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    pit: Option<PitRef>,
+
     /// Track scores
     #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     track_scores: Option<bool>,
@@ -590,15 +742,45 @@ struct SearchQueryOperationBody<'b> {
     #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     highlight: Option<&'b highlight::Highlight>,
 
+    /// Suggesters
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    suggest: Option<&'b Suggest>,
+
+    /// Rescoring, applied in order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rescore: Vec<&'b Rescore>,
+
     /// Version
     #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     version: Option<bool>,
 }
 
+/// The results of any aggregations, parsed according to the aggregations
+/// requested
+fn read_aggs(
+    req_aggs: Option<&aggregations::Aggregations>,
+    raw_aggs: &Option<Value>,
+) -> Result<Option<AggregationsResult>, EsError> {
+    match raw_aggs {
+        Some(ref raw_aggs) => {
+            let req_aggs = match req_aggs {
+                Some(aggs) => aggs,
+                None => {
+                    return Err(EsError::EsError(
+                        "No aggs despite being in results".to_owned(),
+                    ));
+                }
+            };
+            Ok(Some(AggregationsResult::from(req_aggs, raw_aggs)?))
+        }
+        None => Ok(None),
+    }
+}
+
 #[derive(Debug)]
-pub struct SearchQueryOperation<'a, 'b> {
+pub struct SearchQueryOperation<'a, 'b, C = Client> {
     /// The HTTP client
-    client: &'a mut Client,
+    client: &'a C,
 
     /// The indexes to which this query applies
     indexes: &'b [&'b str],
@@ -611,16 +793,24 @@ pub struct SearchQueryOperation<'a, 'b> {
 
     /// The query body
     body: SearchQueryOperationBody<'b>,
+
+    /// Overrides the client's retry policy
+    retry_policy: Option<&'b RetryPolicy>,
+
+    /// The field sorted on last when paging with `search_after`, to break ties
+    tie_breaker: Option<&'b str>,
 }
 
-impl<'a, 'b> SearchQueryOperation<'a, 'b> {
-    pub fn new(client: &'a mut Client) -> Self {
+impl<'a, 'b, C> SearchQueryOperation<'a, 'b, C> {
+    pub fn new(client: &'a C) -> Self {
         SearchQueryOperation {
             client,
             indexes: &[],
             doc_types: &[],
             options: Options::new(),
             body: Default::default(),
+            retry_policy: None,
+            tie_breaker: None,
         }
     }
 
@@ -687,6 +877,28 @@ impl<'a, 'b> SearchQueryOperation<'a, 'b> {
         self
     }
 
+    /// Start after the hit with these sort values, i.e. the `sort` of the last
+    /// hit of the previous page.  Requires ElasticSearch 5.0 or later.
+    pub fn with_search_after(&mut self, search_after: &'b [Value]) -> &mut Self {
+        self.body.search_after = Some(search_after);
+        self
+    }
+
+    /// Scroll through only this slice of the results, see `Slice`
+    pub fn with_slice(&mut self, slice: Slice) -> &mut Self {
+        self.body.slice = Some(slice);
+        self
+    }
+
+    /// Search this point in time, rather than the current state of the
+    /// indexes; the indexes of the point in time are searched, so any given
+    /// by `with_indexes` are ignored.  Each search extends the keep alive of
+    /// the point in time.  Requires ElasticSearch 7.10 or later.
+    pub fn with_pit(&mut self, pit: &PointInTime) -> &mut Self {
+        self.body.pit = Some(pit.pit_ref());
+        self
+    }
+
     /// Specify source filtering, by default full source will be returned in a hit
     ///
     /// To switch-off source document in each hit: `with_source(Source::Off)`.
@@ -710,52 +922,121 @@ impl<'a, 'b> SearchQueryOperation<'a, 'b> {
         self
     }
 
+    /// Specify suggesters, the suggestions are returned as `suggest`
+    pub fn with_suggest(&mut self, suggest: &'b Suggest) -> &mut Self {
+        self.body.suggest = Some(suggest);
+        self
+    }
+
+    /// Rescore the top hits, this can be called more than once to rescore the
+    /// results of the previous rescore.  ElasticSearch does not allow
+    /// rescoring with a sort other than by `_score` descending.
+    pub fn with_rescore(&mut self, rescore: &'b Rescore) -> &mut Self {
+        self.body.rescore.push(rescore);
+        self
+    }
+
     add_option!(with_routing, "routing");
+    add_option!(with_preference, "preference");
     add_option!(with_search_type, "search_type");
     add_option!(with_query_cache, "query_cache");
+    add_option!(with_request_cache, "request_cache");
     add_option!(with_ignore_unavailable, "ignore_unavailable");
     add_option!(with_allow_no_indices, "allow_no_indices");
     add_option!(with_expand_wildcards, "expand_wildcards");
     add_option!(with_explain, "explain");
 
-    /// Performs the search with the specified query and options
-    pub fn send<T>(&'b mut self) -> Result<SearchResult<T>, EsError>
-    where
-        T: DeserializeOwned,
-    {
-        let url = format!(
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&mut self, retry_policy: &'b RetryPolicy) -> &mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// The field `search_after_iter` sorts on last, to break ties, `_uid` by
+    /// default.  ElasticSearch 6 deprecates sorting on `_uid` and 7 removes
+    /// it, so use `_id` for those versions.
+    pub fn with_tie_breaker(&mut self, field: &'b str) -> &mut Self {
+        self.tie_breaker = Some(field);
+        self
+    }
+
+    fn url(&self) -> String {
+        // A point in time already determines the indexes
+        if self.body.pit.is_some() {
+            return format!("/_search{}", self.options);
+        }
+        format!(
             "/{}/_search{}",
             format_indexes_and_types(&self.indexes, &self.doc_types),
             self.options
-        );
-        let response = self.client.post_body_op(&url, &self.body)?;
+        )
+    }
+
+    /// The results of any aggregations requested
+    fn read_aggs(&self, raw_aggs: &Option<Value>) -> Result<Option<AggregationsResult>, EsError> {
+        read_aggs(self.body.aggs, raw_aggs)
+    }
+
+    fn read_result<R, T>(&self, response: R) -> Result<SearchResult<T>, EsError>
+    where
+        R: EsResponse,
+        T: DeserializeOwned,
+    {
+        match response.status_code() {
+            StatusCode::OK => self.read_interim(response.read_response()?),
+            _ => Err(unexpected_status(response)),
+        }
+    }
+
+    /// The result of this search, also used for each search of a
+    /// multi-search
+    fn read_interim<T>(&self, interim: SearchResultInterim<T>) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        if let (Some(pit), Some(pit_id)) = (&self.body.pit, &interim.pit_id) {
+            pit.update(pit_id);
+        }
+        let aggs = self.read_aggs(&interim.aggs)?;
+        let suggest = read_suggest(self.body.suggest, &interim.suggest)?;
+        let mut result = interim.finalize();
+        result.aggs = aggs;
+        result.suggest = suggest;
+        Ok(result)
+    }
+
+    #[cfg(not(feature = "es5"))]
+    fn read_scan_result<R, T>(&self, response: R) -> Result<ScanResult<T>, EsError>
+    where
+        R: EsResponse + Debug,
+        T: DeserializeOwned,
+    {
         match response.status_code() {
             StatusCode::OK => {
-                let interim: SearchResultInterim<T> = response.read_response()?;
-                let aggs = match &interim.aggs {
-                    Some(ref raw_aggs) => {
-                        let req_aggs = match &self.body.aggs {
-                            Some(ref aggs) => aggs,
-                            None => {
-                                return Err(EsError::EsError(
-                                    "No aggs despite being in results".to_owned(),
-                                ));
-                            }
-                        };
-                        Some(AggregationsResult::from(req_aggs, raw_aggs)?)
-                    }
-                    None => None,
-                };
+                let interim: ScanResultInterim<T> = response.read_response()?;
+                let aggs = self.read_aggs(&interim.aggs)?;
                 let mut result = interim.finalize();
                 result.aggs = aggs;
                 Ok(result)
             }
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
+            _ => Err(unexpected_status(response)),
         }
     }
+}
+
+impl<'a, 'b> SearchQueryOperation<'a, 'b> {
+    /// Performs the search with the specified query and options
+    pub fn send<T>(&'b mut self) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.client.post_body_op_with_retry(
+            &self.url(),
+            &self.body,
+            Retry::from(self.retry_policy),
+        )?;
+        self.read_result(response)
+    }
 
     #[cfg(feature = "es5")]
     pub fn scan<T>(&'b mut self, scroll: &'b Duration) -> Result<ScanResult<T>, EsError>
@@ -780,41 +1061,53 @@ impl<'a, 'b> SearchQueryOperation<'a, 'b> {
     {
         self.options.push("search_type", "scan");
         self.options.push("scroll", scroll);
-        let url = format!(
-            "/{}/_search{}",
-            format_indexes_and_types(&self.indexes, &self.doc_types),
-            self.options
-        );
-        let response = self.client.post_body_op(&url, &self.body)?;
-        match response.status_code() {
-            StatusCode::OK => {
-                let interim: ScanResultInterim<T> = response.read_response()?;
-                let aggs = match &interim.aggs {
-                    Some(ref raw_aggs) => {
-                        let req_aggs = match &self.body.aggs {
-                            Some(ref aggs) => aggs,
-                            None => {
-                                return Err(EsError::EsError(
-                                    "No aggs despite being in results".to_owned(),
-                                ));
-                            }
-                        };
-                        Some(AggregationsResult::from(req_aggs, raw_aggs)?)
-                    }
-                    None => None,
-                };
-                let mut result = interim.finalize();
-                result.aggs = aggs;
-                Ok(result)
-            }
-            StatusCode::NOT_FOUND => {
-                Err(EsError::EsServerError(format!("Not found: {:?}", response)))
-            }
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
-        }
+        let response = self.client.post_body_op_with_retry(
+            &self.url(),
+            &self.body,
+            Retry::from(self.retry_policy),
+        )?;
+        self.read_scan_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> SearchQueryOperation<'a, 'b, AsyncClient> {
+    /// Performs the search with the specified query and options
+    pub async fn send<T>(&mut self) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self
+            .client
+            .post_body_op_with_retry(&self.url(), &self.body, Retry::from(self.retry_policy))
+            .await?;
+        self.read_result(response)
+    }
+
+    #[cfg(feature = "es5")]
+    pub async fn scan<T>(&mut self, scroll: &'b Duration) -> Result<ScanResult<T>, EsError>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        self.options.push("scroll", scroll);
+
+        let serialized = serde_json::to_string(&self.send::<T>().await?)?;
+        Ok(serde_json::from_str(&serialized)?)
+    }
+
+    /// Begins a scan with the specified query and options
+    #[cfg(not(feature = "es5"))]
+    pub async fn scan<T>(&mut self, scroll: &'b Duration) -> Result<ScanResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        self.options.push("search_type", "scan");
+        self.options.push("scroll", scroll);
+        let response = self
+            .client
+            .post_body_op_with_retry(&self.url(), &self.body, Retry::from(self.retry_policy))
+            .await?;
+        self.read_scan_result(response)
     }
 }
 
@@ -822,14 +1115,31 @@ impl Client {
     /// Search via the query parameter
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-uri-request.html
-    pub fn search_uri(&mut self) -> SearchURIOperation {
+    pub fn search_uri(&self) -> SearchURIOperation {
         SearchURIOperation::new(self)
     }
 
     /// Search via the query DSL
     ///
     /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-request-body.html
-    pub fn search_query(&mut self) -> SearchQueryOperation {
+    pub fn search_query(&self) -> SearchQueryOperation {
+        SearchQueryOperation::new(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Search via the query parameter
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-uri-request.html
+    pub fn search_uri(&self) -> SearchURIOperation<'_, '_, AsyncClient> {
+        SearchURIOperation::new(self)
+    }
+
+    /// Search via the query DSL
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/1.x/search-request-body.html
+    pub fn search_query(&self) -> SearchQueryOperation<'_, '_, AsyncClient> {
         SearchQueryOperation::new(self)
     }
 }
@@ -854,16 +1164,42 @@ pub struct SearchHitsHitsResult<T> {
     pub timestamp: Option<f64>,
     #[serde(rename = "_routing")]
     pub routing: Option<String>,
+    #[serde(rename = "_parent")]
+    pub parent: Option<String>,
     pub fields: Option<Value>,
+    /// The values by which the hit was sorted, if the search was sorted
+    pub sort: Option<Vec<Value>>,
     pub highlight: Option<HighlightResult>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SearchHitsResult<T> {
+    /// The number of hits.  Since ElasticSearch 7.0 this is only counted up
+    /// to 10,000 by default, so may be a lower bound.
+    #[serde(deserialize_with = "deserialize_total")]
     pub total: u64,
     pub hits: Vec<SearchHitsHitsResult<T>>,
 }
 
+/// `hits.total` is a number before ElasticSearch 7.0, and afterwards an
+/// object, e.g. `{"value": 10, "relation": "eq"}`
+fn deserialize_total<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Total {
+        Count(u64),
+        Object { value: u64 },
+    }
+
+    Ok(match Total::deserialize(deserializer)? {
+        Total::Count(total) => total,
+        Total::Object { value } => value,
+    })
+}
+
 impl<T> SearchHitsResult<T>
 where
     T: DeserializeOwned,
@@ -904,9 +1240,15 @@ pub struct SearchResultInterim<T> {
     #[serde(rename = "aggregations")]
     pub aggs: Option<Value>,
 
+    /// Optional field populated if suggesters are specified
+    pub suggest: Option<Value>,
+
     /// Optional field populated during scanning and scrolling
     #[serde(rename = "_scroll_id")]
     pub scroll_id: Option<String>,
+
+    /// Optional field populated when searching a point in time
+    pub pit_id: Option<String>,
 }
 
 impl<T> SearchResultInterim<T>
@@ -920,7 +1262,9 @@ where
             shards: self.shards,
             hits: self.hits,
             aggs: None,
+            suggest: None,
             scroll_id: self.scroll_id,
+            pit_id: self.pit_id,
         }
     }
 }
@@ -932,7 +1276,9 @@ pub struct SearchResult<T> {
     pub shards: ShardCountResult,
     pub hits: SearchHitsResult<T>,
     pub aggs: Option<AggregationsResult>,
+    pub suggest: Option<SuggestResult>,
     pub scroll_id: Option<String>,
+    pub pit_id: Option<String>,
 }
 
 impl<T> SearchResult<T>
@@ -949,7 +1295,7 @@ where
 pub struct ScanIterator<'a, T: DeserializeOwned + Debug> {
     scan_result: ScanResult<T>,
     scroll: Duration,
-    client: &'a mut Client,
+    client: &'a Client,
     page: Vec<SearchHitsHitsResult<T>>,
 }
 
@@ -1067,7 +1413,7 @@ where
     T: DeserializeOwned + Debug,
 {
     /// Returns an iterator from which hits can be read
-    pub fn iter(self, client: &mut Client, scroll: Duration) -> ScanIterator<T> {
+    pub fn iter(self, client: &Client, scroll: Duration) -> ScanIterator<T> {
         ScanIterator {
             scan_result: self,
             scroll,
@@ -1079,7 +1425,7 @@ where
     /// Calls the `/_search/scroll` ES end-point for the next page
     pub fn scroll(
         &mut self,
-        client: &mut Client,
+        client: &Client,
         scroll: &Duration,
     ) -> Result<SearchResult<T>, EsError> {
         let url = "/_search/scroll";
@@ -1091,7 +1437,13 @@ where
             };
             client.post_body_op(url, &body)?
         };
+        self.read_scroll_result(response)
+    }
 
+    fn read_scroll_result<R: EsResponse>(
+        &mut self,
+        response: R,
+    ) -> Result<SearchResult<T>, EsError> {
         match response.status_code() {
             StatusCode::OK => {
                 let search_result: SearchResultInterim<T> = response.read_response()?;
@@ -1102,41 +1454,152 @@ where
                 log::debug!("Scrolled: {:?}", search_result);
                 Ok(search_result.finalize())
             }
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
+            _ => Err(unexpected_status(response)),
         }
     }
 
     /// Calls ES to close the server-side part of the scan/scroll operation
-    pub fn close(&self, client: &mut Client) -> Result<(), EsError> {
-        let url = format!("/_search/scroll?scroll_id={}", self.scroll_id);
-        let response = client.delete_op(&url)?;
-        match response.status_code() {
-            StatusCode::OK => Ok(()),        // closed
-            StatusCode::NOT_FOUND => Ok(()), // previously closed
-            status_code => Err(EsError::EsError(format!(
-                "Unexpected status: {}",
-                status_code
-            ))),
+    pub fn close(&self, client: &Client) -> Result<(), EsError> {
+        let response = client.delete_op(&self.close_url())?;
+        read_close_result(response)
+    }
+
+    fn close_url(&self) -> String {
+        format!("/_search/scroll?scroll_id={}", self.scroll_id)
+    }
+}
+
+fn read_close_result<R: EsResponse>(response: R) -> Result<(), EsError> {
+    match response.status_code() {
+        StatusCode::OK => Ok(()),        // closed
+        StatusCode::NOT_FOUND => Ok(()), // previously closed
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> ScanResult<T>
+where
+    T: DeserializeOwned + Debug,
+{
+    /// Returns a stream from which hits can be read, the asynchronous
+    /// equivalent of `iter`.
+    ///
+    /// The scroll is closed when the stream ends, including when a scroll
+    /// request fails.  Unlike `ScanIterator` the stream cannot close the scroll
+    /// when dropped, so either read it to the end or close the scroll with
+    /// `close_async`; otherwise the scroll is left open on the server until it
+    /// expires.
+    pub fn stream<'a>(
+        self,
+        client: &'a AsyncClient,
+        scroll: Duration,
+    ) -> impl futures::Stream<Item = Result<SearchHitsHitsResult<T>, EsError>> + 'a
+    where
+        T: 'a,
+    {
+        let state = ScanStreamState {
+            scan_result: self,
+            scroll,
+            client,
+            page: vec![].into_iter(),
+            finished: false,
+        };
+        futures::stream::unfold(state, |mut state| async move {
+            if let Some(hit) = state.page.next() {
+                return Some((Ok(hit), state));
+            }
+            if state.finished {
+                return None;
+            }
+            match state
+                .scan_result
+                .scroll_async(state.client, &state.scroll)
+                .await
+            {
+                Ok(scroll_page) => {
+                    state.page = scroll_page.hits.hits.into_iter();
+                    match state.page.next() {
+                        Some(hit) => Some((Ok(hit), state)),
+                        None => {
+                            state.close().await;
+                            None
+                        }
+                    }
+                }
+                Err(err) => {
+                    state.finished = true;
+                    state.close().await;
+                    Some((Err(err), state))
+                }
+            }
+        })
+    }
+
+    /// Calls the `/_search/scroll` ES end-point for the next page
+    pub async fn scroll_async(
+        &mut self,
+        client: &AsyncClient,
+        scroll: &Duration,
+    ) -> Result<SearchResult<T>, EsError> {
+        let url = "/_search/scroll";
+
+        let body = ScanBody {
+            scroll: scroll.to_string(),
+            scroll_id: &self.scroll_id,
+        };
+        let response = client.post_body_op(url, &body).await?;
+        self.read_scroll_result(response)
+    }
+
+    /// Calls ES to close the server-side part of the scan/scroll operation
+    pub async fn close_async(&self, client: &AsyncClient) -> Result<(), EsError> {
+        let response = client.delete_op(&self.close_url()).await?;
+        read_close_result(response)
+    }
+}
+
+/// The state of the stream returned by `ScanResult::stream`
+#[cfg(feature = "async")]
+struct ScanStreamState<'a, T> {
+    scan_result: ScanResult<T>,
+    scroll: Duration,
+    client: &'a AsyncClient,
+    page: std::vec::IntoIter<SearchHitsHitsResult<T>>,
+    finished: bool,
+}
+
+#[cfg(feature = "async")]
+impl<'a, T> ScanStreamState<'a, T>
+where
+    T: DeserializeOwned + Debug,
+{
+    async fn close(&self) {
+        if let Err(e) = self.scan_result.close_async(self.client).await {
+            log::error!("Cannot close scroll: {}", e);
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use reqwest::Method;
+
     use serde_json::Value;
 
     use crate::Client;
 
-    use crate::tests::{clean_db, make_client, setup_test_data, TestDocument};
+    use crate::tests::{
+        clean_db, make_client, make_mock_client, request_body, setup_test_data, TestDocument,
+    };
+    use crate::transport::MockTransport;
 
     use crate::operations::bulk::Action;
     use crate::query::Query;
     use crate::units::{Duration, JsonVal};
 
     use super::ScanResult;
+    use super::Script;
     use super::SearchHitsHitsResult;
     use super::SearchResult;
     use super::Sort;
@@ -1154,7 +1617,7 @@ mod tests {
             .with_int_field(idx)
     }
 
-    fn setup_scan_data(client: &mut Client, index_name: &str) {
+    fn setup_scan_data(client: &Client, index_name: &str) {
         let actions: Vec<Action<TestDocument>> = (0..1000)
             .map(|idx| Action::index(make_document(idx)))
             .collect();
@@ -1172,10 +1635,10 @@ mod tests {
     #[test]
     fn test_search_uri() {
         let index_name = "test_search_uri";
-        let mut client = make_client();
+        let client = make_client("test_search_uri");
 
-        clean_db(&mut client, index_name);
-        setup_test_data(&mut client, index_name);
+        clean_db(&client, index_name);
+        setup_test_data(&client, index_name);
 
         let all_results: SearchResult<TestDocument> = client
             .search_uri()
@@ -1219,9 +1682,9 @@ mod tests {
     #[test]
     fn test_search_body() {
         let index_name = "test_search_body";
-        let mut client = make_client();
-        clean_db(&mut client, index_name);
-        setup_test_data(&mut client, index_name);
+        let client = make_client("test_search_body");
+        clean_db(&client, index_name);
+        setup_test_data(&client, index_name);
 
         let all_results: SearchResult<TestDocument> = client
             .search_query()
@@ -1249,10 +1712,10 @@ mod tests {
 
     #[test]
     fn test_close() {
-        let mut client = make_client();
+        let client = make_client("test_close");
         let index_name = "tests_test_close";
-        crate::tests::clean_db(&mut client, index_name);
-        setup_scan_data(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
+        setup_scan_data(&client, index_name);
 
         let indexes = [index_name];
 
@@ -1264,18 +1727,18 @@ mod tests {
             .scan(&scroll)
             .unwrap();
 
-        scan_result.scroll(&mut client, &scroll).unwrap();
+        scan_result.scroll(&client, &scroll).unwrap();
 
-        scan_result.close(&mut client).unwrap();
+        scan_result.close(&client).unwrap();
     }
 
     #[test]
     #[cfg(feature = "es5")]
     fn test_scan_and_scroll() {
-        let mut client = make_client();
+        let client = make_client("test_scan_and_scroll_es5");
         let index_name = "tests_test_scan_and_scroll";
-        crate::tests::clean_db(&mut client, index_name);
-        setup_scan_data(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
+        setup_scan_data(&client, index_name);
 
         let indexes = [index_name];
 
@@ -1298,7 +1761,7 @@ mod tests {
 
             total += page_total;
 
-            let page = scan_result.scroll(&mut client, &scroll).unwrap();
+            let page = scan_result.scroll(&client, &scroll).unwrap();
             page_total = page.hits.hits.len();
 
             if page_total == 0 && total == 1000 {
@@ -1306,16 +1769,16 @@ mod tests {
             }
         }
 
-        scan_result.close(&mut client).unwrap();
+        scan_result.close(&client).unwrap();
     }
 
     #[test]
     #[cfg(not(feature = "es5"))]
     fn test_scan_and_scroll() {
-        let mut client = make_client();
+        let client = make_client("test_scan_and_scroll");
         let index_name = "tests_test_scan_and_scroll";
-        crate::tests::clean_db(&mut client, index_name);
-        setup_scan_data(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
+        setup_scan_data(&client, index_name);
 
         let indexes = [index_name];
 
@@ -1331,7 +1794,7 @@ mod tests {
         let mut total = 0;
 
         loop {
-            let page = scan_result.scroll(&mut client, &scroll).unwrap();
+            let page = scan_result.scroll(&client, &scroll).unwrap();
             let page_total = page.hits.hits.len();
             total += page_total;
             if page_total == 0 && total == 1000 {
@@ -1342,15 +1805,15 @@ mod tests {
             assert!(total <= 1000);
         }
 
-        scan_result.close(&mut client).unwrap();
+        scan_result.close(&client).unwrap();
     }
 
     #[test]
     fn test_with_version() {
-        let mut client = make_client();
+        let client = make_client("test_with_version");
         let index_name = "test_version";
-        crate::tests::clean_db(&mut client, index_name);
-        setup_test_data(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
+        setup_test_data(&client, index_name);
 
         let indexes = [index_name];
 
@@ -1420,10 +1883,10 @@ mod tests {
 
     #[test]
     fn test_scan_and_iterate() {
-        let mut client = make_client();
+        let client = make_client("test_scan_and_iterate");
         let index_name = "tests_test_scan_and_iterate";
-        crate::tests::clean_db(&mut client, index_name);
-        setup_scan_data(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
+        setup_scan_data(&client, index_name);
 
         let indexes = [index_name];
 
@@ -1438,7 +1901,7 @@ mod tests {
         assert_eq!(1000, scan_result.hits.total);
 
         let hits: Vec<SearchHitsHitsResult<TestDocument>> = scan_result
-            .iter(&mut client, scroll)
+            .iter(&client, scroll)
             .take(200)
             .map(Result::unwrap)
             .collect();
@@ -1448,9 +1911,9 @@ mod tests {
 
     #[test]
     fn test_source_filter() {
-        let mut client = make_client();
+        let client = make_client("test_source_filter");
         let index_name = "test_source_filter";
-        crate::tests::clean_db(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
 
         client
             .index(index_name, "test")
@@ -1478,9 +1941,9 @@ mod tests {
     #[test]
     #[cfg_attr(feature = "es5", ignore = "need to fix mappings to not be text fields")]
     fn test_highlight() {
-        let mut client = make_client();
+        let client = make_client("test_highlight");
         let index_name = "test_highlight";
-        crate::tests::clean_db(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
 
         client
             .bulk(&[
@@ -1529,9 +1992,9 @@ mod tests {
     #[test]
     #[cfg_attr(feature = "es5", ignore = "need to fix mappings to not be text fields")]
     fn test_bucket_aggs() {
-        let mut client = make_client();
+        let client = make_client("test_bucket_aggs");
         let index_name = "test_bucket_aggs";
-        crate::tests::clean_db(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
 
         client
             .bulk(&[
@@ -1605,11 +2068,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_search_mocked() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_search",
+            200,
+            r#"{"took": 2, "timed_out": false,
+                "_shards": {"total": 1, "successful": 1, "failed": 0},
+                "hits": {"total": 1, "max_score": 1.0, "hits": [
+                    {"_index": "test_idx", "_type": "test_type", "_id": "1", "_score": 1.0,
+                     "_source": {"str_field": "A", "int_field": 2, "bool_field": true}}
+                ]},
+                "aggregations": {"str": {
+                    "doc_count_error_upper_bound": 0, "sum_other_doc_count": 0,
+                    "buckets": [{"key": "A", "doc_count": 1, "int": {"value": 2.0}}]
+                }}}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let aggs = Aggregations::from((
+            "str",
+            (
+                Terms::field("str_field"),
+                Aggregations::from(("int", Min::field("int_field"))),
+            ),
+        ));
+        let result: SearchResult<TestDocument> = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .with_aggs(&aggs)
+            .send()
+            .unwrap();
+
+        assert_eq!(1, result.hits.total);
+        assert_eq!("1", result.hits.hits[0].id);
+        assert_eq!(2, result.hits.hits[0].source.as_ref().unwrap().int_field);
+
+        let buckets = &result
+            .aggs_ref()
+            .unwrap()
+            .get("str")
+            .unwrap()
+            .as_terms()
+            .unwrap()
+            .buckets;
+        assert_eq!(1, buckets.len());
+        assert_eq!(1, buckets[0].doc_count);
+        let min = &buckets[0]
+            .aggs_ref()
+            .unwrap()
+            .get("int")
+            .unwrap()
+            .as_min()
+            .unwrap()
+            .value;
+        match min {
+            JsonVal::Number(ref i) => assert_eq!(Some(2.0), i.as_f64()),
+            _ => panic!("Not a number"),
+        }
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!("str_field", body["aggregations"]["str"]["terms"]["field"]);
+    }
+
     #[test]
     fn test_aggs() {
-        let mut client = make_client();
+        let client = make_client("test_aggs");
         let index_name = "test_aggs";
-        crate::tests::clean_db(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
 
         client
             .bulk(&[
@@ -1651,9 +2178,9 @@ mod tests {
     #[test]
     #[cfg_attr(feature = "es5", ignore = "need to fix mappings to not be text fields")]
     fn test_sort() {
-        let mut client = make_client();
+        let client = make_client("test_sort");
         let index_name = "test_sort";
-        crate::tests::clean_db(&mut client, index_name);
+        crate::tests::clean_db(&client, index_name);
 
         client
             .bulk(&[
@@ -1738,4 +2265,75 @@ mod tests {
             assert_eq!(expected_result_str, result_str);
         }
     }
+
+    #[test]
+    fn test_script_sort() {
+        let inline = Script::new("doc['int_field'].value * n", "number")
+            .add_param("n", 2)
+            .build();
+        assert_eq!(
+            "{\"script\":\"doc['int_field'].value * n\",\"type\":\"number\",\"params\":{\"n\":2}}",
+            serde_json::to_string(&inline).unwrap()
+        );
+
+        let stored = Script::stored("times_n", "number")
+            .add_param("n", 2)
+            .with_order(super::Order::Desc)
+            .build();
+        assert_eq!(
+            "{\"script\":{\"id\":\"times_n\",\"params\":{\"n\":2}},\"type\":\"number\",\"order\":\"desc\"}",
+            serde_json::to_string(&stored).unwrap()
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_scan_stream_closes_on_error() {
+        use futures::StreamExt;
+        use serde_json::json;
+
+        use crate::{
+            async_client::tests::block_on,
+            tests::{make_hits_page, make_mock_async_client},
+        };
+
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/_search",
+                200,
+                make_hits_page(json!({"_scroll_id": "s1"}), &[]),
+            )
+            .with_response(
+                Method::POST,
+                "/_search/scroll",
+                200,
+                make_hits_page(json!({"_scroll_id": "s2"}), &[("1", 1), ("2", 2)]),
+            )
+            .with_response(Method::POST, "/_search/scroll", 500, "{}")
+            .with_response(Method::DELETE, "/_search/scroll", 200, "{}");
+        let client = make_mock_async_client(&transport);
+
+        let scroll = Duration::minutes(1);
+        let hits = block_on(async {
+            let scan_result: ScanResult<Value> = client
+                .search_query()
+                .with_indexes(&["test_idx"])
+                .scan(&scroll)
+                .await?;
+            let hits: Vec<_> = scan_result.stream(&client, scroll).collect().await;
+            Ok::<_, crate::error::EsError>(hits)
+        })
+        .unwrap();
+
+        assert_eq!(3, hits.len());
+        assert_eq!("1", hits[0].as_ref().unwrap().id);
+        assert_eq!("2", hits[1].as_ref().unwrap().id);
+        assert!(hits[2].is_err());
+
+        let requests = transport.requests();
+        let close = requests.last().unwrap();
+        assert_eq!(Method::DELETE, *close.method());
+        assert_eq!(Some("scroll_id=s2"), close.query());
+    }
 }
@@ -0,0 +1,412 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of the Multi Search API
+
+use reqwest::{Method, StatusCode};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::{unexpected_status, EsError, ServerError},
+    operations::common::{OptionVal, Options},
+    retry::{Retry, RetryPolicy},
+    util::StrJoin,
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{SearchQueryOperation, SearchResult, SearchResultInterim};
+
+/// The options of a search which can be given in its header line, the others
+/// are only allowed in the URL of a single search
+const HEADER_OPTIONS: [&str; 7] = [
+    "search_type",
+    "routing",
+    "preference",
+    "request_cache",
+    "ignore_unavailable",
+    "allow_no_indices",
+    "expand_wildcards",
+];
+
+impl<'a, 'b, C> SearchQueryOperation<'a, 'b, C> {
+    /// Add the header and body lines of this search to the request body of a
+    /// multi-search
+    fn add_to_msearch(&self, body: &mut String) -> Result<(), EsError> {
+        let mut header = Map::new();
+        // A point in time already determines the indexes
+        if !self.indexes.is_empty() && self.body.pit.is_none() {
+            header.insert("index".to_owned(), self.indexes.iter().join(",").into());
+        }
+        if !self.doc_types.is_empty() {
+            header.insert("type".to_owned(), self.doc_types.iter().join(",").into());
+        }
+        for &(key, OptionVal(ref value)) in &self.options.0 {
+            if !HEADER_OPTIONS.contains(&key) {
+                return Err(EsError::EsError(format!(
+                    "The {} option is not supported in a multi-search",
+                    key
+                )));
+            }
+            header.insert(key.to_owned(), value.as_str().into());
+        }
+        body.push_str(&serde_json::to_string(&header)?);
+        body.push('\n');
+        body.push_str(&serde_json::to_string(&self.body)?);
+        body.push('\n');
+        Ok(())
+    }
+
+    /// The result of this search in a multi-search, or the error which was
+    /// returned in its place
+    fn read_msearch_response<T>(&self, response: Value) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        if response.get("error").is_some() {
+            let status = response
+                .get("status")
+                .and_then(Value::as_u64)
+                .and_then(|status| StatusCode::from_u16(status as u16).ok())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(EsError::EsServerError(Box::new(ServerError::new(
+                status,
+                &response.to_string(),
+            ))));
+        }
+        let interim: SearchResultInterim<T> = serde_json::from_value(response)?;
+        self.read_interim(interim)
+    }
+}
+
+/// A multi-search operation, to send many searches in one request
+#[derive(Debug)]
+pub struct MultiSearchOperation<'a, 'b, C = Client> {
+    /// The HTTP client
+    client: &'a C,
+
+    /// The default indexes of the searches
+    indexes: &'b [&'b str],
+
+    /// The searches
+    requests: &'b [SearchQueryOperation<'a, 'b, C>],
+
+    /// Optional options
+    options: Options<'b>,
+
+    /// Override the client's retry policy
+    retry_policy: Option<&'b RetryPolicy>,
+}
+
+impl<'a, 'b, C> MultiSearchOperation<'a, 'b, C> {
+    fn new(client: &'a C, requests: &'b [SearchQueryOperation<'a, 'b, C>]) -> Self {
+        MultiSearchOperation {
+            client,
+            indexes: &[],
+            requests,
+            options: Options::default(),
+            retry_policy: None,
+        }
+    }
+
+    /// The indexes of any searches which do not specify them
+    pub fn with_indexes(&mut self, indexes: &'b [&'b str]) -> &mut Self {
+        self.indexes = indexes;
+        self
+    }
+
+    add_option!(with_max_concurrent_searches, "max_concurrent_searches");
+    add_option!(with_search_type, "search_type");
+
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&mut self, retry_policy: &'b RetryPolicy) -> &mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    fn url(&self) -> String {
+        let mut url = String::from("/");
+        if !self.indexes.is_empty() {
+            url.push_str(&self.indexes.iter().join(","));
+            url.push('/');
+        }
+        url.push_str("_msearch");
+        url.push_str(&self.options.to_string());
+        url
+    }
+
+    fn format_requests(&self) -> Result<String, EsError> {
+        let mut body = String::new();
+        for request in self.requests {
+            request.add_to_msearch(&mut body)?;
+        }
+        Ok(body)
+    }
+
+    fn read_result<R, T>(
+        &self,
+        response: R,
+    ) -> Result<Vec<Result<SearchResult<T>, EsError>>, EsError>
+    where
+        R: EsResponse,
+        T: DeserializeOwned,
+    {
+        match response.status_code() {
+            StatusCode::OK => {
+                let result: MultiSearchResult = response.read_response()?;
+                if result.responses.len() != self.requests.len() {
+                    return Err(EsError::EsError(format!(
+                        "Expected {} search responses, received {}",
+                        self.requests.len(),
+                        result.responses.len()
+                    )));
+                }
+                Ok(self
+                    .requests
+                    .iter()
+                    .zip(result.responses)
+                    .map(|(request, response)| request.read_msearch_response(response))
+                    .collect())
+            }
+            _ => Err(unexpected_status(response)),
+        }
+    }
+}
+
+impl<'a, 'b> MultiSearchOperation<'a, 'b> {
+    /// Performs the searches, returning the result of each in the order they
+    /// were given.  A search which fails does not affect the others.
+    pub fn send<T>(&self) -> Result<Vec<Result<SearchResult<T>, EsError>>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        if self.requests.is_empty() {
+            return Ok(Vec::new());
+        }
+        let body = self.format_requests()?;
+        let response = self.client.do_es_op(
+            &self.url(),
+            Retry::from(self.retry_policy),
+            Method::POST,
+            Some(body.into_bytes()),
+        )?;
+        self.read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> MultiSearchOperation<'a, 'b, AsyncClient> {
+    /// Performs the searches, returning the result of each in the order they
+    /// were given.  A search which fails does not affect the others.
+    pub async fn send<T>(&self) -> Result<Vec<Result<SearchResult<T>, EsError>>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        if self.requests.is_empty() {
+            return Ok(Vec::new());
+        }
+        let body = self.format_requests()?;
+        let response = self
+            .client
+            .do_es_op(
+                &self.url(),
+                Retry::from(self.retry_policy),
+                Method::POST,
+                Some(body.into_bytes()),
+            )
+            .await?;
+        self.read_result(response)
+    }
+}
+
+impl Client {
+    /// Many searches, in one request.  Each search is built with
+    /// `search_query`, as for a single search; only the options which
+    /// ElasticSearch allows in the header of a multi-search can be used:
+    /// routing, preference, search type, request cache and the index options.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-multi-search.html
+    pub fn msearch<'a, 'b>(
+        &'a self,
+        requests: &'b [SearchQueryOperation<'a, 'b>],
+    ) -> MultiSearchOperation<'a, 'b> {
+        MultiSearchOperation::new(self, requests)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Many searches, in one request, see
+    /// [`Client::msearch`](../../../struct.Client.html#method.msearch)
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-multi-search.html
+    pub fn msearch<'a, 'b>(
+        &'a self,
+        requests: &'b [SearchQueryOperation<'a, 'b, AsyncClient>],
+    ) -> MultiSearchOperation<'a, 'b, AsyncClient> {
+        MultiSearchOperation::new(self, requests)
+    }
+}
+
+/// The result of a multi-search, each response is either a search result or
+/// an error
+#[derive(Debug, Deserialize)]
+struct MultiSearchResult {
+    responses: Vec<Value>,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        operations::search::{
+            aggregations::{bucket::Terms, Aggregations},
+            Slice, Sort,
+        },
+        query::Query,
+        tests::make_mock_client,
+        transport::MockTransport,
+    };
+
+    #[test]
+    fn test_msearch() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_msearch",
+            200,
+            r#"{"responses": [
+                {"took": 1, "timed_out": false,
+                 "_shards": {"total": 1, "successful": 1, "failed": 0},
+                 "hits": {"total": 1, "max_score": 1.0, "hits": [
+                     {"_index": "idx_a", "_type": "test_type", "_id": "1", "_score": 1.0,
+                      "_source": {"str_field": "A"}}
+                 ]},
+                 "aggregations": {"str": {
+                     "doc_count_error_upper_bound": 0, "sum_other_doc_count": 0,
+                     "buckets": [{"key": "A", "doc_count": 1}]
+                 }}},
+                {"error": {"root_cause": [], "type": "index_not_found_exception",
+                           "reason": "no such index", "index": "idx_b"},
+                 "status": 404},
+                {"took": 1, "timed_out": false,
+                 "_shards": {"total": 1, "successful": 1, "failed": 0},
+                 "hits": {"total": 0, "max_score": null, "hits": []}}
+            ]}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let query = Query::build_match_all().build();
+        let aggs = Aggregations::from(("str", Terms::field("str_field")));
+        let sort = Sort::field("int_field");
+        let search_after = [json!(5)];
+        let mut requests = [
+            client.search_query(),
+            client.search_query(),
+            client.search_query(),
+        ];
+        requests[0]
+            .with_indexes(&["idx_a"])
+            .with_query(&query)
+            .with_aggs(&aggs)
+            .with_size(10);
+        requests[1]
+            .with_indexes(&["idx_b"])
+            .with_routing("user1")
+            .with_request_cache(true);
+        requests[2]
+            .with_sort(&sort)
+            .with_search_after(&search_after)
+            .with_slice(Slice::new(0, 2));
+        let results = client.msearch(&requests).send::<Value>().unwrap();
+
+        assert_eq!(3, results.len());
+        let first = results[0].as_ref().unwrap();
+        assert_eq!("1", first.hits.hits[0].id);
+        let buckets = &first
+            .aggs_ref()
+            .unwrap()
+            .get("str")
+            .unwrap()
+            .as_terms()
+            .unwrap()
+            .buckets;
+        assert_eq!(1, buckets[0].doc_count);
+        assert!(results[1].as_ref().unwrap_err().is_index_missing());
+        assert_eq!(0, results[2].as_ref().unwrap().hits.total);
+
+        let requests = transport.requests();
+        let body = String::from_utf8_lossy(requests[0].body().unwrap()).into_owned();
+        let lines: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(6, lines.len());
+        assert_eq!("idx_a", lines[0]["index"]);
+        assert_eq!(10, lines[1]["size"]);
+        assert_eq!(
+            "str_field",
+            lines[1]["aggregations"]["str"]["terms"]["field"]
+        );
+        assert_eq!("user1", lines[2]["routing"]);
+        assert_eq!("true", lines[2]["request_cache"]);
+        assert_eq!(json!({}), lines[4]);
+        assert!(lines[5]["sort"].is_array());
+        assert_eq!(json!([5]), lines[5]["search_after"]);
+        assert_eq!(json!({"id": 0, "max": 2}), lines[5]["slice"]);
+    }
+
+    #[test]
+    fn test_msearch_index() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/idx_a/_msearch?max_concurrent_searches=2",
+            200,
+            r#"{"responses": []}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let requests = [client.search_query()];
+        let err = client
+            .msearch(&requests)
+            .with_indexes(&["idx_a"])
+            .with_max_concurrent_searches(2)
+            .send::<Value>()
+            .unwrap_err();
+        assert!(err.to_string().contains("Expected 1 search responses"));
+    }
+
+    #[test]
+    fn test_msearch_without_request() {
+        let transport = MockTransport::new();
+        let client = make_mock_client(&transport);
+
+        let results = client.msearch(&[]).send::<Value>().unwrap();
+        assert!(results.is_empty());
+
+        let mut requests = [client.search_query()];
+        requests[0].with_explain(true);
+        let err = client.msearch(&requests).send::<Value>().unwrap_err();
+        assert!(err.to_string().contains("explain"));
+        assert!(transport.requests().is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Searching a point in time, a consistent view of one or more indexes.
+//!
+//! Searches of a point in time see the same documents however the indexes
+//! change, which with `search_after` allows consistent paging without a
+//! scroll.  Requires ElasticSearch 7.10 or later.
+//!
+//! See: https://www.elastic.co/guide/en/elasticsearch/reference/current/point-in-time-api.html
+
+use std::sync::{Arc, Mutex};
+
+use reqwest::StatusCode;
+
+use serde::{ser::Serializer, Deserialize, Serialize};
+
+use crate::{
+    error::{unexpected_status, EsError},
+    operations::{common::Options, format_multi},
+    units::Duration,
+    Client, EsResponse,
+};
+
+/// The ID of a point in time, shared by the `PointInTime` and every search
+/// of it, as each search can return a new ID
+#[derive(Clone, Debug)]
+pub(crate) struct PitRef {
+    id: Arc<Mutex<String>>,
+    keep_alive: String,
+}
+
+impl PitRef {
+    pub(crate) fn id(&self) -> String {
+        self.id.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Use the ID returned by the latest search
+    pub(crate) fn update(&self, id: &str) {
+        let mut current = self.id.lock().unwrap_or_else(|e| e.into_inner());
+        if *current != id {
+            *current = id.to_owned();
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PitBody<'a> {
+    id: &'a str,
+    keep_alive: &'a str,
+}
+
+/// Serialized as the `pit` of a search, so the keep alive of the point in
+/// time is extended by every search
+impl Serialize for PitRef {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let id = self.id();
+        PitBody {
+            id: &id,
+            keep_alive: &self.keep_alive,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Serialize)]
+struct ClosePitBody<'a> {
+    id: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenPointInTimeResult {
+    id: String,
+}
+
+/// An open point in time, see `Client::open_point_in_time`.
+///
+/// The point in time is closed when this is dropped, any error in doing so
+/// is logged; use `close` to handle the error instead.
+#[derive(Debug)]
+pub struct PointInTime<'a> {
+    client: &'a Client,
+    pit: PitRef,
+    closed: bool,
+}
+
+impl<'a> PointInTime<'a> {
+    /// The current ID of the point in time
+    pub fn id(&self) -> String {
+        self.pit.id()
+    }
+
+    pub(crate) fn pit_ref(&self) -> PitRef {
+        self.pit.clone()
+    }
+
+    /// Close the point in time
+    pub fn close(mut self) -> Result<(), EsError> {
+        self.closed = true;
+        self.close_pit()
+    }
+
+    fn close_pit(&self) -> Result<(), EsError> {
+        let id = self.pit.id();
+        let response = self
+            .client
+            .delete_body_op("/_pit", &ClosePitBody { id: &id })?;
+        match response.status_code() {
+            StatusCode::OK => Ok(()),        // closed
+            StatusCode::NOT_FOUND => Ok(()), // previously closed, or expired
+            _ => Err(unexpected_status(response)),
+        }
+    }
+}
+
+impl<'a> Drop for PointInTime<'a> {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        if let Err(e) = self.close_pit() {
+            log::error!("Cannot close point in time: {}", e);
+        }
+    }
+}
+
+impl Client {
+    /// Open a point in time of the given indexes, or all indexes if none are
+    /// given, which is kept alive for `keep_alive` after each search of it.
+    /// Requires ElasticSearch 7.10 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/point-in-time-api.html
+    pub fn open_point_in_time(
+        &self,
+        indexes: &[&str],
+        keep_alive: &Duration,
+    ) -> Result<PointInTime<'_>, EsError> {
+        let mut options = Options::new();
+        options.push("keep_alive", keep_alive);
+        let url = format!("/{}/_pit{}", format_multi(indexes), options);
+        let response = self.post_op(&url)?;
+        match response.status_code() {
+            StatusCode::OK => {
+                let result: OpenPointInTimeResult = response.read_response()?;
+                Ok(PointInTime {
+                    client: self,
+                    pit: PitRef {
+                        id: Arc::new(Mutex::new(result.id)),
+                        keep_alive: keep_alive.to_string(),
+                    },
+                    closed: false,
+                })
+            }
+            _ => Err(unexpected_status(response)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        operations::search::{Order, Sort},
+        tests::{make_hits_page, make_mock_client, request_body},
+        transport::MockTransport,
+        units::Duration,
+    };
+
+    /// A page of hits in the format of ElasticSearch 7.10, the first version
+    /// with points in time
+    fn page(pit_id: &str, hits: &[(&str, i64)]) -> String {
+        make_hits_page(
+            json!({"pit_id": pit_id,
+                   "hits": {"total": {"value": 3, "relation": "eq"}}}),
+            hits,
+        )
+    }
+
+    #[test]
+    fn test_point_in_time() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/_pit?keep_alive=1m",
+                200,
+                r#"{"id": "pit1"}"#,
+            )
+            .with_response(Method::POST, "/_search", 200, page("pit2", &[("1", 1)]))
+            .with_response(Method::DELETE, "/_pit", 200, r#"{"succeeded": true}"#);
+        let client = make_mock_client(&transport);
+
+        let pit = client
+            .open_point_in_time(&["test_idx"], &Duration::minutes(1))
+            .unwrap();
+        let result = client
+            .search_query()
+            .with_pit(&pit)
+            .send::<Value>()
+            .unwrap();
+        assert_eq!(Some("pit2".to_owned()), result.pit_id);
+        assert_eq!(3, result.hits.total);
+        assert_eq!("pit2", pit.id());
+        drop(pit);
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!(
+            json!({"id": "pit1", "keep_alive": "1m"}),
+            request_body(&requests[1])["pit"]
+        );
+        assert_eq!(Method::DELETE, *requests[2].method());
+        assert_eq!("pit2", request_body(&requests[2])["id"]);
+    }
+
+    #[test]
+    fn test_point_in_time_search_after() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/_all/_pit?keep_alive=5m",
+                200,
+                r#"{"id": "pit1"}"#,
+            )
+            .with_response(
+                Method::POST,
+                "/_search",
+                200,
+                page("pit1", &[("1", 1), ("2", 2)]),
+            )
+            .with_response(Method::POST, "/_search", 200, page("pit2", &[("3", 3)]))
+            .with_response(Method::POST, "/_search", 200, page("pit2", &[]))
+            .with_response(Method::DELETE, "/_pit", 404, "{}");
+        let client = make_mock_client(&transport);
+
+        let pit = client
+            .open_point_in_time(&[], &Duration::minutes(5))
+            .unwrap();
+        let sort = Sort::field_order("int_field", Order::Asc);
+        let ids: Vec<String> = client
+            .search_query()
+            .with_pit(&pit)
+            .with_sort(&sort)
+            .with_size(2)
+            .search_after_iter::<Value>()
+            .unwrap()
+            .map(|hit| hit.unwrap().id)
+            .collect();
+        assert_eq!(vec!["1", "2", "3"], ids);
+        assert_eq!("pit2", pit.id());
+        assert!(pit.close().is_ok());
+
+        let requests = transport.requests();
+        assert_eq!(5, requests.len());
+        let bodies: Vec<Value> = requests[1..4].iter().map(request_body).collect();
+        assert_eq!(json!([{"int_field": {"order": "asc"}}]), bodies[0]["sort"]);
+        assert_eq!(json!({"id": "pit1", "keep_alive": "5m"}), bodies[0]["pit"]);
+        assert_eq!(json!({"id": "pit1", "keep_alive": "5m"}), bodies[1]["pit"]);
+        assert_eq!(json!({"id": "pit2", "keep_alive": "5m"}), bodies[2]["pit"]);
+        assert_eq!(json!([2, "test_type#2"]), bodies[1]["search_after"]);
+    }
+}
@@ -0,0 +1,190 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of ElasticSearch [rescoring](https://www.elastic.co/guide/en/elasticsearch/reference/current/search-request-rescore.html)
+//!
+//! Rescoring re-ranks the top hits of each shard with a second, typically
+//! more expensive, query.  Rescores can be stacked, each rescoring the
+//! results of the previous one.
+
+use serde::{ser::Serializer, Serialize};
+
+use crate::{json::ShouldSkip, query::Query};
+
+/// How the score of the original query and the rescore query are combined
+#[derive(Debug)]
+pub enum RescoreMode {
+    Total,
+    Multiply,
+    Avg,
+    Max,
+    Min,
+}
+
+impl Serialize for RescoreMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            RescoreMode::Total => "total",
+            RescoreMode::Multiply => "multiply",
+            RescoreMode::Avg => "avg",
+            RescoreMode::Max => "max",
+            RescoreMode::Min => "min",
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RescoreQuery {
+    rescore_query: Query,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    query_weight: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    rescore_query_weight: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    score_mode: Option<RescoreMode>,
+}
+
+/// A query with which to rescore the top hits of each shard, see
+/// `SearchQueryOperation::with_rescore`
+#[derive(Debug, Serialize)]
+pub struct Rescore {
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    window_size: Option<u64>,
+    query: RescoreQuery,
+}
+
+impl Rescore {
+    pub fn new(rescore_query: Query) -> Rescore {
+        Rescore {
+            window_size: None,
+            query: RescoreQuery {
+                rescore_query,
+                query_weight: None,
+                rescore_query_weight: None,
+                score_mode: None,
+            },
+        }
+    }
+
+    /// The number of hits of each shard to rescore, by default the size of
+    /// the search
+    pub fn with_window_size<T: Into<u64>>(mut self, window_size: T) -> Self {
+        self.window_size = Some(window_size.into());
+        self
+    }
+
+    /// The weight of the original score, by default 1
+    pub fn with_query_weight<T: Into<f64>>(mut self, query_weight: T) -> Self {
+        self.query.query_weight = Some(query_weight.into());
+        self
+    }
+
+    /// The weight of the rescore query's score, by default 1
+    pub fn with_rescore_query_weight<T: Into<f64>>(mut self, rescore_query_weight: T) -> Self {
+        self.query.rescore_query_weight = Some(rescore_query_weight.into());
+        self
+    }
+
+    /// How the scores are combined, by default `Total`
+    pub fn with_score_mode(mut self, score_mode: RescoreMode) -> Self {
+        self.query.score_mode = Some(score_mode);
+        self
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        operations::search::{Order, Sort},
+        query::{full_text::MatchType, functions::Function, Query},
+        tests::{make_mock_client, request_body},
+        transport::MockTransport,
+    };
+
+    use super::{Rescore, RescoreMode};
+
+    #[test]
+    fn test_rescore() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_search",
+            200,
+            r#"{"took": 1, "timed_out": false,
+                "_shards": {"total": 1, "successful": 1, "failed": 0},
+                "hits": {"total": 0, "hits": []}}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let query = Query::build_match("str_field", "a b").build();
+        let proximity = Rescore::new(
+            Query::build_match("str_field", "a b")
+                .with_type(MatchType::Phrase)
+                .with_slop(2)
+                .build(),
+        )
+        .with_window_size(50u64)
+        .with_query_weight(0.7)
+        .with_rescore_query_weight(1.2);
+        let function_score = Rescore::new(
+            Query::build_function_score()
+                .with_function(Function::build_script_score("_score * 2").build())
+                .build(),
+        )
+        .with_window_size(10u64)
+        .with_score_mode(RescoreMode::Multiply);
+        let sort = Sort::field_order("_score", Order::Desc);
+        client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .with_query(&query)
+            .with_rescore(&proximity)
+            .with_rescore(&function_score)
+            .with_sort(&sort)
+            .with_track_scores(true)
+            .send::<Value>()
+            .unwrap();
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(
+            json!([
+                {"window_size": 50, "query": {
+                    "rescore_query": {"match": {"str_field": {
+                        "query": "a b", "type": "phrase", "slop": 2
+                    }}},
+                    "query_weight": 0.7,
+                    "rescore_query_weight": 1.2
+                }},
+                {"window_size": 10, "query": {
+                    "rescore_query": {"function_score": {"functions": [
+                        {"script_score": {"params": {}, "inline": "_score * 2"}}
+                    ]}},
+                    "score_mode": "multiply"
+                }}
+            ]),
+            body["rescore"]
+        );
+        assert_eq!(json!([{"_score": {"order": "desc"}}]), body["sort"]);
+        assert_eq!(true, body["track_scores"]);
+    }
+}
@@ -0,0 +1,368 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Paging through the results of a search with `search_after`.
+//!
+//! Each page is requested with the sort values of the last hit of the
+//! previous page, so unlike `from` and `size` it is not limited by
+//! `index.max_result_window`, and unlike a scroll it does not hold any
+//! resources open on the server.  Requires ElasticSearch 5.0 or later.
+//!
+//! See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-request-search-after.html
+
+use std::vec;
+
+use reqwest::StatusCode;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::{
+    error::{unexpected_status, EsError},
+    retry::{Retry, RetryPolicy},
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{
+    pit::PitRef, SearchHitsHitsResult, SearchQueryOperation, SearchQueryOperationBody,
+    SearchResultInterim,
+};
+
+/// Sorted on last, so that hits with the same values for every other sort
+/// field are in a consistent order, unless another field is given with
+/// `SearchQueryOperation::with_tie_breaker`
+const DEFAULT_TIE_BREAKER: &str = "_uid";
+
+fn is_tie_breaker(sort_by: &Value, tie_breaker: &str) -> bool {
+    let is_unique = |field: &str| field == tie_breaker || field == "_uid" || field == "_id";
+    match *sort_by {
+        Value::String(ref field) => is_unique(field),
+        Value::Object(ref field) => field.keys().any(|field| is_unique(field)),
+        _ => false,
+    }
+}
+
+/// The state of paging through a search, common to the iterator and the
+/// stream
+#[derive(Debug)]
+struct SearchAfter<T> {
+    url: String,
+    /// The search, with the `search_after` of the next page
+    body: Value,
+    /// The point in time being searched, if any
+    pit: Option<PitRef>,
+    retry_policy: Option<RetryPolicy>,
+    page: vec::IntoIter<SearchHitsHitsResult<T>>,
+    finished: bool,
+}
+
+impl<T> SearchAfter<T>
+where
+    T: DeserializeOwned,
+{
+    fn new(
+        url: String,
+        body: &SearchQueryOperationBody,
+        tie_breaker: Option<&str>,
+        retry_policy: Option<&RetryPolicy>,
+    ) -> Result<Self, EsError> {
+        let tie_breaker = tie_breaker.unwrap_or(DEFAULT_TIE_BREAKER);
+        let pit = body.pit.clone();
+        let mut body = serde_json::to_value(body)?;
+        if let Value::Object(ref mut body) = body {
+            // Pages are found by `search_after`, not by position
+            body.remove("from");
+
+            let mut sort = match body.remove("sort") {
+                Some(Value::Array(sort)) => sort,
+                _ => vec![json!({"_score": {"order": "desc"}})],
+            };
+            // Searches of a point in time are already sorted on `_shard_doc`
+            // last, to break ties
+            if pit.is_none()
+                && !sort
+                    .iter()
+                    .any(|sort_by| is_tie_breaker(sort_by, tie_breaker))
+            {
+                sort.push(json!({ tie_breaker: {"order": "asc"} }));
+            }
+            body.insert("sort".to_owned(), Value::Array(sort));
+        }
+        Ok(SearchAfter {
+            url,
+            body,
+            pit,
+            retry_policy: retry_policy.cloned(),
+            page: Vec::new().into_iter(),
+            finished: false,
+        })
+    }
+
+    fn retry(&self) -> Retry<'_> {
+        Retry::from(self.retry_policy.as_ref())
+    }
+
+    /// Read a page of hits, returning the first, or `None` if there are no
+    /// more
+    fn read_page<R: EsResponse>(
+        &mut self,
+        response: Result<R, EsError>,
+    ) -> Option<Result<SearchHitsHitsResult<T>, EsError>> {
+        match response.and_then(|response| self.next_page(response)) {
+            Ok(()) => {
+                let hit = self.page.next();
+                self.finished = hit.is_none();
+                hit.map(Ok)
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+
+    fn next_page<R: EsResponse>(&mut self, response: R) -> Result<(), EsError> {
+        let result: SearchResultInterim<T> = match response.status_code() {
+            StatusCode::OK => response.read_response()?,
+            _ => return Err(unexpected_status(response)),
+        };
+        if let (Some(pit), Some(pit_id)) = (&self.pit, &result.pit_id) {
+            pit.update(pit_id);
+            self.body["pit"]["id"] = Value::String(pit_id.clone());
+        }
+        if let Some(last) = result.hits.hits.last() {
+            match last.sort {
+                Some(ref sort) => self.body["search_after"] = Value::Array(sort.clone()),
+                None => return Err(EsError::EsError("Expecting sort values".to_owned())),
+            }
+        }
+        self.page = result.hits.hits.into_iter();
+        Ok(())
+    }
+}
+
+/// Iterates over every hit of a search, requesting each page after the last
+/// hit of the previous one, see `SearchQueryOperation::search_after_iter`
+#[derive(Debug)]
+pub struct SearchAfterIterator<'a, T> {
+    client: &'a Client,
+    state: SearchAfter<T>,
+}
+
+impl<'a, T> Iterator for SearchAfterIterator<'a, T>
+where
+    T: DeserializeOwned,
+{
+    type Item = Result<SearchHitsHitsResult<T>, EsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(hit) = self.state.page.next() {
+            return Some(Ok(hit));
+        }
+        if self.state.finished {
+            return None;
+        }
+        let response = self.client.post_body_op_with_retry(
+            &self.state.url,
+            &self.state.body,
+            self.state.retry(),
+        );
+        self.state.read_page(response)
+    }
+}
+
+impl<'a, 'b> SearchQueryOperation<'a, 'b> {
+    /// An iterator over every hit of the search, requesting pages of the
+    /// given size with `search_after`.  If the search is not sorted by `_uid`,
+    /// or the field given with `with_tie_breaker`, then it is added as the last
+    /// sort field, to break ties; if the search is not sorted at all it is
+    /// sorted by score.  When searching a point in
+    /// time, see `with_pit`, ties are broken by ElasticSearch instead and the
+    /// latest ID of the point in time is used for each page.
+    ///
+    /// If an error is returned, the iterator ends.
+    pub fn search_after_iter<T>(&self) -> Result<SearchAfterIterator<'a, T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(SearchAfterIterator {
+            client: self.client,
+            state: SearchAfter::new(self.url(), &self.body, self.tie_breaker, self.retry_policy)?,
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> SearchQueryOperation<'a, 'b, AsyncClient> {
+    /// A stream of every hit of the search, the asynchronous equivalent of
+    /// `search_after_iter`.
+    pub fn search_after_stream<T>(
+        &self,
+    ) -> Result<impl futures::Stream<Item = Result<SearchHitsHitsResult<T>, EsError>> + 'a, EsError>
+    where
+        T: DeserializeOwned + 'a,
+    {
+        let client = self.client;
+        let state = SearchAfter::new(self.url(), &self.body, self.tie_breaker, self.retry_policy)?;
+        Ok(futures::stream::unfold(
+            state,
+            move |mut state| async move {
+                if let Some(hit) = state.page.next() {
+                    return Some((Ok(hit), state));
+                }
+                if state.finished {
+                    return None;
+                }
+                let response = client
+                    .post_body_op_with_retry(&state.url, &state.body, state.retry())
+                    .await;
+                state.read_page(response).map(|hit| (hit, state))
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        operations::search::{Order, Sort},
+        tests::{make_hits_page, make_mock_client, request_body},
+        transport::MockTransport,
+    };
+
+    fn page(hits: &[(&str, i64)]) -> String {
+        make_hits_page(json!({}), hits)
+    }
+
+    #[test]
+    fn test_search_after_iter() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/_search",
+                200,
+                page(&[("1", 1), ("2", 2)]),
+            )
+            .with_response(Method::POST, "/test_idx/_search", 200, page(&[("3", 3)]))
+            .with_response(Method::POST, "/test_idx/_search", 200, page(&[]));
+        let client = make_mock_client(&transport);
+
+        let sort = Sort::field_order("int_field", Order::Asc);
+        let ids: Vec<String> = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .with_sort(&sort)
+            .with_from(10)
+            .with_size(2)
+            .search_after_iter::<Value>()
+            .unwrap()
+            .map(|hit| hit.unwrap().id)
+            .collect();
+        assert_eq!(vec!["1", "2", "3"], ids);
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        let bodies: Vec<Value> = requests.iter().map(request_body).collect();
+        assert_eq!(
+            json!([{"int_field": {"order": "asc"}}, {"_uid": {"order": "asc"}}]),
+            bodies[0]["sort"]
+        );
+        assert!(bodies[0].get("from").is_none());
+        assert!(bodies[0].get("search_after").is_none());
+        assert_eq!(2, bodies[0]["size"]);
+        assert_eq!(json!([2, "test_type#2"]), bodies[1]["search_after"]);
+        assert_eq!(json!([3, "test_type#3"]), bodies[2]["search_after"]);
+    }
+
+    #[test]
+    fn test_search_after_default_sort() {
+        let transport =
+            MockTransport::new().with_response(Method::POST, "/_all/_search", 200, page(&[]));
+        let client = make_mock_client(&transport);
+
+        let hits: Vec<_> = client
+            .search_query()
+            .search_after_iter::<Value>()
+            .unwrap()
+            .collect();
+        assert!(hits.is_empty());
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(
+            json!([{"_score": {"order": "desc"}}, {"_uid": {"order": "asc"}}]),
+            body["sort"]
+        );
+    }
+
+    #[test]
+    fn test_search_after_tie_breaker() {
+        let transport =
+            MockTransport::new().with_response(Method::POST, "/_all/_search", 200, page(&[]));
+        let client = make_mock_client(&transport);
+
+        let sort = Sort::field_order("int_field", Order::Asc);
+        let mut search = client.search_query();
+        search.with_sort(&sort).with_tie_breaker("_id");
+        assert_eq!(0, search.search_after_iter::<Value>().unwrap().count());
+
+        let sort = Sort::field_order("_id", Order::Desc);
+        let mut search = client.search_query();
+        search.with_sort(&sort).with_tie_breaker("_id");
+        assert_eq!(0, search.search_after_iter::<Value>().unwrap().count());
+
+        let requests = transport.requests();
+        assert_eq!(
+            json!([{"int_field": {"order": "asc"}}, {"_id": {"order": "asc"}}]),
+            request_body(&requests[0])["sort"]
+        );
+        assert_eq!(
+            json!([{"_id": {"order": "desc"}}]),
+            request_body(&requests[1])["sort"]
+        );
+    }
+
+    #[test]
+    fn test_with_search_after() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_all/_search",
+            200,
+            page(&[("3", 3)]),
+        );
+        let client = make_mock_client(&transport);
+
+        let search_after = [json!(2), json!("test_type#2")];
+        let result = client
+            .search_query()
+            .with_search_after(&search_after)
+            .send::<Value>()
+            .unwrap();
+        assert_eq!(
+            Some(vec![json!(3), json!("test_type#3")]),
+            result.hits.hits[0].sort
+        );
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(json!([2, "test_type#2"]), body["search_after"]);
+    }
+}
@@ -0,0 +1,363 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Scanning the slices of a scroll in parallel.
+//!
+//! The slices are scrolled in parallel by a limited number of threads, the
+//! hits of every slice are returned by a single iterator, in no particular
+//! order.  Requires ElasticSearch 5.0 or later.
+
+use std::fmt::Debug;
+use std::mem;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use reqwest::StatusCode;
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use crate::{
+    error::{unexpected_status, EsError},
+    retry::{Retry, RetryPolicy},
+    units::Duration,
+    Client, EsResponse,
+};
+
+use super::{ScanResult, SearchHitsHitsResult, SearchQueryOperation, SearchResultInterim, Slice};
+
+/// The number of hits which can be waiting to be read, before the threads
+/// wait for them to be
+const BUFFERED_HITS: usize = 1000;
+
+/// The most slices which are scrolled at once, each by its own thread
+const MAX_THREADS: u32 = 8;
+
+type Hit<T> = Result<SearchHitsHitsResult<T>, EsError>;
+
+/// A slice to be scrolled by a thread
+struct SliceScan {
+    client: Client,
+    url: String,
+    body: Value,
+    scroll: Duration,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl SliceScan {
+    fn open<T>(&self) -> Result<ScanResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.client.post_body_op_with_retry(
+            &self.url,
+            &self.body,
+            Retry::from(self.retry_policy.as_ref()),
+        )?;
+        match response.status_code() {
+            StatusCode::OK => {
+                let interim: SearchResultInterim<T> = response.read_response()?;
+                let scroll_id = match interim.scroll_id {
+                    Some(scroll_id) => scroll_id,
+                    None => return Err(EsError::EsError("Expecting scroll_id".to_owned())),
+                };
+                Ok(ScanResult {
+                    scroll_id,
+                    took: interim.took,
+                    timed_out: interim.timed_out,
+                    shards: interim.shards,
+                    hits: interim.hits,
+                    aggs: None,
+                })
+            }
+            _ => Err(unexpected_status(response)),
+        }
+    }
+
+    /// Send every hit of the slice, until there are no more, or an error.
+    /// The scroll is closed when the `ScanIterator` is dropped.
+    ///
+    /// Returns false if the receiver has been dropped, so no more slices
+    /// should be scrolled.
+    fn run<T>(self, sender: &SyncSender<Hit<T>>) -> bool
+    where
+        T: DeserializeOwned + Debug,
+    {
+        let mut scan = match self.open() {
+            Ok(scan) => scan,
+            Err(err) => return sender.send(Err(err)).is_ok(),
+        };
+        // The first page is returned when the scroll is opened
+        let first_page = mem::take(&mut scan.hits.hits);
+        let hits = first_page
+            .into_iter()
+            .map(Ok)
+            .chain(scan.iter(&self.client, self.scroll.clone()));
+        for hit in hits {
+            let is_err = hit.is_err();
+            if sender.send(hit).is_err() {
+                return false;
+            }
+            if is_err {
+                break;
+            }
+        }
+        true
+    }
+}
+
+/// An iterator over the hits of every slice of a scroll, see
+/// `SearchQueryOperation::sliced_scan`
+#[derive(Debug)]
+pub struct SlicedScanIterator<T> {
+    receiver: Option<Receiver<Hit<T>>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<T> Iterator for SlicedScanIterator<T> {
+    type Item = Hit<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver {
+            Some(ref receiver) => receiver.recv().ok(),
+            None => None,
+        }
+    }
+}
+
+impl<T> Drop for SlicedScanIterator<T> {
+    /// Stops the threads, and waits for them to close their scrolls
+    fn drop(&mut self) {
+        self.receiver.take();
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                log::error!("Sliced scan thread panicked");
+            }
+        }
+    }
+}
+
+impl<'a, 'b> SearchQueryOperation<'a, 'b> {
+    /// Scroll through the results in `slices` slices, optionally sliced by the
+    /// value of `field` rather than `_uid`, scrolled in parallel.  Up to eight
+    /// slices are scrolled at once, each by its own thread, the others once a
+    /// thread has finished its slice.  A single slice is a plain scroll, as
+    /// ElasticSearch does not allow a `slice` with a `max` of one.
+    ///
+    /// The scroll of each slice is closed when it has no more hits, or when
+    /// the iterator is dropped.
+    pub fn sliced_scan<T>(
+        &mut self,
+        slices: u32,
+        field: Option<&str>,
+        scroll: &Duration,
+    ) -> Result<SlicedScanIterator<T>, EsError>
+    where
+        T: DeserializeOwned + Debug + Send + 'static,
+    {
+        if slices == 0 {
+            return Err(EsError::EsError(
+                "A sliced scan needs at least one slice".to_owned(),
+            ));
+        }
+        self.options.push("scroll", scroll);
+        let url = self.url();
+
+        // The slices of each thread
+        let thread_count = slices.min(MAX_THREADS);
+        let mut thread_scans: Vec<Vec<SliceScan>> = (0..thread_count).map(|_| Vec::new()).collect();
+        for id in 0..slices {
+            let mut body = serde_json::to_value(&self.body)?;
+            if slices > 1 {
+                let mut slice = Slice::new(id, slices);
+                if let Some(field) = field {
+                    slice = slice.with_field(field);
+                }
+                body["slice"] = serde_json::to_value(slice)?;
+            }
+            thread_scans[(id % thread_count) as usize].push(SliceScan {
+                client: self.client.clone(),
+                url: url.clone(),
+                body,
+                scroll: scroll.clone(),
+                retry_policy: self.retry_policy.cloned(),
+            });
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(BUFFERED_HITS);
+        let threads = thread_scans
+            .into_iter()
+            .map(|slice_scans| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for slice_scan in slice_scans {
+                        if !slice_scan.run::<T>(&sender) {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+        Ok(SlicedScanIterator {
+            receiver: Some(receiver),
+            threads,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        tests::{make_hits_page, make_mock_client, request_body},
+        transport::MockTransport,
+        units::Duration,
+    };
+
+    use super::MAX_THREADS;
+
+    fn page(scroll_id: &str, hits: &[(&str, i64)]) -> String {
+        make_hits_page(json!({ "_scroll_id": scroll_id }), hits)
+    }
+
+    fn make_transport() -> MockTransport {
+        MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/_search?scroll=1m",
+                200,
+                page("s1", &[("1", 1), ("2", 2)]),
+            )
+            .with_response(
+                Method::POST,
+                "/test_idx/_search?scroll=1m",
+                200,
+                page("s2", &[("3", 3)]),
+            )
+            .with_response(Method::POST, "/_search/scroll", 200, page("s3", &[]))
+            .with_response(Method::DELETE, "/_search/scroll", 200, "{}")
+    }
+
+    fn count_requests(transport: &MockTransport, method: Method) -> usize {
+        transport
+            .requests()
+            .iter()
+            .filter(|request| *request.method() == method)
+            .count()
+    }
+
+    #[test]
+    fn test_sliced_scan() {
+        let transport = make_transport();
+        let client = make_mock_client(&transport);
+
+        let scroll = Duration::minutes(1);
+        let mut ids: Vec<String> = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .sliced_scan::<Value>(2, Some("date"), &scroll)
+            .unwrap()
+            .map(|hit| hit.unwrap().id)
+            .collect();
+        ids.sort();
+        assert_eq!(vec!["1", "2", "3"], ids);
+
+        let mut slices: Vec<Value> = transport
+            .requests()
+            .iter()
+            .filter(|request| request.path() == "/test_idx/_search")
+            .map(|request| request_body(request)["slice"].clone())
+            .collect();
+        slices.sort_by_key(|slice| slice["id"].as_u64());
+        assert_eq!(
+            vec![
+                json!({"id": 0, "max": 2, "field": "date"}),
+                json!({"id": 1, "max": 2, "field": "date"})
+            ],
+            slices
+        );
+        assert_eq!(2, count_requests(&transport, Method::DELETE));
+    }
+
+    #[test]
+    fn test_sliced_scan_dropped() {
+        let transport = make_transport();
+        let client = make_mock_client(&transport);
+
+        let scroll = Duration::minutes(1);
+        let first = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .sliced_scan::<Value>(2, None, &scroll)
+            .unwrap()
+            .next();
+        assert!(first.unwrap().is_ok());
+        assert_eq!(2, count_requests(&transport, Method::DELETE));
+    }
+
+    #[test]
+    fn test_sliced_scan_one_slice() {
+        let transport = make_transport();
+        let client = make_mock_client(&transport);
+
+        let scroll = Duration::minutes(1);
+        let ids: Vec<String> = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .sliced_scan::<Value>(1, Some("date"), &scroll)
+            .unwrap()
+            .map(|hit| hit.unwrap().id)
+            .collect();
+        assert_eq!(vec!["1", "2"], ids);
+        assert!(request_body(&transport.requests()[0])
+            .get("slice")
+            .is_none());
+
+        let result = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .sliced_scan::<Value>(0, None, &scroll);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sliced_scan_threads() {
+        let transport = MockTransport::new()
+            .with_response(
+                Method::POST,
+                "/test_idx/_search?scroll=1m",
+                200,
+                page("s1", &[("1", 1)]),
+            )
+            .with_response(Method::POST, "/_search/scroll", 200, page("s2", &[]))
+            .with_response(Method::DELETE, "/_search/scroll", 200, "{}");
+        let client = make_mock_client(&transport);
+
+        let slices = MAX_THREADS + 2;
+        let scroll = Duration::minutes(1);
+        let iter = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .sliced_scan::<Value>(slices, None, &scroll)
+            .unwrap();
+        assert_eq!(MAX_THREADS as usize, iter.threads.len());
+        assert_eq!(slices as usize, iter.count());
+        assert_eq!(slices as usize, count_requests(&transport, Method::DELETE));
+    }
+}
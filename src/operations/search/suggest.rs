@@ -0,0 +1,970 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of ElasticSearch [suggesters](https://www.elastic.co/guide/en/elasticsearch/reference/current/search-suggesters.html)
+//!
+//! Each suggester is given a name, the suggestions are returned by the same
+//! name, parsed according to the type of suggester.  Suggesters are either
+//! part of a search, see `SearchQueryOperation::with_suggest`, or requested
+//! on their own with `Client::suggest`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use reqwest::StatusCode;
+
+use serde::{
+    ser::{SerializeMap, Serializer},
+    Deserialize, Serialize,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::{unexpected_status, EsError},
+    json::ShouldSkip,
+    operations::format_indexes_and_types,
+    query::Fuzziness,
+    retry::{Retry, RetryPolicy},
+    units::{JsonVal, Location},
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+/// How the suggestions of a term suggester are sorted
+#[derive(Debug)]
+pub enum SuggestSort {
+    Score,
+    Frequency,
+}
+
+impl Serialize for SuggestSort {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            SuggestSort::Score => "score",
+            SuggestSort::Frequency => "frequency",
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Which terms suggestions are made for
+#[derive(Debug)]
+pub enum SuggestMode {
+    /// Only terms which are not in the index
+    Missing,
+    /// Only suggest terms which occur in more documents than the term
+    Popular,
+    /// Every term
+    Always,
+}
+
+impl Serialize for SuggestMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            SuggestMode::Missing => "missing",
+            SuggestMode::Popular => "popular",
+            SuggestMode::Always => "always",
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Term suggester, suggests terms by edit distance
+#[derive(Debug, Default, Serialize)]
+pub struct TermSuggester {
+    #[serde(skip)]
+    text: Option<String>,
+    field: String,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    analyzer: Option<String>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    shard_size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    sort: Option<SuggestSort>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    suggest_mode: Option<SuggestMode>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    max_edits: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    prefix_length: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    min_word_length: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    min_doc_freq: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    max_term_freq: Option<f64>,
+}
+
+impl Suggester {
+    pub fn build_term<A>(field: A) -> TermSuggester
+    where
+        A: Into<String>,
+    {
+        TermSuggester {
+            field: field.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl TermSuggester {
+    add_field!(with_text, text, String);
+    add_field!(with_analyzer, analyzer, String);
+    add_field!(with_size, size, u64);
+    add_field!(with_shard_size, shard_size, u64);
+    add_field!(with_sort, sort, SuggestSort);
+    add_field!(with_suggest_mode, suggest_mode, SuggestMode);
+    add_field!(with_max_edits, max_edits, u64);
+    add_field!(with_prefix_length, prefix_length, u64);
+    add_field!(with_min_word_length, min_word_length, u64);
+    add_field!(with_min_doc_freq, min_doc_freq, f64);
+    add_field!(with_max_term_freq, max_term_freq, f64);
+
+    pub fn build(self) -> Suggester {
+        Suggester::Term(self)
+    }
+}
+
+/// The tags with which a phrase suggester highlights the changed terms
+#[derive(Debug, Serialize)]
+struct PhraseHighlight {
+    pre_tag: String,
+    post_tag: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CollateQuery {
+    source: Value,
+}
+
+/// A query, a search template, with which each suggestion of a phrase
+/// suggester is checked against the index
+#[derive(Debug, Serialize)]
+pub struct Collate {
+    query: CollateQuery,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    params: Map<String, Value>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    prune: Option<bool>,
+}
+
+impl Collate {
+    /// The query, in which `{{suggestion}}` is replaced by each suggestion
+    pub fn new<V: Into<Value>>(query: V) -> Collate {
+        Collate {
+            query: CollateQuery {
+                source: query.into(),
+            },
+            params: Map::new(),
+            prune: None,
+        }
+    }
+
+    pub fn add_param<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+
+    add_field!(with_prune, prune, bool);
+}
+
+/// A generator of candidate terms for a phrase suggester
+#[derive(Debug, Default, Serialize)]
+pub struct DirectGenerator {
+    field: String,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    suggest_mode: Option<SuggestMode>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    max_edits: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    prefix_length: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    min_word_length: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    min_doc_freq: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    max_term_freq: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    pre_filter: Option<String>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    post_filter: Option<String>,
+}
+
+impl DirectGenerator {
+    pub fn new<A: Into<String>>(field: A) -> DirectGenerator {
+        DirectGenerator {
+            field: field.into(),
+            ..Default::default()
+        }
+    }
+
+    add_field!(with_size, size, u64);
+    add_field!(with_suggest_mode, suggest_mode, SuggestMode);
+    add_field!(with_max_edits, max_edits, u64);
+    add_field!(with_prefix_length, prefix_length, u64);
+    add_field!(with_min_word_length, min_word_length, u64);
+    add_field!(with_min_doc_freq, min_doc_freq, f64);
+    add_field!(with_max_term_freq, max_term_freq, f64);
+    add_field!(with_pre_filter, pre_filter, String);
+    add_field!(with_post_filter, post_filter, String);
+}
+
+/// Phrase suggester, suggests corrections of whole phrases
+#[derive(Debug, Default, Serialize)]
+pub struct PhraseSuggester {
+    #[serde(skip)]
+    text: Option<String>,
+    field: String,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    analyzer: Option<String>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    shard_size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    gram_size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    real_word_error_likelihood: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    confidence: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    max_errors: Option<f64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    separator: Option<String>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    highlight: Option<PhraseHighlight>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    collate: Option<Collate>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    direct_generator: Vec<DirectGenerator>,
+}
+
+impl Suggester {
+    pub fn build_phrase<A>(field: A) -> PhraseSuggester
+    where
+        A: Into<String>,
+    {
+        PhraseSuggester {
+            field: field.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl PhraseSuggester {
+    add_field!(with_text, text, String);
+    add_field!(with_analyzer, analyzer, String);
+    add_field!(with_size, size, u64);
+    add_field!(with_shard_size, shard_size, u64);
+    add_field!(with_gram_size, gram_size, u64);
+    add_field!(
+        with_real_word_error_likelihood,
+        real_word_error_likelihood,
+        f64
+    );
+    add_field!(with_confidence, confidence, f64);
+    add_field!(with_max_errors, max_errors, f64);
+    add_field!(with_separator, separator, String);
+    add_field!(with_collate, collate, Collate);
+
+    /// Surround the changed terms of each suggestion with these tags
+    pub fn with_highlight<P, Q>(mut self, pre_tag: P, post_tag: Q) -> Self
+    where
+        P: Into<String>,
+        Q: Into<String>,
+    {
+        self.highlight = Some(PhraseHighlight {
+            pre_tag: pre_tag.into(),
+            post_tag: post_tag.into(),
+        });
+        self
+    }
+
+    pub fn add_direct_generator(mut self, direct_generator: DirectGenerator) -> Self {
+        self.direct_generator.push(direct_generator);
+        self
+    }
+
+    pub fn build(self) -> Suggester {
+        Suggester::Phrase(self)
+    }
+}
+
+/// Fuzzy matching of the prefix of a completion suggester
+#[derive(Debug, Default, Serialize)]
+pub struct CompletionFuzzy {
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    fuzziness: Option<Fuzziness>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    transpositions: Option<bool>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    min_length: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    prefix_length: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    unicode_aware: Option<bool>,
+}
+
+impl CompletionFuzzy {
+    pub fn new() -> CompletionFuzzy {
+        Default::default()
+    }
+
+    add_field!(with_fuzziness, fuzziness, Fuzziness);
+    add_field!(with_transpositions, transpositions, bool);
+    add_field!(with_min_length, min_length, u64);
+    add_field!(with_prefix_length, prefix_length, u64);
+    add_field!(with_unicode_aware, unicode_aware, bool);
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ContextValue {
+    Category(String),
+    Geo(Location),
+}
+
+/// A context to which the suggestions of a completion suggester are limited,
+/// or by which they are boosted
+#[derive(Debug, Serialize)]
+pub struct CompletionContext {
+    context: ContextValue,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    boost: Option<f64>,
+    /// Whether a category context matches categories it is a prefix of
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    prefix: Option<bool>,
+    /// The precision of a geo context, a geohash length or a distance
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    precision: Option<JsonVal>,
+}
+
+impl CompletionContext {
+    /// A category context
+    pub fn category<A: Into<String>>(category: A) -> CompletionContext {
+        CompletionContext::new(ContextValue::Category(category.into()))
+    }
+
+    /// A geo context
+    pub fn geo<A: Into<Location>>(location: A) -> CompletionContext {
+        CompletionContext::new(ContextValue::Geo(location.into()))
+    }
+
+    fn new(context: ContextValue) -> CompletionContext {
+        CompletionContext {
+            context,
+            boost: None,
+            prefix: None,
+            precision: None,
+        }
+    }
+
+    add_field!(with_boost, boost, f64);
+    add_field!(with_prefix, prefix, bool);
+    add_field!(with_precision, precision, JsonVal);
+}
+
+/// Completion suggester, suggests completions of a prefix from a
+/// `completion` field, for search-as-you-type
+#[derive(Debug, Default, Serialize)]
+pub struct CompletionSuggester {
+    #[serde(skip)]
+    prefix: Option<String>,
+    /// Complete anything matching this regular expression, rather than a
+    /// prefix
+    #[serde(skip)]
+    regex: Option<String>,
+    field: String,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    skip_duplicates: Option<bool>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    fuzzy: Option<CompletionFuzzy>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    contexts: BTreeMap<String, Vec<CompletionContext>>,
+}
+
+impl Suggester {
+    pub fn build_completion<A>(field: A) -> CompletionSuggester
+    where
+        A: Into<String>,
+    {
+        CompletionSuggester {
+            field: field.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl CompletionSuggester {
+    add_field!(with_prefix, prefix, String);
+    add_field!(with_regex, regex, String);
+    add_field!(with_size, size, u64);
+    add_field!(with_skip_duplicates, skip_duplicates, bool);
+    add_field!(with_fuzzy, fuzzy, CompletionFuzzy);
+
+    /// Add a context of the named context mapping of the field
+    pub fn add_context<A>(mut self, name: A, context: CompletionContext) -> Self
+    where
+        A: Into<String>,
+    {
+        self.contexts.entry(name.into()).or_default().push(context);
+        self
+    }
+
+    pub fn build(self) -> Suggester {
+        Suggester::Completion(self)
+    }
+}
+
+/// A suggester
+#[derive(Debug)]
+pub enum Suggester {
+    Term(TermSuggester),
+    Phrase(PhraseSuggester),
+    Completion(CompletionSuggester),
+}
+
+impl Serialize for Suggester {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            Suggester::Term(ref term) => {
+                if let Some(ref text) = term.text {
+                    map.serialize_entry("text", text)?;
+                }
+                map.serialize_entry("term", term)?;
+            }
+            Suggester::Phrase(ref phrase) => {
+                if let Some(ref text) = phrase.text {
+                    map.serialize_entry("text", text)?;
+                }
+                map.serialize_entry("phrase", phrase)?;
+            }
+            Suggester::Completion(ref completion) => {
+                if let Some(ref prefix) = completion.prefix {
+                    map.serialize_entry("prefix", prefix)?;
+                }
+                if let Some(ref regex) = completion.regex {
+                    map.serialize_entry("regex", regex)?;
+                }
+                map.serialize_entry("completion", completion)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Named suggesters, and the text to make suggestions for if a suggester
+/// does not have its own
+#[derive(Debug, Default)]
+pub struct Suggest {
+    text: Option<String>,
+    suggesters: BTreeMap<String, Suggester>,
+}
+
+impl Suggest {
+    pub fn new() -> Suggest {
+        Default::default()
+    }
+
+    add_field!(with_text, text, String);
+
+    pub fn add<A>(mut self, name: A, suggester: Suggester) -> Self
+    where
+        A: Into<String>,
+    {
+        self.suggesters.insert(name.into(), suggester);
+        self
+    }
+}
+
+impl Serialize for Suggest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(ref text) = self.text {
+            map.serialize_entry("text", text)?;
+        }
+        for (name, suggester) in &self.suggesters {
+            map.serialize_entry(name, suggester)?;
+        }
+        map.end()
+    }
+}
+
+// Results
+
+/// The suggestions for one term, or the whole text of a phrase or completion
+/// suggester
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SuggestEntry<O> {
+    pub text: String,
+    pub offset: u64,
+    pub length: u64,
+    pub options: Vec<O>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TermSuggestOption {
+    pub text: String,
+    pub score: f64,
+    pub freq: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PhraseSuggestOption {
+    pub text: String,
+    pub score: f64,
+    /// Populated if the suggester has highlighting
+    pub highlighted: Option<String>,
+    /// Populated if the suggester has a collate query with `prune`
+    pub collate_match: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CompletionSuggestOption {
+    pub text: String,
+    #[serde(rename = "_score", alias = "score")]
+    pub score: f64,
+    #[serde(rename = "_index")]
+    pub index: Option<String>,
+    #[serde(rename = "_type")]
+    pub doc_type: Option<String>,
+    #[serde(rename = "_id")]
+    pub id: Option<String>,
+    #[serde(rename = "_source")]
+    pub source: Option<Value>,
+    /// The contexts of the suggestion, if the field has contexts
+    pub contexts: Option<HashMap<String, Vec<String>>>,
+}
+
+/// The suggestions of one suggester
+#[derive(Debug, Serialize)]
+pub enum SuggestionResult {
+    Term(Vec<SuggestEntry<TermSuggestOption>>),
+    Phrase(Vec<SuggestEntry<PhraseSuggestOption>>),
+    Completion(Vec<SuggestEntry<CompletionSuggestOption>>),
+}
+
+macro_rules! suggestion_as {
+    ($n:ident,$t:ident,$rt:ty) => {
+        pub fn $n(&self) -> Result<&[SuggestEntry<$rt>], EsError> {
+            match self {
+                SuggestionResult::$t(ref res) => Ok(res),
+                _ => Err(EsError::EsError(format!("Wrong type: {:?}", self))),
+            }
+        }
+    };
+}
+
+impl SuggestionResult {
+    suggestion_as!(as_term, Term, TermSuggestOption);
+    suggestion_as!(as_phrase, Phrase, PhraseSuggestOption);
+    suggestion_as!(as_completion, Completion, CompletionSuggestOption);
+
+    fn from(suggester: &Suggester, json: &Value) -> Result<SuggestionResult, EsError> {
+        use serde_json::value::from_value;
+
+        let json = json.clone();
+        Ok(match suggester {
+            Suggester::Term(_) => SuggestionResult::Term(from_value(json)?),
+            Suggester::Phrase(_) => SuggestionResult::Phrase(from_value(json)?),
+            Suggester::Completion(_) => SuggestionResult::Completion(from_value(json)?),
+        })
+    }
+}
+
+/// The suggestions of each suggester, by name
+#[derive(Debug, Serialize)]
+pub struct SuggestResult(HashMap<String, SuggestionResult>);
+
+impl SuggestResult {
+    pub fn get<'a>(&'a self, name: &str) -> Result<&'a SuggestionResult, EsError> {
+        match self.0.get(name) {
+            Some(suggestion) => Ok(suggestion),
+            None => Err(EsError::EsError(format!(
+                "No suggestion for name: {}",
+                name
+            ))),
+        }
+    }
+
+    fn from(suggest: &Suggest, json: &Value) -> Result<SuggestResult, EsError> {
+        let mut result = HashMap::new();
+        for (name, suggester) in &suggest.suggesters {
+            let json = match json.get(name) {
+                Some(json) => json,
+                None => return Err(EsError::EsError(format!("No key: {}", name))),
+            };
+            result.insert(name.clone(), SuggestionResult::from(suggester, json)?);
+        }
+        Ok(SuggestResult(result))
+    }
+}
+
+/// The suggestions, parsed according to the suggesters requested
+pub(super) fn read_suggest(
+    req_suggest: Option<&Suggest>,
+    raw_suggest: &Option<Value>,
+) -> Result<Option<SuggestResult>, EsError> {
+    match (req_suggest, raw_suggest) {
+        (Some(suggest), Some(raw_suggest)) => Ok(Some(SuggestResult::from(suggest, raw_suggest)?)),
+        (None, Some(_)) => Err(EsError::EsError(
+            "No suggest despite being in results".to_owned(),
+        )),
+        (_, None) => Ok(None),
+    }
+}
+
+/// Suggesters on their own, see `Client::suggest`
+#[derive(Debug)]
+pub struct SuggestOperation<'a, 'b, C = Client> {
+    /// The HTTP client
+    client: &'a C,
+
+    /// The indexes from which to suggest
+    indexes: &'b [&'b str],
+
+    /// The suggesters
+    suggest: &'b Suggest,
+
+    /// Overrides the client's retry policy
+    retry_policy: Option<&'b RetryPolicy>,
+}
+
+/// A search for only suggestions, as the `_suggest` endpoint was removed in
+/// ElasticSearch 5.0
+#[derive(Debug, Serialize)]
+struct SuggestBody<'b> {
+    size: u64,
+    suggest: &'b Suggest,
+}
+
+#[derive(Debug, Deserialize)]
+struct SuggestOnlyResult {
+    suggest: Option<Value>,
+}
+
+impl<'a, 'b, C> SuggestOperation<'a, 'b, C> {
+    pub fn new(client: &'a C, suggest: &'b Suggest) -> Self {
+        SuggestOperation {
+            client,
+            indexes: &[],
+            suggest,
+            retry_policy: None,
+        }
+    }
+
+    pub fn with_indexes(&mut self, indexes: &'b [&'b str]) -> &mut Self {
+        self.indexes = indexes;
+        self
+    }
+
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&mut self, retry_policy: &'b RetryPolicy) -> &mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    fn url(&self) -> String {
+        format!("/{}/_search", format_indexes_and_types(self.indexes, &[]))
+    }
+
+    fn body(&self) -> SuggestBody<'b> {
+        SuggestBody {
+            size: 0,
+            suggest: self.suggest,
+        }
+    }
+
+    fn read_result<R: EsResponse>(&self, response: R) -> Result<SuggestResult, EsError> {
+        match response.status_code() {
+            StatusCode::OK => {
+                let result: SuggestOnlyResult = response.read_response()?;
+                match read_suggest(Some(self.suggest), &result.suggest)? {
+                    Some(suggest) => Ok(suggest),
+                    None => Err(EsError::EsError("Expecting suggest".to_owned())),
+                }
+            }
+            _ => Err(unexpected_status(response)),
+        }
+    }
+}
+
+impl<'a, 'b> SuggestOperation<'a, 'b> {
+    pub fn send(&mut self) -> Result<SuggestResult, EsError> {
+        let response = self.client.post_body_op_with_retry(
+            &self.url(),
+            &self.body(),
+            Retry::from(self.retry_policy),
+        )?;
+        self.read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> SuggestOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&mut self) -> Result<SuggestResult, EsError> {
+        let response = self
+            .client
+            .post_body_op_with_retry(&self.url(), &self.body(), Retry::from(self.retry_policy))
+            .await?;
+        self.read_result(response)
+    }
+}
+
+impl Client {
+    /// Suggestions, without searching
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-suggesters.html
+    pub fn suggest<'a, 'b>(&'a self, suggest: &'b Suggest) -> SuggestOperation<'a, 'b> {
+        SuggestOperation::new(self, suggest)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Suggestions, without searching
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-suggesters.html
+    pub fn suggest<'a, 'b>(
+        &'a self,
+        suggest: &'b Suggest,
+    ) -> SuggestOperation<'a, 'b, AsyncClient> {
+        SuggestOperation::new(self, suggest)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        query::Fuzziness,
+        tests::{make_mock_client, request_body},
+        transport::MockTransport,
+    };
+
+    use super::{
+        Collate, CompletionContext, CompletionFuzzy, DirectGenerator, Suggest, SuggestMode,
+        SuggestSort, Suggester,
+    };
+
+    #[test]
+    fn test_suggest_serialization() {
+        let suggest = Suggest::new()
+            .with_text("rsut elastc")
+            .add(
+                "terms",
+                Suggester::build_term("title")
+                    .with_sort(SuggestSort::Frequency)
+                    .with_suggest_mode(SuggestMode::Popular)
+                    .build(),
+            )
+            .add(
+                "phrase",
+                Suggester::build_phrase("title.trigram")
+                    .with_text("rsut elastc")
+                    .with_size(1u64)
+                    .with_highlight("<em>", "</em>")
+                    .with_collate(
+                        Collate::new(json!({"match": {"{{field}}": "{{suggestion}}"}}))
+                            .add_param("field", "title")
+                            .with_prune(true),
+                    )
+                    .add_direct_generator(
+                        DirectGenerator::new("title.trigram")
+                            .with_suggest_mode(SuggestMode::Always),
+                    )
+                    .build(),
+            )
+            .add(
+                "complete",
+                Suggester::build_completion("suggest")
+                    .with_prefix("rus")
+                    .with_skip_duplicates(true)
+                    .with_fuzzy(CompletionFuzzy::new().with_fuzziness(Fuzziness::Auto))
+                    .add_context("kind", CompletionContext::category("crate"))
+                    .add_context(
+                        "kind",
+                        CompletionContext::category("lib")
+                            .with_prefix(true)
+                            .with_boost(2.0),
+                    )
+                    .build(),
+            );
+        assert_eq!(
+            json!({
+                "text": "rsut elastc",
+                "complete": {
+                    "prefix": "rus",
+                    "completion": {
+                        "field": "suggest",
+                        "skip_duplicates": true,
+                        "fuzzy": {"fuzziness": "auto"},
+                        "contexts": {"kind": [
+                            {"context": "crate"},
+                            {"context": "lib", "boost": 2.0, "prefix": true}
+                        ]}
+                    }
+                },
+                "phrase": {
+                    "text": "rsut elastc",
+                    "phrase": {
+                        "field": "title.trigram",
+                        "size": 1,
+                        "highlight": {"pre_tag": "<em>", "post_tag": "</em>"},
+                        "collate": {
+                            "query": {"source": {"match": {"{{field}}": "{{suggestion}}"}}},
+                            "params": {"field": "title"},
+                            "prune": true
+                        },
+                        "direct_generator": [
+                            {"field": "title.trigram", "suggest_mode": "always"}
+                        ]
+                    }
+                },
+                "terms": {
+                    "term": {"field": "title", "sort": "frequency", "suggest_mode": "popular"}
+                }
+            }),
+            serde_json::to_value(&suggest).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_suggest_in_search() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_search",
+            200,
+            r#"{"took": 1, "timed_out": false,
+                "_shards": {"total": 1, "successful": 1, "failed": 0},
+                "hits": {"total": 0, "hits": []},
+                "suggest": {
+                    "terms": [{"text": "rsut", "offset": 0, "length": 4,
+                               "options": [{"text": "rust", "score": 0.75, "freq": 3}]}],
+                    "phrase": [{"text": "rsut", "offset": 0, "length": 4,
+                                "options": [{"text": "rust", "highlighted": "<em>rust</em>",
+                                             "score": 0.5, "collate_match": true}]}]
+                }}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let suggest = Suggest::new()
+            .with_text("rsut")
+            .add("terms", Suggester::build_term("title").build())
+            .add("phrase", Suggester::build_phrase("title").build());
+        let result = client
+            .search_query()
+            .with_indexes(&["test_idx"])
+            .with_suggest(&suggest)
+            .send::<Value>()
+            .unwrap();
+        let suggestions = result.suggest.unwrap();
+
+        let terms = suggestions.get("terms").unwrap().as_term().unwrap();
+        assert_eq!("rsut", terms[0].text);
+        assert_eq!("rust", terms[0].options[0].text);
+        assert_eq!(3, terms[0].options[0].freq);
+
+        let phrase = suggestions.get("phrase").unwrap().as_phrase().unwrap();
+        assert_eq!(
+            Some("<em>rust</em>"),
+            phrase[0].options[0].highlighted.as_deref()
+        );
+        assert_eq!(Some(true), phrase[0].options[0].collate_match);
+        assert!(suggestions.get("phrase").unwrap().as_term().is_err());
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!("rsut", body["suggest"]["text"]);
+    }
+
+    #[test]
+    fn test_suggest_standalone() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_search",
+            200,
+            r#"{"took": 1, "timed_out": false,
+                "_shards": {"total": 1, "successful": 1, "failed": 0},
+                "hits": {"total": 0, "hits": []},
+                "suggest": {
+                    "complete": [{"text": "rus", "offset": 0, "length": 3, "options": [
+                        {"text": "rust", "_index": "test_idx", "_type": "test_type",
+                         "_id": "1", "_score": 2.0, "_source": {"str_field": "rust"},
+                         "contexts": {"kind": ["crate"]}}
+                    ]}]
+                }}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let suggest = Suggest::new().add(
+            "complete",
+            Suggester::build_completion("suggest")
+                .with_prefix("rus")
+                .build(),
+        );
+        let suggestions = client
+            .suggest(&suggest)
+            .with_indexes(&["test_idx"])
+            .send()
+            .unwrap();
+        let complete = suggestions
+            .get("complete")
+            .unwrap()
+            .as_completion()
+            .unwrap();
+        let option = &complete[0].options[0];
+        assert_eq!("rust", option.text);
+        assert_eq!(2.0, option.score);
+        assert_eq!(Some("1"), option.id.as_deref());
+        assert_eq!(Some(json!({"str_field": "rust"})), option.source);
+        assert_eq!(vec!["crate"], option.contexts.as_ref().unwrap()["kind"]);
+        assert!(suggestions.get("missing").is_err());
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(0, body["size"]);
+        assert_eq!(
+            json!({"complete": {"prefix": "rus", "completion": {"field": "suggest"}}}),
+            body["suggest"]
+        );
+    }
+}
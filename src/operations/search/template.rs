@@ -0,0 +1,430 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of Mustache search templates.
+//!
+//! A template is either stored, see `Client::put_search_template`, or given
+//! inline; it is rendered into a search with the given parameters.  Requires
+//! ElasticSearch 5.6 or later, both for stored templates, which are stored
+//! scripts, and for inline templates, which are given as `source`.
+//!
+//! See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html
+
+use reqwest::StatusCode;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::{unexpected_status, EsError},
+    json::ShouldSkip,
+    operations::{format_indexes_and_types, script::StoredScript, GenericResult},
+    retry::{Retry, RetryPolicy},
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{aggregations::Aggregations, read_aggs, SearchResult, SearchResultInterim};
+
+/// The language of search templates
+const MUSTACHE: &str = "mustache";
+
+/// A search template, and the parameters with which to render it
+#[derive(Debug, Serialize)]
+pub struct SearchTemplate {
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    source: Option<Value>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    params: Map<String, Value>,
+}
+
+impl SearchTemplate {
+    /// The template stored with the given ID
+    pub fn stored<S: Into<String>>(id: S) -> SearchTemplate {
+        SearchTemplate {
+            id: Some(id.into()),
+            source: None,
+            params: Map::new(),
+        }
+    }
+
+    /// An inline template, either a JSON object or, for templates which are
+    /// not valid JSON until rendered, a string
+    pub fn inline<V: Into<Value>>(source: V) -> SearchTemplate {
+        SearchTemplate {
+            id: None,
+            source: Some(source.into()),
+            params: Map::new(),
+        }
+    }
+
+    pub fn add_param<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RenderSearchTemplateResult {
+    template_output: Value,
+}
+
+fn read_render_result<R: EsResponse>(response: R) -> Result<Value, EsError> {
+    match response.status_code() {
+        StatusCode::OK => {
+            let result: RenderSearchTemplateResult = response.read_response()?;
+            Ok(result.template_output)
+        }
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+/// The template as a stored script, templates which are JSON objects are
+/// stored as their JSON
+fn template_script(template: &Value) -> StoredScript {
+    match template {
+        Value::String(ref source) => StoredScript::new(MUSTACHE, source.as_str()),
+        _ => StoredScript::new(MUSTACHE, template.to_string()),
+    }
+}
+
+/// A search using a template, see `Client::search_template`
+#[derive(Debug)]
+pub struct SearchTemplateOperation<'a, 'b, C = Client> {
+    /// The HTTP client
+    client: &'a C,
+
+    /// The indexes to search
+    indexes: &'b [&'b str],
+
+    /// The types to search
+    doc_types: &'b [&'b str],
+
+    /// The template and its parameters
+    template: &'b SearchTemplate,
+
+    /// The aggregations of the template, to parse their results
+    aggs: Option<&'b Aggregations<'b>>,
+
+    /// Overrides the client's retry policy
+    retry_policy: Option<&'b RetryPolicy>,
+}
+
+impl<'a, 'b, C> SearchTemplateOperation<'a, 'b, C> {
+    pub fn new(client: &'a C, template: &'b SearchTemplate) -> Self {
+        SearchTemplateOperation {
+            client,
+            indexes: &[],
+            doc_types: &[],
+            template,
+            aggs: None,
+            retry_policy: None,
+        }
+    }
+
+    pub fn with_indexes(&mut self, indexes: &'b [&'b str]) -> &mut Self {
+        self.indexes = indexes;
+        self
+    }
+
+    pub fn with_types(&mut self, doc_types: &'b [&'b str]) -> &mut Self {
+        self.doc_types = doc_types;
+        self
+    }
+
+    /// The aggregations the template renders, as these determine how their
+    /// results are parsed.  Without these the results of any aggregations are
+    /// ignored.
+    pub fn with_aggs(&mut self, aggs: &'b Aggregations) -> &mut Self {
+        self.aggs = Some(aggs);
+        self
+    }
+
+    /// Override the client's retry policy
+    pub fn with_retry_policy(&mut self, retry_policy: &'b RetryPolicy) -> &mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "/{}/_search/template",
+            format_indexes_and_types(self.indexes, self.doc_types)
+        )
+    }
+
+    fn read_result<R, T>(&self, response: R) -> Result<SearchResult<T>, EsError>
+    where
+        R: EsResponse,
+        T: DeserializeOwned,
+    {
+        match response.status_code() {
+            StatusCode::OK => {
+                let interim: SearchResultInterim<T> = response.read_response()?;
+                let aggs = match self.aggs {
+                    Some(aggs) => read_aggs(Some(aggs), &interim.aggs)?,
+                    None => None,
+                };
+                let mut result = interim.finalize();
+                result.aggs = aggs;
+                Ok(result)
+            }
+            _ => Err(unexpected_status(response)),
+        }
+    }
+}
+
+impl<'a, 'b> SearchTemplateOperation<'a, 'b> {
+    pub fn send<T>(&mut self) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.client.post_body_op_with_retry(
+            &self.url(),
+            self.template,
+            Retry::from(self.retry_policy),
+        )?;
+        self.read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> SearchTemplateOperation<'a, 'b, AsyncClient> {
+    pub async fn send<T>(&mut self) -> Result<SearchResult<T>, EsError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self
+            .client
+            .post_body_op_with_retry(&self.url(), self.template, Retry::from(self.retry_policy))
+            .await?;
+        self.read_result(response)
+    }
+}
+
+impl Client {
+    /// Store a search template with the given ID, the template is either a
+    /// JSON object or a string.  Requires ElasticSearch 5.6 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html
+    pub fn put_search_template(
+        &self,
+        id: &str,
+        template: &Value,
+    ) -> Result<GenericResult, EsError> {
+        self.put_script(id, &template_script(template))
+    }
+
+    /// Search using a template.  Requires ElasticSearch 5.6 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html
+    pub fn search_template<'a, 'b>(
+        &'a self,
+        template: &'b SearchTemplate,
+    ) -> SearchTemplateOperation<'a, 'b> {
+        SearchTemplateOperation::new(self, template)
+    }
+
+    /// The search which a template renders, without running it
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html
+    pub fn render_search_template(&self, template: &SearchTemplate) -> Result<Value, EsError> {
+        read_render_result(self.post_body_op("/_render/template", template)?)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Store a search template with the given ID, the template is either a
+    /// JSON object or a string.  Requires ElasticSearch 5.6 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html
+    pub async fn put_search_template(
+        &self,
+        id: &str,
+        template: &Value,
+    ) -> Result<GenericResult, EsError> {
+        self.put_script(id, &template_script(template)).await
+    }
+
+    /// Search using a template.  Requires ElasticSearch 5.6 or later.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html
+    pub fn search_template<'a, 'b>(
+        &'a self,
+        template: &'b SearchTemplate,
+    ) -> SearchTemplateOperation<'a, 'b, AsyncClient> {
+        SearchTemplateOperation::new(self, template)
+    }
+
+    /// The search which a template renders, without running it
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html
+    pub async fn render_search_template(
+        &self,
+        template: &SearchTemplate,
+    ) -> Result<Value, EsError> {
+        read_render_result(self.post_body_op("/_render/template", template).await?)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        operations::search::aggregations::{bucket::Terms, Aggregations},
+        tests::{make_mock_client, request_body},
+        transport::MockTransport,
+    };
+
+    use super::SearchTemplate;
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
+
+    #[test]
+    fn test_put_search_template() {
+        let transport = MockTransport::new().with_response(
+            Method::PUT,
+            "/_scripts/by_field",
+            200,
+            r#"{"acknowledged": true}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let template = json!({"query": {"match": {"{{field}}": "{{value}}"}}});
+        client.put_search_template("by_field", &template).unwrap();
+        client
+            .put_search_template("by_field", &json!("{\"size\": {{size}}}"))
+            .unwrap();
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!("mustache", body["script"]["lang"]);
+        let source: Value =
+            serde_json::from_str(body["script"]["source"].as_str().unwrap()).unwrap();
+        assert_eq!(template, source);
+        assert_eq!(
+            "{\"size\": {{size}}}",
+            request_body(&transport.requests()[1])["script"]["source"]
+        );
+    }
+
+    #[test]
+    fn test_search_template() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_search/template",
+            200,
+            r#"{"took": 1, "timed_out": false,
+                "_shards": {"total": 1, "successful": 1, "failed": 0},
+                "hits": {"total": 1, "hits": [{"_index": "test_idx", "_type": "test_type",
+                         "_id": "1", "_source": {"str_field": "a"}}]},
+                "aggregations": {"str": {
+                    "doc_count_error_upper_bound": 0, "sum_other_doc_count": 0,
+                    "buckets": [{"key": "a", "doc_count": 1}]
+                }}}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let template = SearchTemplate::stored("by_field")
+            .add_param("field", "str_field")
+            .add_param("value", "a");
+        let result = client
+            .search_template(&template)
+            .with_indexes(&["test_idx"])
+            .send::<Value>()
+            .unwrap();
+        assert_eq!(1, result.hits.total);
+        assert!(result.aggs.is_none());
+
+        let aggs = Aggregations::from(("str", Terms::field("str_field")));
+        let result = client
+            .search_template(&template)
+            .with_indexes(&["test_idx"])
+            .with_aggs(&aggs)
+            .send::<Value>()
+            .unwrap();
+        let buckets = &result
+            .aggs_ref()
+            .unwrap()
+            .get("str")
+            .unwrap()
+            .as_terms()
+            .unwrap()
+            .buckets;
+        assert_eq!(1, buckets[0].doc_count);
+
+        assert_eq!(
+            json!({"id": "by_field", "params": {"field": "str_field", "value": "a"}}),
+            request_body(&transport.requests()[0])
+        );
+    }
+
+    #[test]
+    fn test_render_search_template() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/_render/template",
+            200,
+            r#"{"template_output": {"size": 10}}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let template = SearchTemplate::inline(json!({"size": "{{size}}"})).add_param("size", 10);
+        let rendered = client.render_search_template(&template).unwrap();
+        assert_eq!(json!({"size": 10}), rendered);
+
+        assert_eq!(
+            json!({"source": {"size": "{{size}}"}, "params": {"size": 10}}),
+            request_body(&transport.requests()[0])
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_search_template_async() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/_search/template",
+            200,
+            r#"{"took": 1, "timed_out": false,
+                "_shards": {"total": 1, "successful": 1, "failed": 0},
+                "hits": {"total": 0, "hits": []}}"#,
+        );
+        let client = make_mock_async_client(&transport);
+
+        let template = SearchTemplate::stored("by_field").add_param("value", "a");
+        let result = block_on(
+            client
+                .search_template(&template)
+                .with_indexes(&["test_idx"])
+                .send::<Value>(),
+        )
+        .unwrap();
+        assert_eq!(0, result.hits.total);
+    }
+}
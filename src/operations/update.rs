@@ -0,0 +1,695 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of the Update API
+
+use std::collections::HashMap;
+use std::fmt;
+
+use reqwest::StatusCode;
+
+use serde::{Deserialize, Serialize};
+
+use serde_json::Value;
+
+use crate::{
+    error::{unexpected_status, EsError},
+    json::ShouldSkip,
+    query::Query,
+    units::JsonVal,
+    Client, EsResponse,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
+use super::{
+    common::{OptionVal, Options},
+    delete::read_by_query_result,
+    format_indexes_and_types, ByQueryResult, ShardCountResult,
+};
+
+/// A script to update a document with
+#[derive(Debug, Default, Serialize)]
+pub struct Script {
+    inline: String,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    lang: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    params: HashMap<String, JsonVal>,
+}
+
+impl Script {
+    pub fn new<S: Into<String>>(inline: S) -> Script {
+        Script {
+            inline: inline.into(),
+            ..Default::default()
+        }
+    }
+
+    add_field!(with_lang, lang, String);
+
+    pub fn with_params<A>(mut self, params: A) -> Self
+    where
+        A: IntoIterator<Item = (String, JsonVal)>,
+    {
+        self.params.extend(params);
+        self
+    }
+
+    pub fn add_param<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<JsonVal>,
+    {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// The body of an update, either a partial document or a script, and what to
+/// do if the document does not exist.
+///
+/// The partial document is a JSON `Value`, as it is rarely of the same type
+/// as the whole document, the upsert document is of the document type `E`.
+///
+/// This is used directly by bulk update actions, see
+/// [`Action::update`](../bulk/struct.Action.html#method.update).
+#[derive(Debug, Serialize)]
+pub struct Update<E> {
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    doc: Option<Value>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    doc_as_upsert: Option<bool>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    script: Option<Script>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    scripted_upsert: Option<bool>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    upsert: Option<E>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    detect_noop: Option<bool>,
+}
+
+impl<E> Default for Update<E> {
+    fn default() -> Update<E> {
+        Update {
+            doc: None,
+            doc_as_upsert: None,
+            script: None,
+            scripted_upsert: None,
+            upsert: None,
+            detect_noop: None,
+        }
+    }
+}
+
+impl<E> Update<E> {
+    /// Merge a partial document into the existing document
+    pub fn doc(doc: Value) -> Update<E> {
+        Update {
+            doc: Some(doc),
+            ..Default::default()
+        }
+    }
+
+    /// Update the existing document with a script
+    pub fn script(script: Script) -> Update<E> {
+        Update {
+            script: Some(script),
+            ..Default::default()
+        }
+    }
+
+    add_field!(with_doc_as_upsert, doc_as_upsert, bool);
+    add_field!(with_scripted_upsert, scripted_upsert, bool);
+    add_field!(with_detect_noop, detect_noop, bool);
+
+    /// The document to index if the document does not already exist
+    pub fn with_upsert(mut self, upsert: E) -> Self {
+        self.upsert = Some(upsert);
+        self
+    }
+}
+
+/// A document of any type, which is serialized when the operation is sent
+trait ToJson {
+    fn to_json(&self) -> Result<Value, EsError>;
+}
+
+impl<T: Serialize> ToJson for T {
+    fn to_json(&self) -> Result<Value, EsError> {
+        Ok(serde_json::to_value(self)?)
+    }
+}
+
+impl<'a> fmt::Debug for dyn ToJson + 'a {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.to_json() {
+            Ok(value) => fmt::Display::fmt(&value, f),
+            Err(err) => write!(f, "<{}>", err),
+        }
+    }
+}
+
+/// An update operation, to update part of a document by ID
+#[derive(Debug)]
+pub struct UpdateOperation<'a, 'b, C = Client> {
+    /// The HTTP client that this operation will use
+    client: &'a C,
+
+    /// The index of the document
+    index: &'b str,
+
+    /// The type of the document
+    doc_type: &'b str,
+
+    /// The ID of the document
+    id: &'b str,
+
+    /// The optional options
+    options: Options<'b>,
+
+    /// The partial document
+    doc: Option<&'b dyn ToJson>,
+
+    /// The document to index if there is no existing document
+    upsert: Option<&'b dyn ToJson>,
+
+    /// The script, and other options
+    body: Update<Value>,
+}
+
+impl<'a, 'b, C> UpdateOperation<'a, 'b, C> {
+    pub fn new(
+        client: &'a C,
+        index: &'b str,
+        doc_type: &'b str,
+        id: &'b str,
+    ) -> UpdateOperation<'a, 'b, C> {
+        UpdateOperation {
+            client,
+            index,
+            doc_type,
+            id,
+            options: Options::default(),
+            doc: None,
+            upsert: None,
+            body: Update::default(),
+        }
+    }
+
+    /// The partial document to merge into the existing document
+    pub fn with_doc<D: Serialize>(&'b mut self, doc: &'b D) -> &'b mut Self {
+        self.doc = Some(doc);
+        self
+    }
+
+    /// Use the partial document as the new document if there is no existing
+    /// document
+    pub fn with_doc_as_upsert(&'b mut self, doc_as_upsert: bool) -> &'b mut Self {
+        self.body.doc_as_upsert = Some(doc_as_upsert);
+        self
+    }
+
+    /// The script to update the existing document with
+    pub fn with_script(&'b mut self, script: Script) -> &'b mut Self {
+        self.body.script = Some(script);
+        self
+    }
+
+    /// Run the script even if there is no existing document, starting with
+    /// the upsert document
+    pub fn with_scripted_upsert(&'b mut self, scripted_upsert: bool) -> &'b mut Self {
+        self.body.scripted_upsert = Some(scripted_upsert);
+        self
+    }
+
+    /// The document to index if there is no existing document, this need not
+    /// be of the same type as the partial document
+    pub fn with_upsert<U: Serialize>(&'b mut self, upsert: &'b U) -> &'b mut Self {
+        self.upsert = Some(upsert);
+        self
+    }
+
+    /// Whether to skip the update if the partial document would not change
+    /// the existing document
+    pub fn with_detect_noop(&'b mut self, detect_noop: bool) -> &'b mut Self {
+        self.body.detect_noop = Some(detect_noop);
+        self
+    }
+
+    add_option!(with_retry_on_conflict, "retry_on_conflict");
+    add_option!(with_version, "version");
+    add_option!(with_version_type, "version_type");
+    add_option!(with_routing, "routing");
+    add_option!(with_parent, "parent");
+    add_option!(with_refresh, "refresh");
+    add_option!(with_timeout, "timeout");
+    add_option!(with_consistency, "consistency");
+    add_option!(with_fields, "fields");
+    add_option!(with_source, "_source");
+
+    fn format_url(&self) -> String {
+        format!(
+            "/{}/{}/{}/_update{}",
+            self.index, self.doc_type, self.id, self.options
+        )
+    }
+
+    /// The body, with the documents serialized
+    fn body(&self) -> Result<UpdateBody<'_>, EsError> {
+        Ok(UpdateBody {
+            doc: self.doc.map(|doc| doc.to_json()).transpose()?,
+            upsert: self.upsert.map(|upsert| upsert.to_json()).transpose()?,
+            body: &self.body,
+        })
+    }
+}
+
+/// The body of an `UpdateOperation`
+#[derive(Serialize)]
+struct UpdateBody<'a> {
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    doc: Option<Value>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    upsert: Option<Value>,
+    #[serde(flatten)]
+    body: &'a Update<Value>,
+}
+
+impl<'a, 'b> UpdateOperation<'a, 'b> {
+    pub fn send(&self) -> Result<UpdateResult, EsError> {
+        let response = self
+            .client
+            .post_body_op(&self.format_url(), &self.body()?)?;
+        read_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> UpdateOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&self) -> Result<UpdateResult, EsError> {
+        let response = self
+            .client
+            .post_body_op(&self.format_url(), &self.body()?)
+            .await?;
+        read_result(response)
+    }
+}
+
+fn read_result<R: EsResponse>(response: R) -> Result<UpdateResult, EsError> {
+    match response.status_code() {
+        StatusCode::OK | StatusCode::CREATED => Ok(response.read_response()?),
+        _ => Err(unexpected_status(response)),
+    }
+}
+
+impl Client {
+    /// An update operation, to update a document with a partial document or
+    /// a script.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-update.html
+    pub fn update<'a, 'b>(
+        &'a self,
+        index: &'b str,
+        doc_type: &'b str,
+        id: &'b str,
+    ) -> UpdateOperation<'a, 'b> {
+        UpdateOperation::new(self, index, doc_type, id)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// An update operation, to update a document with a partial document or
+    /// a script.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-update.html
+    pub fn update<'a, 'b>(
+        &'a self,
+        index: &'b str,
+        doc_type: &'b str,
+        id: &'b str,
+    ) -> UpdateOperation<'a, 'b, AsyncClient> {
+        UpdateOperation::new(self, index, doc_type, id)
+    }
+}
+
+/// The updated document, if requested with `fields` or `_source`
+#[derive(Debug, Deserialize)]
+pub struct UpdateGetResult {
+    pub found: Option<bool>,
+    #[serde(rename = "_source")]
+    pub source: Option<Value>,
+    pub fields: Option<Value>,
+}
+
+/// The result of an update operation
+#[derive(Debug, Deserialize)]
+pub struct UpdateResult {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_type")]
+    pub doc_type: String,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_version")]
+    pub version: u64,
+    /// One of `created`, `updated` or `noop`, ElasticSearch 5 and later only
+    pub result: Option<String>,
+    #[serde(rename = "_shards")]
+    pub shards: Option<ShardCountResult>,
+    pub get: Option<UpdateGetResult>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct UpdateByQueryBody<'b> {
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    query: Option<&'b Query>,
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    script: Option<Script>,
+}
+
+/// Update each document matching a query, either with a script or by
+/// re-indexing it unchanged, e.g. to pick up a new mapping
+#[derive(Debug)]
+pub struct UpdateByQueryOperation<'a, 'b, C = Client> {
+    /// The HTTP client
+    client: &'a C,
+
+    /// The indexes
+    indexes: &'b [&'b str],
+
+    /// The types
+    doc_types: &'b [&'b str],
+
+    /// Optional options
+    options: Options<'b>,
+
+    /// The query and script
+    body: UpdateByQueryBody<'b>,
+}
+
+impl<'a, 'b, C> UpdateByQueryOperation<'a, 'b, C> {
+    pub fn new(client: &'a C) -> UpdateByQueryOperation<'a, 'b, C> {
+        UpdateByQueryOperation {
+            client,
+            indexes: &[],
+            doc_types: &[],
+            options: Options::default(),
+            body: UpdateByQueryBody::default(),
+        }
+    }
+
+    pub fn with_indexes(&'b mut self, indexes: &'b [&'b str]) -> &'b mut Self {
+        self.indexes = indexes;
+        self
+    }
+
+    pub fn with_types(&'b mut self, doc_types: &'b [&'b str]) -> &'b mut Self {
+        self.doc_types = doc_types;
+        self
+    }
+
+    /// Only update documents matching the query, otherwise all documents are
+    /// updated
+    pub fn with_query(&'b mut self, query: &'b Query) -> &'b mut Self {
+        self.body.query = Some(query);
+        self
+    }
+
+    pub fn with_script(&'b mut self, script: Script) -> &'b mut Self {
+        self.body.script = Some(script);
+        self
+    }
+
+    add_option!(with_conflicts, "conflicts");
+    add_option!(with_slices, "slices");
+    add_option!(with_requests_per_second, "requests_per_second");
+    add_option!(with_scroll_size, "scroll_size");
+    add_option!(with_size, "size");
+    add_option!(with_wait_for_completion, "wait_for_completion");
+    add_option!(with_pipeline, "pipeline");
+    add_option!(with_routing, "routing");
+    add_option!(with_refresh, "refresh");
+    add_option!(with_timeout, "timeout");
+
+    fn url(&self) -> String {
+        format!(
+            "/{}/_update_by_query{}",
+            format_indexes_and_types(self.indexes, self.doc_types),
+            self.options
+        )
+    }
+}
+
+impl<'a, 'b> UpdateByQueryOperation<'a, 'b> {
+    pub fn send(&self) -> Result<ByQueryResult, EsError> {
+        let response = self.client.post_body_op(&self.url(), &self.body)?;
+        read_by_query_result(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'b> UpdateByQueryOperation<'a, 'b, AsyncClient> {
+    pub async fn send(&self) -> Result<ByQueryResult, EsError> {
+        let response = self.client.post_body_op(&self.url(), &self.body).await?;
+        read_by_query_result(response)
+    }
+}
+
+impl Client {
+    /// Update each document matching a query.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-update-by-query.html
+    pub fn update_by_query(&self) -> UpdateByQueryOperation<'_, '_> {
+        UpdateByQueryOperation::new(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Update each document matching a query.
+    ///
+    /// See: https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-update-by-query.html
+    pub fn update_by_query(&self) -> UpdateByQueryOperation<'_, '_, AsyncClient> {
+        UpdateByQueryOperation::new(self)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::Method;
+
+    use serde::Serialize;
+    use serde_json::json;
+
+    use crate::{
+        operations::common::Conflicts,
+        query::Query,
+        tests::{make_mock_client, request_body, TestDocument},
+        transport::MockTransport,
+    };
+
+    use super::Script;
+
+    #[cfg(feature = "async")]
+    use crate::{async_client::tests::block_on, tests::make_mock_async_client};
+
+    #[test]
+    fn test_update_doc() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/1/_update?retry_on_conflict=3&_source=true",
+            200,
+            r#"{"_index": "test_idx", "_type": "test_type", "_id": "1", "_version": 2,
+                "result": "updated",
+                "_shards": {"total": 2, "successful": 1, "failed": 0},
+                "get": {"found": true, "_source": {"int_field": 2}}}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let doc = TestDocument::new().with_int_field(2);
+        let result = client
+            .update("test_idx", "test_type", "1")
+            .with_doc(&doc)
+            .with_doc_as_upsert(true)
+            .with_detect_noop(false)
+            .with_retry_on_conflict(3)
+            .with_source(true)
+            .send()
+            .unwrap();
+
+        assert_eq!("1", result.id);
+        assert_eq!(2, result.version);
+        assert_eq!(Some("updated".to_owned()), result.result);
+        let source = result.get.unwrap().source.unwrap();
+        assert_eq!(2, source["int_field"]);
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(2, body["doc"]["int_field"]);
+        assert_eq!(true, body["doc_as_upsert"]);
+        assert_eq!(false, body["detect_noop"]);
+        assert!(body.get("script").is_none());
+    }
+
+    #[test]
+    fn test_update_script() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/1/_update",
+            201,
+            r#"{"_index": "test_idx", "_type": "test_type", "_id": "1", "_version": 1}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let upsert = TestDocument::new();
+        let result = client
+            .update("test_idx", "test_type", "1")
+            .with_script(
+                Script::new("ctx._source.int_field += n")
+                    .with_lang("groovy")
+                    .add_param("n", 4),
+            )
+            .with_upsert(&upsert)
+            .send()
+            .unwrap();
+        assert_eq!(1, result.version);
+        assert!(result.get.is_none());
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!("ctx._source.int_field += n", body["script"]["inline"]);
+        assert_eq!("groovy", body["script"]["lang"]);
+        assert_eq!(4, body["script"]["params"]["n"]);
+        assert_eq!(1, body["upsert"]["int_field"]);
+        assert!(body.get("doc").is_none());
+    }
+
+    #[test]
+    fn test_update_doc_and_upsert() {
+        #[derive(Serialize)]
+        struct Partial {
+            int_field: i64,
+        }
+
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/1/_update",
+            200,
+            r#"{"_index": "test_idx", "_type": "test_type", "_id": "1", "_version": 3}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let partial = Partial { int_field: 5 };
+        let upsert = TestDocument::new().with_int_field(5);
+        client
+            .update("test_idx", "test_type", "1")
+            .with_doc(&partial)
+            .with_upsert(&upsert)
+            .send()
+            .unwrap();
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(json!({"int_field": 5}), body["doc"]);
+        assert_eq!("I am a test", body["upsert"]["str_field"]);
+    }
+
+    #[test]
+    fn test_update_missing_document() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/2/_update",
+            404,
+            r#"{"error": {"root_cause": [{"type": "document_missing_exception",
+                "reason": "[test_type][2]: document missing"}],
+                "type": "document_missing_exception",
+                "reason": "[test_type][2]: document missing"}, "status": 404}"#,
+        );
+        let client = make_mock_client(&transport);
+
+        let doc = TestDocument::new();
+        let err = client
+            .update("test_idx", "test_type", "2")
+            .with_doc(&doc)
+            .send()
+            .unwrap_err();
+        assert!(err.is_not_found());
+    }
+
+    #[test]
+    fn test_update_by_query() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/_update_by_query?conflicts=proceed&requests_per_second=500&scroll_size=100",
+            200,
+            r#"{"took": 12, "timed_out": false, "total": 5, "updated": 4,
+                "batches": 1, "version_conflicts": 0, "noops": 1,
+                "retries": {"bulk": 0, "search": 0}, "failures": []}"#,
+        );
+        let client = make_mock_client(&transport);
+        let query = Query::build_term("bool_field", true).build();
+
+        let result = client
+            .update_by_query()
+            .with_indexes(&["test_idx"])
+            .with_types(&["test_type"])
+            .with_query(&query)
+            .with_script(Script::new("ctx._source.int_field++"))
+            .with_conflicts(Conflicts::Proceed)
+            .with_requests_per_second(500)
+            .with_scroll_size(100)
+            .send()
+            .unwrap();
+        let stats = result.stats().unwrap();
+        assert_eq!(5, stats.total);
+        assert_eq!(4, stats.updated);
+        assert_eq!(1, stats.noops);
+        assert!(stats.failures.is_empty());
+        assert!(result.task().is_none());
+
+        let body = request_body(&transport.requests()[0]);
+        assert_eq!(true, body["query"]["term"]["bool_field"]["value"]);
+        assert_eq!("ctx._source.int_field++", body["script"]["inline"]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_update_async() {
+        let transport = MockTransport::new().with_response(
+            Method::POST,
+            "/test_idx/test_type/1/_update",
+            200,
+            r#"{"_index": "test_idx", "_type": "test_type", "_id": "1", "_version": 2,
+                "_shards": {"total": 2, "successful": 1, "failed": 0}}"#,
+        );
+        let client = make_mock_async_client(&transport);
+
+        let doc = TestDocument::new().with_int_field(2);
+        let result = block_on(
+            client
+                .update("test_idx", "test_type", "1")
+                .with_doc(&doc)
+                .send(),
+        )
+        .unwrap();
+        assert_eq!(2, result.version);
+        assert_eq!(
+            2,
+            request_body(&transport.requests()[0])["doc"]["int_field"]
+        );
+    }
+}
@@ -20,25 +20,46 @@ use serde::Deserialize;
 
 use crate::{error::EsError, Client, EsResponse};
 
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+
 #[derive(Debug)]
-pub struct VersionOperation<'a> {
-    client: &'a mut Client,
+pub struct VersionOperation<'a, C = Client> {
+    client: &'a C,
 }
 
-impl<'a> VersionOperation<'a> {
-    pub fn new(client: &'a mut Client) -> Self {
+impl<'a, C> VersionOperation<'a, C> {
+    pub fn new(client: &'a C) -> Self {
         VersionOperation { client }
     }
+}
 
+impl<'a> VersionOperation<'a> {
     pub fn send(&mut self) -> Result<VersionResult, EsError> {
         let response = self.client.get_op("/")?;
         Ok(response.read_response()?)
     }
 }
 
+#[cfg(feature = "async")]
+impl<'a> VersionOperation<'a, AsyncClient> {
+    pub async fn send(&mut self) -> Result<VersionResult, EsError> {
+        let response = self.client.get_op("/").await?;
+        response.read_response()
+    }
+}
+
 impl Client {
     /// Calls the base ES path, returning the version number
-    pub fn version(&mut self) -> VersionOperation {
+    pub fn version(&self) -> VersionOperation {
+        VersionOperation::new(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncClient {
+    /// Calls the base ES path, returning the version number
+    pub fn version(&self) -> VersionOperation<'_, AsyncClient> {
         VersionOperation::new(self)
     }
 }
@@ -72,7 +93,7 @@ pub mod tests {
 
     #[test]
     fn it_works() {
-        let mut client = make_client();
+        let client = make_client("test_version");
         let result = client.version().send().unwrap();
 
         let expected_regex = Regex::new(r"^\d\.\d\.\d+$").unwrap();
@@ -0,0 +1,417 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The set of ElasticSearch nodes a `Client` sends requests to.
+//!
+//! Requests are spread across all live nodes round-robin.  A node which fails
+//! with a connection error is marked dead and skipped, until a back-off period
+//! has passed; the back-off doubles with each consecutive failure.  After the
+//! back-off the node is pinged before being used again.
+//!
+//! The set of nodes can also be replaced entirely, see the `sniff` module.
+
+use std::cmp;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex, RwLock,
+};
+use std::time::{Duration, Instant};
+
+use reqwest::Url;
+
+/// The default time a node is considered dead after its first failure
+pub const DEFAULT_DEAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The default upper-limit for the exponential back-off of dead nodes
+pub const DEFAULT_MAX_DEAD_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// The health of a node
+#[derive(Debug, Default)]
+struct NodeState {
+    /// The number of consecutive failures
+    failures: u32,
+
+    /// When the node can next be tried, `None` for live nodes
+    dead_until: Option<Instant>,
+}
+
+/// A single ElasticSearch node
+#[derive(Debug)]
+pub(crate) struct Node {
+    url: Url,
+    state: Mutex<NodeState>,
+}
+
+impl Node {
+    fn new(url: Url) -> Self {
+        Node {
+            url,
+            state: Mutex::new(NodeState::default()),
+        }
+    }
+
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn is_alive(&self) -> bool {
+        self.state.lock().unwrap().dead_until.is_none()
+    }
+}
+
+/// A node chosen to handle a request
+#[derive(Debug)]
+pub(crate) struct Selected {
+    pub(crate) node: Arc<Node>,
+
+    /// The node was dead, but its back-off period has expired.  It should be
+    /// pinged before being used.
+    pub(crate) needs_ping: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct NodePool {
+    nodes: RwLock<Vec<Arc<Node>>>,
+    next: AtomicUsize,
+    dead_timeout: Duration,
+    max_dead_timeout: Duration,
+
+    /// How often the set of nodes should be refreshed by sniffing, if at all
+    sniff_interval: Option<Duration>,
+    /// `None` if a sniff is due regardless of the interval
+    last_sniffed: Mutex<Option<Instant>>,
+    sniffing: AtomicBool,
+}
+
+impl NodePool {
+    pub(crate) fn new(urls: Vec<Url>, dead_timeout: Duration, max_dead_timeout: Duration) -> Self {
+        assert!(!urls.is_empty(), "At least one node is required");
+        NodePool {
+            nodes: RwLock::new(
+                urls.into_iter()
+                    .map(|url| Arc::new(Node::new(url)))
+                    .collect(),
+            ),
+            next: AtomicUsize::new(0),
+            dead_timeout,
+            max_dead_timeout,
+            sniff_interval: None,
+            last_sniffed: Mutex::new(Some(Instant::now())),
+            sniffing: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn with_sniff_interval(mut self, sniff_interval: Option<Duration>) -> Self {
+        self.sniff_interval = sniff_interval;
+        self
+    }
+
+    /// Sniff before the first request is sent
+    pub(crate) fn with_initial_sniff(mut self) -> Self {
+        self.last_sniffed = Mutex::new(None);
+        self
+    }
+
+    /// The number of nodes, live or dead
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.read().unwrap().len()
+    }
+
+    /// The first node
+    pub(crate) fn first(&self) -> Arc<Node> {
+        self.nodes.read().unwrap()[0].clone()
+    }
+
+    /// The URLs of each node which is currently considered alive
+    pub(crate) fn live_urls(&self) -> Vec<Url> {
+        self.nodes
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|node| node.is_alive())
+            .map(|node| node.url.clone())
+            .collect()
+    }
+
+    /// Replace the set of nodes.  Nodes which were already known keep their
+    /// current state, so a dead node remains dead.
+    pub(crate) fn set_nodes(&self, urls: Vec<Url>) {
+        if urls.is_empty() {
+            log::warn!("Ignoring empty set of nodes");
+            return;
+        }
+        let mut nodes = self.nodes.write().unwrap();
+        let new_nodes = urls
+            .into_iter()
+            .map(|url| match nodes.iter().find(|node| node.url == url) {
+                Some(node) => node.clone(),
+                None => Arc::new(Node::new(url)),
+            })
+            .collect();
+        *nodes = new_nodes;
+    }
+
+    /// Is it time to refresh the set of nodes?  If so, the caller is
+    /// responsible for doing so and calling `sniff_done` afterwards, other
+    /// callers will see `false` in the meantime.
+    pub(crate) fn sniff_due(&self) -> bool {
+        let last_sniffed = self.last_sniffed.lock().unwrap();
+        let due = match (*last_sniffed, self.sniff_interval) {
+            (None, _) => true,
+            (Some(last_sniffed), Some(interval)) => last_sniffed.elapsed() >= interval,
+            (Some(_), None) => false,
+        };
+        due && !self.sniffing.swap(true, Ordering::AcqRel)
+    }
+
+    /// The refresh started by `sniff_due` has finished
+    pub(crate) fn sniff_done(&self) {
+        *self.last_sniffed.lock().unwrap() = Some(Instant::now());
+        self.sniffing.store(false, Ordering::Release);
+    }
+
+    /// Choose the next node to use.
+    ///
+    /// This is the next live node in round-robin order, unless a dead node has
+    /// reached the end of its back-off period first.  If every node is dead the
+    /// one which is due to be retried soonest is returned, as there is nothing
+    /// better to do.
+    pub(crate) fn next_node(&self) -> Selected {
+        let now = Instant::now();
+        let nodes = self.nodes.read().unwrap();
+        let mut live = Vec::with_capacity(nodes.len());
+        let mut soonest: Option<(Instant, &Arc<Node>)> = None;
+        for node in nodes.iter() {
+            let mut state = node.state.lock().unwrap();
+            match state.dead_until {
+                None => live.push(node),
+                Some(dead_until) if dead_until <= now => {
+                    // Hold-off other requests until the ping is complete
+                    state.dead_until = Some(now + self.dead_timeout);
+                    return Selected {
+                        node: node.clone(),
+                        needs_ping: true,
+                    };
+                }
+                Some(dead_until) => {
+                    if soonest.map(|(s, _)| dead_until < s).unwrap_or(true) {
+                        soonest = Some((dead_until, node));
+                    }
+                }
+            }
+        }
+
+        let node = if live.is_empty() {
+            soonest.expect("No nodes").1
+        } else {
+            live[self.next.fetch_add(1, Ordering::Relaxed) % live.len()]
+        };
+        Selected {
+            node: node.clone(),
+            needs_ping: false,
+        }
+    }
+
+    /// Mark a node as working
+    pub(crate) fn mark_alive(&self, node: &Node) {
+        let mut state = node.state.lock().unwrap();
+        if state.dead_until.is_some() {
+            log::info!("Node {} is alive", node.url);
+        }
+        *state = NodeState::default();
+    }
+
+    /// Mark a node as dead, it won't be used again until the back-off period
+    /// has passed.
+    pub(crate) fn mark_dead(&self, node: &Node) {
+        let mut state = node.state.lock().unwrap();
+        state.failures = state.failures.saturating_add(1);
+        let timeout = self.backoff(state.failures);
+        log::warn!(
+            "Node {} is dead, failures: {}, retrying in: {:?}",
+            node.url,
+            state.failures,
+            timeout
+        );
+        state.dead_until = Some(Instant::now() + timeout);
+    }
+
+    /// The exponential back-off after the given number of failures
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 1u32 << cmp::min(failures.saturating_sub(1), 16);
+        let timeout = self
+            .dead_timeout
+            .checked_mul(factor)
+            .unwrap_or(self.max_dead_timeout);
+        cmp::min(timeout, self.max_dead_timeout)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Duration;
+
+    use reqwest::Url;
+
+    use crate::{tests::stub_server, Client, ClientBuilder};
+
+    use super::NodePool;
+
+    fn make_pool(dead_timeout: Duration) -> NodePool {
+        let urls = vec![
+            Url::parse("http://node1:9200").unwrap(),
+            Url::parse("http://node2:9200").unwrap(),
+            Url::parse("http://node3:9200").unwrap(),
+        ];
+        NodePool::new(urls, dead_timeout, Duration::from_secs(600))
+    }
+
+    fn next_host(pool: &NodePool) -> String {
+        pool.next_node().node.url().host_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let pool = make_pool(Duration::from_secs(60));
+        let hosts: Vec<String> = (0..6).map(|_| next_host(&pool)).collect();
+        assert_eq!(
+            vec!["node1", "node2", "node3", "node1", "node2", "node3"],
+            hosts
+        );
+    }
+
+    #[test]
+    fn test_dead_nodes_skipped() {
+        let pool = make_pool(Duration::from_secs(60));
+        let node = pool.next_node().node;
+        pool.mark_dead(&node);
+
+        let hosts: Vec<String> = (0..4).map(|_| next_host(&pool)).collect();
+        assert_eq!(vec!["node3", "node2", "node3", "node2"], hosts);
+        assert_eq!(2, pool.live_urls().len());
+
+        pool.mark_alive(&node);
+        assert_eq!(3, pool.live_urls().len());
+    }
+
+    #[test]
+    fn test_resurrection() {
+        let pool = make_pool(Duration::from_millis(0));
+        let node = pool.next_node().node;
+        pool.mark_dead(&node);
+
+        // The back-off has already expired, so the dead node is due a ping
+        let selected = pool.next_node();
+        assert_eq!("node1", selected.node.url().host_str().unwrap());
+        assert!(selected.needs_ping);
+
+        // A successful ping returns it to the pool
+        pool.mark_alive(&selected.node);
+        assert_eq!(3, pool.live_urls().len());
+        assert!(!pool.next_node().needs_ping);
+    }
+
+    #[test]
+    fn test_all_dead() {
+        let pool = make_pool(Duration::from_secs(60));
+        for _ in 0..3 {
+            let node = pool.next_node().node;
+            pool.mark_dead(&node);
+        }
+        assert!(pool.live_urls().is_empty());
+
+        // All nodes are dead, so the node which is due to be retried first is
+        // used anyway
+        let selected = pool.next_node();
+        assert_eq!("node1", selected.node.url().host_str().unwrap());
+        assert!(!selected.needs_ping);
+    }
+
+    #[test]
+    fn test_set_nodes() {
+        let pool = make_pool(Duration::from_secs(60));
+        let node = pool.next_node().node;
+        pool.mark_dead(&node);
+
+        pool.set_nodes(vec![
+            Url::parse("http://node1:9200").unwrap(),
+            Url::parse("http://node4:9200").unwrap(),
+        ]);
+        assert_eq!(2, pool.len());
+
+        // node1 is still dead
+        let live = pool.live_urls();
+        assert_eq!(1, live.len());
+        assert_eq!("node4", live[0].host_str().unwrap());
+    }
+
+    #[test]
+    fn test_sniff_due() {
+        let pool = make_pool(Duration::from_secs(60));
+        assert!(!pool.sniff_due());
+
+        let pool =
+            make_pool(Duration::from_secs(60)).with_sniff_interval(Some(Duration::from_millis(0)));
+        assert!(pool.sniff_due());
+        // Only one caller sniffs at a time
+        assert!(!pool.sniff_due());
+        pool.sniff_done();
+        assert!(pool.sniff_due());
+
+        let pool =
+            make_pool(Duration::from_secs(60)).with_sniff_interval(Some(Duration::from_secs(60)));
+        assert!(!pool.sniff_due());
+
+        // Only once, when there is no interval
+        let pool = make_pool(Duration::from_secs(60)).with_initial_sniff();
+        assert!(pool.sniff_due());
+        pool.sniff_done();
+        assert!(!pool.sniff_due());
+    }
+
+    #[test]
+    fn test_failover() {
+        let live = stub_server(|_, _| (200, "{\"acknowledged\":true}".to_owned()));
+        // Nothing listens on port 1, so connections will be refused
+        let dead = "http://127.0.0.1:1";
+
+        let client: Client = ClientBuilder::new(dead)
+            .with_node(live.as_str())
+            .build()
+            .unwrap();
+        assert_eq!(2, client.live_nodes().len());
+
+        for _ in 0..4 {
+            client.post_op("/_refresh").unwrap();
+        }
+        let live_nodes = client.live_nodes();
+        assert_eq!(1, live_nodes.len());
+        assert_eq!(Url::parse(&live).unwrap(), live_nodes[0]);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let pool = make_pool(Duration::from_secs(60));
+        assert_eq!(Duration::from_secs(60), pool.backoff(1));
+        assert_eq!(Duration::from_secs(120), pool.backoff(2));
+        assert_eq!(Duration::from_secs(480), pool.backoff(4));
+        assert_eq!(Duration::from_secs(600), pool.backoff(5));
+        assert_eq!(Duration::from_secs(600), pool.backoff(100));
+
+        let pool = make_pool(Duration::from_secs(u64::MAX / 2));
+        assert_eq!(Duration::from_secs(600), pool.backoff(100));
+    }
+}
@@ -18,7 +18,7 @@
 
 use std::collections::HashMap;
 
-use serde::{Serialize, Serializer};
+use serde::{ser::SerializeMap, Serialize, Serializer};
 
 use crate::{
     json::{FieldBased, NoOuter, ShouldSkip},
@@ -45,12 +45,53 @@ pub enum Function {
 }
 
 /// ScriptScore function
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default)]
 pub struct ScriptScore {
-    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
     lang: Option<String>,
     params: HashMap<String, JsonVal>,
     inline: String,
+    id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct InlineScriptScore<'a> {
+    #[serde(skip_serializing_if = "ShouldSkip::should_skip")]
+    lang: Option<&'a String>,
+    params: &'a HashMap<String, JsonVal>,
+    inline: &'a str,
+}
+
+#[derive(Serialize)]
+struct StoredScript<'a> {
+    id: &'a str,
+    params: &'a HashMap<String, JsonVal>,
+}
+
+impl Serialize for ScriptScore {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.id {
+            Some(ref id) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(
+                    "script",
+                    &StoredScript {
+                        id,
+                        params: &self.params,
+                    },
+                )?;
+                map.end()
+            }
+            None => InlineScriptScore {
+                lang: self.lang.as_ref(),
+                params: &self.params,
+                inline: &self.inline,
+            }
+            .serialize(serializer),
+        }
+    }
 }
 
 impl Function {
@@ -63,6 +104,18 @@ impl Function {
             ..Default::default()
         }
     }
+
+    /// Score with the script stored with the given ID, see
+    /// `Client::put_script`
+    pub fn build_stored_script_score<A>(id: A) -> ScriptScore
+    where
+        A: Into<String>,
+    {
+        ScriptScore {
+            id: Some(id.into()),
+            ..Default::default()
+        }
+    }
 }
 
 impl ScriptScore {
@@ -407,7 +407,7 @@ pub mod tests {
         }
     }
 
-    pub fn setup_test_data(mut client: &mut Client, index_name: &str) {
+    pub fn setup_test_data(client: &Client, index_name: &str) {
         let mut mapping = HashMap::new();
         let mut doc = HashMap::new();
         let mut geo_field = HashMap::new();
@@ -431,7 +431,7 @@ pub mod tests {
         // failures anyway
         let _ = client.delete_index(index_name);
 
-        let result = MappingOperation::new(&mut client, index_name)
+        let result = MappingOperation::new(client, index_name)
             .with_mapping(&mapping)
             .with_settings(&settings)
             .send();
@@ -458,10 +458,10 @@ pub mod tests {
     #[test]
     fn test_geoshape_search_point() {
         let index_name = "test_geoshape_search_point";
-        let mut client = make_client();
+        let client = make_client("test_geoshape_search_point");
 
-        clean_db(&mut client, index_name);
-        setup_test_data(&mut client, index_name);
+        clean_db(&client, index_name);
+        setup_test_data(&client, index_name);
 
         let all_results: SearchResult<GeoTestDocument> = client
             .search_query()
@@ -481,10 +481,10 @@ pub mod tests {
     #[test]
     fn test_geoshape_search_polygon() {
         let index_name = "test_geoshape_search_polygon";
-        let mut client = make_client();
+        let client = make_client("test_geoshape_search_polygon");
 
-        clean_db(&mut client, index_name);
-        setup_test_data(&mut client, index_name);
+        clean_db(&client, index_name);
+        setup_test_data(&client, index_name);
 
         let all_results: SearchResult<GeoTestDocument> = client
             .search_query()
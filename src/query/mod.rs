@@ -460,6 +460,19 @@ mod tests {
                    serde_json::to_string(&function_score_query).unwrap());
     }
 
+    #[test]
+    fn test_stored_script_score() {
+        let function_score_query = Query::build_function_score()
+            .with_function(
+                Function::build_stored_script_score("this_is_a_stored_script")
+                    .add_param("A", 12)
+                    .build(),
+            )
+            .build();
+        assert_eq!("{\"function_score\":{\"functions\":[{\"script_score\":{\"script\":{\"id\":\"this_is_a_stored_script\",\"params\":{\"A\":12}}}}]}}",
+                   serde_json::to_string(&function_score_query).unwrap());
+    }
+
     #[test]
     fn test_exists_query() {
         let exists_query = Query::build_exists("name").build();
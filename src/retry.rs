@@ -0,0 +1,318 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Retrying of requests which fail for transient reasons.
+//!
+//! Only requests which are safe to repeat are retried, these are those using
+//! the `GET`, `PUT` and `DELETE` methods, and read-only operations such as
+//! searches.  A request is retried if the server responds with one of 429, 502,
+//! 503 or 504; or if the connection is reset.
+//!
+//! By default requests are not retried, a policy can be set for all requests
+//! with [`ClientBuilder::with_retry_policy`](../builder/struct.ClientBuilder.html#method.with_retry_policy),
+//! and for individual operations with their `with_retry_policy` method.
+
+use std::cmp;
+use std::time::Duration;
+
+use rand::Rng;
+
+use reqwest::StatusCode;
+
+use crate::{
+    error::{self, EsError},
+    EsResponse,
+};
+
+/// The default delay before the first retry
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The default upper-limit of the delay between retries
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// How many times, and how often, to retry a request
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rs_es::retry::RetryPolicy;
+///
+/// let policy = RetryPolicy::default()
+///     .with_max_attempts(5)
+///     .with_initial_backoff(Duration::from_millis(50));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries
+    pub fn none() -> Self {
+        RetryPolicy::default()
+    }
+
+    /// The maximum number of attempts, including the first.  The default is
+    /// one, i.e. no retries.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = cmp::max(max_attempts, 1);
+        self
+    }
+
+    /// The delay before the first retry, this doubles for each subsequent
+    /// retry.
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// The maximum delay between retries
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Randomise each delay to between half and all of its value, to prevent
+    /// many clients retrying at once.  This is enabled by default.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The maximum number of attempts, including the first
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The delay before the given retry, starting at one
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32 << cmp::min(retry.saturating_sub(1), 16);
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff);
+        let backoff = cmp::min(backoff, self.max_backoff);
+        if self.jitter && backoff > Duration::from_millis(0) {
+            let half = backoff / 2;
+            let extra = rand::thread_rng().gen_range(0, half.as_micros() as u64 + 1);
+            half + Duration::from_micros(extra)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Is the response status one which may succeed if tried again
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Should a request which had this result be tried again
+pub(crate) fn is_retryable<R: EsResponse>(result: &Result<R, EsError>) -> bool {
+    match result {
+        Ok(response) => is_retryable_status(response.status_code()),
+        Err(err) => error::is_connection_reset(err),
+    }
+}
+
+/// Whether a particular request can be retried, and with which policy
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Retry<'a> {
+    /// The request is not safe to repeat
+    Never,
+
+    /// The request is safe to repeat, using the client's policy
+    Default,
+
+    /// The request is safe to repeat, using the given policy
+    Policy(&'a RetryPolicy),
+}
+
+impl<'a> From<Option<&'a RetryPolicy>> for Retry<'a> {
+    /// For a request which is safe to repeat, optionally overriding the
+    /// client's policy
+    fn from(policy: Option<&'a RetryPolicy>) -> Retry<'a> {
+        match policy {
+            Some(policy) => Retry::Policy(policy),
+            None => Retry::Default,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+
+    use crate::{
+        operations::search::count::CountResult, query::Query, tests::stub_server, ClientBuilder,
+    };
+
+    use super::{is_retryable_status, RetryPolicy};
+
+    const COUNT_RESULT: &str =
+        "{\"count\":1,\"_shards\":{\"total\":1,\"successful\":1,\"failed\":0}}";
+
+    /// A server which responds with `status` the first `failures` times, then
+    /// succeeds.  Returns the URL, and the number of requests made.
+    fn flaky_server(status: u16, failures: usize) -> (String, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let server_count = count.clone();
+        let url = stub_server(move |_, _| {
+            if server_count.fetch_add(1, Ordering::SeqCst) < failures {
+                (status, "{\"error\":\"try again\"}".to_owned())
+            } else {
+                (200, COUNT_RESULT.to_owned())
+            }
+        });
+        (url, count)
+    }
+
+    fn quick_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::default()
+            .with_max_attempts(max_attempts)
+            .with_initial_backoff(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default()
+            .with_initial_backoff(Duration::from_millis(100))
+            .with_max_backoff(Duration::from_millis(1000))
+            .with_jitter(false);
+        assert_eq!(Duration::from_millis(100), policy.backoff(1));
+        assert_eq!(Duration::from_millis(200), policy.backoff(2));
+        assert_eq!(Duration::from_millis(800), policy.backoff(4));
+        assert_eq!(Duration::from_millis(1000), policy.backoff(5));
+        assert_eq!(Duration::from_millis(1000), policy.backoff(100));
+
+        let policy = policy.with_jitter(true);
+        for _ in 0..100 {
+            let backoff = policy.backoff(2);
+            assert!(backoff >= Duration::from_millis(100));
+            assert!(backoff <= Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn test_backoff_overflow() {
+        let policy = RetryPolicy::default()
+            .with_initial_backoff(Duration::from_secs(u64::MAX / 2))
+            .with_max_backoff(Duration::from_secs(3600))
+            .with_jitter(false);
+        assert_eq!(Duration::from_secs(3600), policy.backoff(1));
+        assert_eq!(Duration::from_secs(3600), policy.backoff(u32::MAX));
+    }
+
+    #[test]
+    fn test_retryable_status() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_retry() {
+        let (url, count) = flaky_server(503, 2);
+        let client = ClientBuilder::new(url)
+            .with_retry_policy(quick_policy(3))
+            .build()
+            .unwrap();
+
+        let result = client.count_uri().send().unwrap();
+        assert_eq!(1, result.count);
+        assert_eq!(3, count.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_retry_gives_up() {
+        let (url, count) = flaky_server(429, 5);
+        let client = ClientBuilder::new(url)
+            .with_retry_policy(quick_policy(3))
+            .build()
+            .unwrap();
+
+        assert!(client.count_uri().send().is_err());
+        assert_eq!(3, count.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_no_retry_by_default() {
+        let (url, count) = flaky_server(503, 1);
+        let client = ClientBuilder::new(url).build().unwrap();
+
+        assert!(client.count_uri().send().is_err());
+        assert_eq!(1, count.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_no_retry_when_unsafe() {
+        let (url, count) = flaky_server(503, 1);
+        let client = ClientBuilder::new(url)
+            .with_retry_policy(quick_policy(3))
+            .build()
+            .unwrap();
+
+        // A plain POST may not be safe to repeat
+        assert!(client.post_op("/_refresh").is_err());
+        assert_eq!(1, count.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_operation_policy() {
+        let (url, count) = flaky_server(502, 1);
+        let client = ClientBuilder::new(url).build().unwrap();
+
+        let policy = quick_policy(2);
+        let query = Query::build_match_all().build();
+        let result: CountResult = client
+            .count_query()
+            .with_query(&query)
+            .with_retry_policy(&policy)
+            .send()
+            .unwrap();
+        assert_eq!(1, result.count);
+        assert_eq!(2, count.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,356 @@
+/*
+ * Copyright 2019 Ben Ashford
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The transport which sends each HTTP request to a single node.
+//!
+//! By default a `Client` uses `HttpTransport`, a different `Transport` can be
+//! given with
+//! [`ClientBuilder::with_transport`](../builder/struct.ClientBuilder.html#method.with_transport).
+//! The `Client` itself is still responsible for choosing the node, retries and
+//! interpreting the response.
+//!
+//! `MockTransport` returns canned responses without a running ElasticSearch,
+//! for use in tests.
+
+use std::fmt;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE},
+    Method, StatusCode, Url,
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{error::EsError, EsResponse};
+
+/// A request to be sent to a single node
+#[derive(Clone, Debug)]
+pub struct TransportRequest {
+    method: Method,
+    url: Url,
+    body: Option<Vec<u8>>,
+}
+
+impl TransportRequest {
+    pub fn new(method: Method, url: Url, body: Option<Vec<u8>>) -> TransportRequest {
+        TransportRequest { method, url, body }
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// The full URL, including the node
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn path(&self) -> &str {
+        self.url.path()
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.url.query()
+    }
+
+    /// The JSON body, if any
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+}
+
+/// A response, with the body read in full
+#[derive(Clone, Debug)]
+pub struct TransportResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl TransportResponse {
+    pub fn new<B: Into<Vec<u8>>>(status: StatusCode, body: B) -> TransportResponse {
+        TransportResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+impl EsResponse for TransportResponse {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn read_response<R>(self) -> Result<R, EsError>
+    where
+        R: DeserializeOwned,
+    {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    fn read_text(self) -> Result<String, EsError> {
+        Ok(String::from_utf8_lossy(&self.body).into_owned())
+    }
+}
+
+/// Sends a request to a node, and reads the response.
+///
+/// An error should only be returned if there is no response, e.g. the node
+/// cannot be connected to; a response with an error status is not an error at
+/// this level.  A `std::io::Error` of kind `ConnectionRefused` is treated as
+/// the node being down, the request is then sent to the next node.
+pub trait Transport: fmt::Debug + Send + Sync {
+    fn send(&self, request: TransportRequest) -> Result<TransportResponse, EsError>;
+}
+
+/// The default transport, sending requests over HTTP
+#[derive(Clone, Debug)]
+pub struct HttpTransport {
+    http_client: reqwest::Client,
+}
+
+impl HttpTransport {
+    pub fn new(http_client: reqwest::Client) -> HttpTransport {
+        HttpTransport { http_client }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, request: TransportRequest) -> Result<TransportResponse, EsError> {
+        let TransportRequest { method, url, body } = request;
+        let mut builder = self.http_client.request(method, url.clone());
+        if !url.username().is_empty() {
+            builder = builder.basic_auth(url.username(), url.password());
+        }
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+        let mut response = builder.header(CONTENT_TYPE, "application/json").send()?;
+
+        let mut body = Vec::new();
+        response.read_to_end(&mut body)?;
+        Ok(TransportResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body,
+        })
+    }
+}
+
+/// A canned response for requests matching a method and path
+#[derive(Debug)]
+struct MockRule {
+    method: Method,
+    path: String,
+    query: Option<String>,
+    response: TransportResponse,
+}
+
+impl MockRule {
+    fn matches(&self, request: &TransportRequest) -> bool {
+        self.method == request.method
+            && self.path == request.path()
+            && match self.query {
+                Some(ref query) => Some(query.as_str()) == request.query(),
+                None => true,
+            }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    rules: Vec<MockRule>,
+    requests: Vec<TransportRequest>,
+}
+
+/// A `Transport` which returns canned responses, to test code using a `Client`
+/// without a running ElasticSearch.
+///
+/// A response is given for a method and a path, which may include a query
+/// string; if it does not the query string of the request is ignored.  If more
+/// than one response matches a request they are returned in the order they
+/// were added, the last one is then repeated.  A request with no matching
+/// response is an error.
+///
+/// Clones share the same responses, and record the same requests.
+///
+/// # Examples
+///
+/// ```
+/// use reqwest::Method;
+///
+/// use rs_es::{transport::MockTransport, Client};
+///
+/// let transport = MockTransport::new().with_response(
+///     Method::GET,
+///     "/my_index/_count",
+///     200,
+///     r#"{"count": 3, "_shards": {"total": 1, "successful": 1, "failed": 0}}"#,
+/// );
+/// let client = Client::builder("http://localhost:9200")
+///     .with_transport(transport.clone())
+///     .build()
+///     .unwrap();
+///
+/// let result = client.count_uri().with_indexes(&["my_index"]).send().unwrap();
+/// assert_eq!(3, result.count);
+/// assert_eq!(1, transport.requests().len());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> MockTransport {
+        MockTransport::default()
+    }
+
+    /// Respond to requests with the given method and path with a JSON body
+    pub fn with_response<P, B>(self, method: Method, path: P, status: u16, body: B) -> Self
+    where
+        P: AsRef<str>,
+        B: Into<String>,
+    {
+        let status = StatusCode::from_u16(status).expect("Invalid status code");
+        self.with_transport_response(
+            method,
+            path,
+            TransportResponse::new(status, body.into().into_bytes()),
+        )
+    }
+
+    /// Respond to requests with the given method and path with the given
+    /// response, e.g. to include headers
+    pub fn with_transport_response<P>(
+        self,
+        method: Method,
+        path: P,
+        response: TransportResponse,
+    ) -> Self
+    where
+        P: AsRef<str>,
+    {
+        let path = path.as_ref();
+        let (path, query) = match path.find('?') {
+            Some(idx) => (&path[..idx], Some(path[idx + 1..].to_owned())),
+            None => (path, None),
+        };
+        self.state.lock().unwrap().rules.push(MockRule {
+            method,
+            path: path.to_owned(),
+            query,
+            response,
+        });
+        self
+    }
+
+    /// The requests received so far, in order
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: TransportRequest) -> Result<TransportResponse, EsError> {
+        let mut state = self.state.lock().unwrap();
+        let matching: Vec<usize> = state
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.matches(&request))
+            .map(|(idx, _)| idx)
+            .collect();
+        let response = match matching.len() {
+            0 => {
+                return Err(EsError::EsError(format!(
+                    "No mock response for: {} {}",
+                    request.method, request.url
+                )))
+            }
+            1 => state.rules[matching[0]].response.clone(),
+            _ => state.rules.remove(matching[0]).response,
+        };
+        state.requests.push(request);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use reqwest::{Method, StatusCode, Url};
+
+    use super::{MockTransport, Transport, TransportRequest};
+
+    fn request(method: Method, url: &str) -> TransportRequest {
+        TransportRequest::new(method, Url::parse(url).unwrap(), None)
+    }
+
+    #[test]
+    fn test_mock_matching() {
+        let transport = MockTransport::new()
+            .with_response(Method::GET, "/a", 200, "{\"n\":1}")
+            .with_response(Method::GET, "/a", 200, "{\"n\":2}")
+            .with_response(Method::GET, "/b?q=1", 404, "{}");
+
+        let body = |url: &str| {
+            let response = transport.send(request(Method::GET, url)).unwrap();
+            String::from_utf8(response.body().to_vec()).unwrap()
+        };
+        assert_eq!("{\"n\":1}", body("http://localhost:9200/a"));
+        assert_eq!("{\"n\":2}", body("http://localhost:9200/a?x=y"));
+        assert_eq!("{\"n\":2}", body("http://localhost:9200/a"));
+
+        let response = transport
+            .send(request(Method::GET, "http://localhost:9200/b?q=1"))
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        assert!(transport
+            .send(request(Method::GET, "http://localhost:9200/b?q=2"))
+            .is_err());
+        assert!(transport
+            .send(request(Method::POST, "http://localhost:9200/a"))
+            .is_err());
+
+        let paths: Vec<String> = transport
+            .requests()
+            .iter()
+            .map(|request| request.path().to_owned())
+            .collect();
+        assert_eq!(vec!["/a", "/a", "/a", "/b"], paths);
+    }
+}
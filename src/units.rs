@@ -33,7 +33,7 @@ use crate::{error::EsError, operations::common::OptionVal};
 ///
 /// TODO - this list is incomplete, see: https://www.elastic.co/guide/en/elasticsearch/reference/current/common-options.html#time-units
 /// TODO - ensure deserialization works correctly
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum DurationUnit {
     Month,
     Week,
@@ -70,7 +70,7 @@ impl ToString for DurationUnit {
 /// ```
 ///
 /// TODO - implement Deserialize correctly
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Duration {
     amt: i64,
     unit: DurationUnit,